@@ -0,0 +1,286 @@
+//! Shoemake's arcball. Dragging across a virtual ball turns the molecule by
+//! twice the arc between the grabbed point and the pointer, which makes the
+//! turn depend only on where the drag starts and ends: a drag that returns to
+//! where it started undoes itself, and nothing depends on the current
+//! orientation. After release the ball can keep spinning and slow down, and
+//! it can spin on its own.
+use glam::{Quat, Vec3};
+use serde::Deserialize;
+
+/// How the molecule keeps moving when nobody is dragging it.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Motion {
+    /// Whether the molecule keeps turning after a drag is released
+    pub momentum: bool,
+    /// How quickly momentum dies away. Speed falls by a factor of e every
+    /// `1 / friction` seconds.
+    pub friction: f32,
+    /// Degrees per second to turn about the vertical axis while idle, or 0
+    /// to stay still
+    pub auto_spin: f32,
+}
+
+impl Default for Motion {
+    fn default() -> Self {
+        Motion {
+            momentum: true,
+            friction: 3.0,
+            auto_spin: 0.0,
+        }
+    }
+}
+
+/// A drag held still for longer than this, in seconds, is let go without
+/// momentum
+const RELEASE_PAUSE: f64 = 0.1;
+
+/// Below this many radians per second momentum stops
+const REST_SPEED: f32 = 0.01;
+
+/// Where a drag is, on the ball and in time.
+struct Grab {
+    point: Vec3,
+    time: f64,
+}
+
+pub struct Arcball {
+    /// Normalized
+    pub rotation: Quat,
+    /// Axis times radians per second, in view space
+    velocity: Vec3,
+    grab: Option<Grab>,
+    /// When `update` was last called, in seconds
+    updated: Option<f64>,
+}
+
+/// Maps a pointer position in normalized device coordinates onto a unit
+/// ball filling the viewport's height, or onto its rim outside of it.
+/// `aspect` is the viewport's width over its height.
+fn ball_point(pointer: (f32, f32), aspect: f32) -> Vec3 {
+    let (x, y) = (pointer.0 * aspect, pointer.1);
+    let length = x * x + y * y;
+    if length <= 1.0 {
+        Vec3::new(x, y, (1.0 - length).sqrt())
+    } else {
+        Vec3::new(x, y, 0.0) / length.sqrt()
+    }
+}
+
+/// The arcball turn for moving from `from` to `to` on the ball, as an axis
+/// scaled by the angle, or zero when they are the same.
+fn turn(from: Vec3, to: Vec3) -> Vec3 {
+    let axis = from.cross(to);
+    let sine = axis.length();
+    if sine < 1e-6 {
+        return Vec3::zero();
+    }
+    axis / sine * 2.0 * sine.atan2(from.dot(to))
+}
+
+/// Applies an axis-times-angle turn to `rotation`, renormalizing so rounding
+/// can't build up over many small turns.
+fn rotate(rotation: Quat, turn: Vec3) -> Quat {
+    let angle = turn.length();
+    if angle < 1e-9 {
+        return rotation;
+    }
+    (Quat::from_axis_angle(turn / angle, angle) * rotation).normalize()
+}
+
+impl Arcball {
+    pub fn new(rotation: Quat) -> Self {
+        Arcball {
+            rotation: rotation.normalize(),
+            velocity: Vec3::zero(),
+            grab: None,
+            updated: None,
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.grab.is_some()
+    }
+
+    /// Grabs the ball, stopping any momentum. `time` is in seconds.
+    pub fn begin(&mut self, pointer: (f32, f32), aspect: f32, time: f64) {
+        self.velocity = Vec3::zero();
+        self.grab = Some(Grab {
+            point: ball_point(pointer, aspect),
+            time,
+        });
+    }
+
+    /// Rolls the ball so the grabbed point follows the pointer.
+    pub fn drag(&mut self, pointer: (f32, f32), aspect: f32, time: f64) {
+        let grab = match self.grab.as_mut() {
+            Some(grab) => grab,
+            None => return,
+        };
+        let point = ball_point(pointer, aspect);
+        let turn = turn(grab.point, point);
+        self.rotation = rotate(self.rotation, turn);
+        let elapsed = (time - grab.time) as f32;
+        if elapsed > 0.0 {
+            self.velocity = turn / elapsed;
+        }
+        *grab = Grab { point, time };
+    }
+
+    /// Lets go of the ball, which keeps the speed of the last movement
+    /// unless the pointer was held still first.
+    pub fn end(&mut self, time: f64) {
+        if let Some(grab) = self.grab.take() {
+            if time - grab.time > RELEASE_PAUSE {
+                self.velocity = Vec3::zero();
+            }
+        }
+    }
+
+    /// Turns by an axis scaled by an angle in radians, in view space.
+    pub fn turn(&mut self, turn: Vec3) {
+        self.rotation = rotate(self.rotation, turn);
+    }
+
+    /// Stops any spinning, such as when the molecule is reset.
+    pub fn stop(&mut self) {
+        self.velocity = Vec3::zero();
+    }
+
+    /// Advances momentum and auto-spin to `time`, in seconds. Called once a
+    /// frame.
+    pub fn update(&mut self, time: f64, motion: &Motion) {
+        let elapsed = match self.updated.replace(time) {
+            Some(updated) => (time - updated).clamp(0.0, 0.25) as f32,
+            None => return,
+        };
+        if self.grab.is_some() {
+            return;
+        }
+        if motion.momentum && self.velocity.length() > REST_SPEED {
+            self.rotation = rotate(self.rotation, self.velocity * elapsed);
+            self.velocity *= (-motion.friction * elapsed).exp();
+        } else {
+            self.velocity = Vec3::zero();
+            let spin = motion.auto_spin.to_radians() * elapsed;
+            self.rotation = rotate(self.rotation, Vec3::unit_y() * spin);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether two rotations are the same, either way round
+    fn same(a: Quat, b: Quat) -> bool {
+        a.dot(b).abs() > 1.0 - 1e-6
+    }
+
+    fn spinning(auto_spin: f32) -> Motion {
+        Motion {
+            auto_spin,
+            ..Motion::default()
+        }
+    }
+
+    /// An arcball flicked upwards, turning about the X axis, let go at `time`
+    /// 0.1
+    fn flicked() -> Arcball {
+        let mut arcball = Arcball::new(Quat::identity());
+        arcball.begin((0.0, 0.0), 1.0, 0.0);
+        arcball.drag((0.0, 0.1), 1.0, 0.05);
+        arcball.end(0.1);
+        arcball
+    }
+
+    #[test]
+    fn stays_normalized_while_dragged_in_circles() {
+        let mut arcball = Arcball::new(Quat::identity());
+        arcball.begin((0.5, 0.0), 1.5, 0.0);
+        for step in 1..=3600 {
+            let angle = step as f32 / 360.0 * std::f32::consts::PI * 2.0;
+            let pointer = (0.5 * angle.cos(), 0.5 * angle.sin());
+            arcball.drag(pointer, 1.5, step as f64 / 60.0);
+        }
+        assert!((arcball.rotation.length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn keeps_turning_after_a_flick_and_slows_down() {
+        let mut arcball = flicked();
+        let motion = Motion::default();
+        let speed = arcball.velocity.length();
+        assert!(speed > REST_SPEED);
+        arcball.update(0.1, &motion);
+        let rotation = arcball.rotation;
+        arcball.update(0.2, &motion);
+        assert!(!same(arcball.rotation, rotation));
+        // Friction 3 takes off a factor of e every third of a second
+        let expected = speed * (-0.3f32).exp();
+        assert!((arcball.velocity.length() - expected).abs() < 1e-4);
+
+        for frame in 3..=300 {
+            arcball.update(frame as f64 / 10.0, &motion);
+        }
+        assert_eq!(arcball.velocity, Vec3::zero());
+        let rotation = arcball.rotation;
+        arcball.update(30.1, &motion);
+        assert_eq!(arcball.rotation, rotation);
+    }
+
+    #[test]
+    fn stops_without_momentum_or_after_a_pause() {
+        let mut arcball = flicked();
+        let still = Motion {
+            momentum: false,
+            ..Motion::default()
+        };
+        arcball.update(0.1, &still);
+        let rotation = arcball.rotation;
+        arcball.update(0.2, &still);
+        assert_eq!(arcball.rotation, rotation);
+
+        let mut arcball = Arcball::new(Quat::identity());
+        arcball.begin((0.0, 0.0), 1.0, 0.0);
+        arcball.drag((0.0, 0.1), 1.0, 0.05);
+        arcball.end(0.5);
+        assert_eq!(arcball.velocity, Vec3::zero());
+    }
+
+    #[test]
+    fn spins_about_y_only_when_at_rest() {
+        let motion = spinning(90.0);
+        let mut arcball = Arcball::new(Quat::identity());
+        arcball.update(0.0, &motion);
+        arcball.update(0.1, &motion);
+        let expected = Quat::from_rotation_y(9.0f32.to_radians());
+        assert!(same(arcball.rotation, expected));
+
+        // Not while grabbed
+        arcball.begin((0.0, 0.0), 1.0, 0.2);
+        arcball.update(0.3, &motion);
+        assert!(same(arcball.rotation, expected));
+
+        // Nor while coasting, which turns about the X axis alone
+        let mut arcball = flicked();
+        arcball.update(0.1, &motion);
+        let rotation = arcball.rotation;
+        arcball.update(0.2, &motion);
+        let (axis, _) = (arcball.rotation * rotation.conjugate()).to_axis_angle();
+        assert!((axis.x().abs() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn catches_up_at_most_a_quarter_second() {
+        let motion = spinning(90.0);
+        let mut arcball = Arcball::new(Quat::identity());
+        arcball.update(0.0, &motion);
+        arcball.update(10.0, &motion);
+        let expected = Quat::from_rotation_y(22.5f32.to_radians());
+        assert!(same(arcball.rotation, expected));
+        // Nor does time running backwards turn it back
+        arcball.update(5.0, &motion);
+        assert!(same(arcball.rotation, expected));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::constants::{INDICES, MESH};
+use crate::instances::{instance_floats, Layout};
 
 pub fn init_buffers_from_constants<G: glow::HasContext>(
     gl: &G,
@@ -48,3 +49,97 @@ pub fn init_buffers_from_constants<G: glow::HasContext>(
         vertex_array
     }
 }
+
+/// The GPU buffers holding atom, bond and line instances.
+pub struct InstanceBuffers<G: glow::HasContext> {
+    pub atoms: <G as glow::HasContext>::Buffer,
+    pub bonds: <G as glow::HasContext>::Buffer,
+    pub lines: <G as glow::HasContext>::Buffer,
+}
+
+impl<G: glow::HasContext> InstanceBuffers<G> {
+    pub fn new(gl: &G) -> Self {
+        unsafe {
+            InstanceBuffers {
+                atoms: gl.create_buffer().expect("Cannot create buffer"),
+                bonds: gl.create_buffer().expect("Cannot create buffer"),
+                lines: gl.create_buffer().expect("Cannot create buffer"),
+            }
+        }
+    }
+
+    pub fn delete(&self, gl: &G) {
+        unsafe {
+            gl.delete_buffer(self.atoms);
+            gl.delete_buffer(self.bonds);
+            gl.delete_buffer(self.lines);
+        }
+    }
+}
+
+/// Uploads floats, such as instance data built by `Instances`, into `buffer`.
+pub fn upload_floats<G: glow::HasContext>(
+    gl: &G,
+    buffer: <G as glow::HasContext>::Buffer,
+    data: &[f32],
+) {
+    unsafe {
+        let bytes =
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+        gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, bytes, glow::DYNAMIC_DRAW);
+    }
+}
+
+/// Points the per-instance attributes in `layout` at `buffer`, advancing
+/// once per instance.
+pub fn bind_instance_attributes<G: glow::HasContext>(
+    gl: &G,
+    buffer: <G as glow::HasContext>::Buffer,
+    layout: Layout,
+) {
+    let float = std::mem::size_of::<f32>() as i32;
+    let stride = instance_floats(layout) as i32 * float;
+    unsafe {
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+        let mut offset = 0;
+        for &(location, size) in layout {
+            gl.enable_vertex_attrib_array(location);
+            gl.vertex_attrib_pointer_f32(location, size, glow::FLOAT, false, stride, offset);
+            gl.vertex_attrib_divisor(location, 1);
+            offset += size * float;
+        }
+    }
+}
+
+/// Creates a vertex array holding `data` at location 0, `size` floats per
+/// vertex.
+fn init_shape_buffers<G: glow::HasContext>(
+    gl: &G,
+    data: &[f32],
+    size: i32,
+) -> <G as glow::HasContext>::VertexArray {
+    unsafe {
+        let vertex_array = gl
+            .create_vertex_array()
+            .expect("Cannot create vertex array");
+        gl.bind_vertex_array(Some(vertex_array));
+        let vbo = gl.create_buffer().unwrap();
+        upload_floats(gl, vbo, data);
+        gl.enable_vertex_attrib_array(0);
+        gl.vertex_attrib_pointer_f32(0, size, glow::FLOAT, false, 0, 0);
+        vertex_array
+    }
+}
+
+/// Creates a vertex array holding one quad, drawn as a triangle strip, with
+/// corners from -1 to 1 at location 0. Impostors are drawn on it.
+pub fn init_quad_buffers<G: glow::HasContext>(gl: &G) -> <G as glow::HasContext>::VertexArray {
+    init_shape_buffers(gl, &[-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0], 2)
+}
+
+/// Creates a vertex array holding one line, from 0 to 1 at location 0, which
+/// each line instance stretches between its two ends.
+pub fn init_segment_buffers<G: glow::HasContext>(gl: &G) -> <G as glow::HasContext>::VertexArray {
+    init_shape_buffers(gl, &[0.0, 1.0], 1)
+}
@@ -0,0 +1,475 @@
+//! The wasm-bindgen front end, with the same API as the stdweb one in
+//! `main.rs`. Build it with `wasm-pack build -- --no-default-features
+//! --features web-sys`, which also writes TypeScript typings.
+use crate::colco::{Colco, RenderSettings};
+use crate::color::ColorScheme;
+use crate::input::{self, Controller, Event, Response, TouchPhase};
+use crate::mol::{Mol, ParseError, Sdf};
+use crate::picking::Pick;
+use crate::pipeline::Pipeline;
+use crate::selection::Selection;
+use glow::HasContext as Context;
+use glow::HasRenderLoop;
+use js_sys::{Array, Date, Function, Promise, RangeError, Reflect, SyntaxError, TypeError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_wasm_bindgen::Serializer;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::str::FromStr;
+use wasm_bindgen::convert::FromWasmAbi;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    console, HtmlCanvasElement, KeyboardEvent, MouseEvent, PointerEvent, WebGl2RenderingContext,
+    WheelEvent,
+};
+
+#[wasm_bindgen(typescript_custom_section)]
+const TYPESCRIPT: &str = r#"
+export type Representation = "ball_and_stick" | "space_filling" | "licorice" | "wireframe" | "sticks";
+
+export type ColorScheme =
+  | "element" | "jmol" | "cpk" | "rasmol" | "chain" | "residue_type" | "partial_charge"
+  | { gradient: { property: string | { custom: number[] }, range?: [number, number] } };
+
+export interface Layer {
+  selection?: string;
+  representation?: Representation;
+  color_scheme?: ColorScheme;
+  atom_size?: number;
+  bond_size?: number;
+}
+
+export interface RenderSettings {
+  atom_size: number;
+  bond_size: number;
+  geometry?: "mesh" | "impostor";
+  projection?: "orthographic" | "perspective";
+  fov?: number;
+  representation?: Representation;
+  layers?: Layer[];
+  highlight?: { color?: [number, number, number], width?: number, opacity?: number };
+  motion?: { momentum?: boolean, friction?: number, auto_spin?: number };
+}
+
+export interface Atom {
+  index: number;
+  element: string;
+  x: number;
+  y: number;
+  z: number;
+  bond: number | null;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "RenderSettings")]
+    pub type RenderSettingsObject;
+    #[wasm_bindgen(typescript_type = "ColorScheme")]
+    pub type ColorSchemeObject;
+    #[wasm_bindgen(typescript_type = "(atom: Atom | null) => void")]
+    pub type AtomCallback;
+    #[wasm_bindgen(typescript_type = "string[]")]
+    pub type StringArray;
+    #[wasm_bindgen(typescript_type = "Record<string, string>")]
+    pub type StringRecord;
+}
+
+/// What callbacks are told about an atom.
+#[derive(Serialize)]
+struct Atom {
+    index: u32,
+    element: String,
+    x: f32,
+    y: f32,
+    z: f32,
+    bond: Option<u32>,
+}
+
+struct State {
+    colco: Option<Colco>,
+    sdf: Option<Sdf>,
+    color_scheme: ColorScheme,
+    render_settings: RenderSettings,
+    atom_hover: Option<Function>,
+    atom_click: Option<Function>,
+}
+
+// Wasm is single threaded, so the state is too
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State {
+        colco: None,
+        sdf: None,
+        color_scheme: ColorScheme::default(),
+        render_settings: RenderSettings::default(),
+        atom_hover: None,
+        atom_click: None,
+    });
+}
+
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> T {
+    STATE.with(|state| f(&mut state.borrow_mut()))
+}
+
+/// Reads a settings object, throwing a `TypeError` when it doesn't fit.
+fn deserialize<T: DeserializeOwned>(value: JsValue) -> Result<T, JsValue> {
+    serde_wasm_bindgen::from_value(value).map_err(|error| TypeError::new(&error.to_string()).into())
+}
+
+/// Writes a value as plain objects, arrays and nulls.
+fn serialize<T: Serialize>(value: &T) -> JsValue {
+    value
+        .serialize(&Serializer::json_compatible())
+        .unwrap_or(JsValue::NULL)
+}
+
+#[wasm_bindgen(js_name = setRenderSettings)]
+pub fn set_render_settings(render: RenderSettingsObject) -> Result<(), JsValue> {
+    let render = deserialize(render.into())?;
+    with_state(|state| state.render_settings = render);
+    Ok(())
+}
+
+/// Recolors the molecule, and any loaded later, with a scheme such as
+/// `"cpk"`, `"chain"` or `{ gradient: { property: "b_factor" } }`.
+#[wasm_bindgen(js_name = setColorScheme)]
+pub fn set_color_scheme(scheme: ColorSchemeObject) -> Result<(), JsValue> {
+    let scheme: ColorScheme = deserialize(scheme.into())?;
+    with_state(|state| {
+        if let Some(colco) = state.colco.as_mut() {
+            colco.recolor(&scheme);
+        }
+        state.color_scheme = scheme;
+    });
+    Ok(())
+}
+
+/// Reads a selection query, logging an error when it can't be read.
+fn parse_selection(query: &str) -> Option<Selection> {
+    match query.parse::<Selection>() {
+        Ok(selection) => Some(selection),
+        Err(error) => {
+            console::error_1(&format!("Invalid selection: {}", error).into());
+            None
+        }
+    }
+}
+
+/// Indices of the loaded molecule's atoms matching a selection query, such
+/// as `"chain A and resi 10-50"`. Logs an error and returns nothing when the
+/// query can't be read.
+#[wasm_bindgen(js_name = selectAtoms)]
+pub fn select_atoms(query: &str) -> Option<Vec<u32>> {
+    let selection = parse_selection(query)?;
+    let indices = with_state(|state| {
+        state
+            .colco
+            .as_ref()
+            .map_or_else(Vec::new, |colco| selection.indices(&colco.mol))
+    });
+    Some(indices.into_iter().map(|index| index as u32).collect())
+}
+
+/// Zooms and pans back to show the whole molecule.
+#[wasm_bindgen(js_name = resetCamera)]
+pub fn reset_camera() {
+    with_state(|state| {
+        if let Some(colco) = state.colco.as_mut() {
+            colco.reset_camera();
+        }
+    });
+}
+
+/// Highlights the atoms matching a selection query, and bonds between them,
+/// replacing any earlier highlight. Returns whether the query could be read.
+#[wasm_bindgen(js_name = setHighlight)]
+pub fn set_highlight(query: &str) -> bool {
+    let selection = match parse_selection(query) {
+        Some(selection) => selection,
+        None => return false,
+    };
+    with_state(|state| {
+        if let Some(colco) = state.colco.as_mut() {
+            let indices = selection.indices(&colco.mol);
+            colco.highlight(indices);
+        }
+    });
+    true
+}
+
+/// Highlights the atoms with the given indices, such as ones picked with
+/// `onAtomClick`, replacing any earlier highlight.
+#[wasm_bindgen(js_name = highlightAtoms)]
+pub fn highlight_atoms(indices: Vec<u32>) {
+    with_state(|state| {
+        if let Some(colco) = state.colco.as_mut() {
+            colco.highlight(indices.into_iter().map(|index| index as usize));
+        }
+    });
+}
+
+/// Calls `callback(atom)` when the pointer moves onto an atom or bond, and
+/// `callback(null)` when it leaves. `atom` has the atom's `index`, `element`
+/// and `x`, `y` and `z` coordinates from the file, and `bond` with the bond's
+/// index when a bond was hovered rather than an atom.
+#[wasm_bindgen(js_name = onAtomHover)]
+pub fn on_atom_hover(callback: AtomCallback) {
+    with_state(|state| state.atom_hover = callback.dyn_into().ok());
+}
+
+/// Calls `callback(atom)` when an atom or bond is clicked, with the same
+/// `atom` as `onAtomHover`.
+#[wasm_bindgen(js_name = onAtomClick)]
+pub fn on_atom_click(callback: AtomCallback) {
+    with_state(|state| state.atom_click = callback.dyn_into().ok());
+}
+
+/// Calls a callback set by `onAtomHover` or `onAtomClick`. This has to happen
+/// after the state is released, since callbacks are free to call back in.
+fn call_atom_callback(callback: Option<Function>, atom: Option<Atom>) {
+    if let Some(callback) = callback {
+        let atom = serialize(&atom);
+        if let Err(error) = callback.call1(&JsValue::NULL, &atom) {
+            console::error_1(&error);
+        }
+    }
+}
+
+fn atom(colco: &Colco, picked: Pick) -> Atom {
+    let atom = &colco.mol.atoms[picked.atom];
+    Atom {
+        index: picked.atom as u32,
+        element: atom.symbol.clone(),
+        x: atom.position.x(),
+        y: atom.position.y(),
+        z: atom.position.z(),
+        bond: picked.bond.map(|bond| bond as u32),
+    }
+}
+
+/// Seconds since the epoch, which drags and momentum are timed with.
+fn now() -> f64 {
+    Date::now() / 1000.0
+}
+
+/// Hands an event to the viewer, then calls any callbacks it set off.
+fn dispatch(event: Event) {
+    let called = with_state(|state| {
+        let colco = state.colco.as_mut()?;
+        match colco.handle(event, now()) {
+            Response::Hover(hovered) => Some((
+                state.atom_hover.clone(),
+                hovered.map(|hovered| atom(colco, hovered)),
+            )),
+            Response::Click(clicked) => {
+                Some((state.atom_click.clone(), Some(atom(colco, clicked))))
+            }
+            Response::Nothing => None,
+        }
+    });
+    if let Some((callback, atom)) = called {
+        call_atom_callback(callback, atom);
+    }
+}
+
+/// A promise rejected with a `SyntaxError` carrying `line` and `column`
+/// properties.
+fn reject_parse_error(error: ParseError) -> Promise {
+    let rejection = SyntaxError::new(&error.to_string());
+    let line = JsValue::from(error.line() as u32);
+    let column = error
+        .column()
+        .map_or(JsValue::NULL, |column| (column as u32).into());
+    let _ = Reflect::set(&rejection, &"line".into(), &line);
+    let _ = Reflect::set(&rejection, &"column".into(), &column);
+    Promise::reject(&rejection)
+}
+
+fn show_molecule(parsed: Result<Mol, ParseError>) -> Promise {
+    match parsed {
+        Ok(mol) => {
+            with_state(|state| state.colco = Some(Colco::new(mol, &state.color_scheme)));
+            Promise::resolve(&JsValue::UNDEFINED)
+        }
+        Err(error) => reject_parse_error(error),
+    }
+}
+
+/// Returns a promise that rejects with a `SyntaxError` carrying `line` and
+/// `column` properties when the molecule can't be parsed.
+#[wasm_bindgen(js_name = setMolecule)]
+pub fn set_molecule(molecule_data: &str) -> Promise {
+    show_molecule(Mol::from_str(molecule_data))
+}
+
+/// Loads a multi-record SD file and shows its first record. Records are only
+/// parsed when shown, so the returned promise only reflects the first one.
+#[wasm_bindgen(js_name = setSdf)]
+pub fn set_sdf(sdf_data: String) -> Promise {
+    let file = Sdf::new(sdf_data);
+    let shown = file.molecule(0);
+    with_state(|state| state.sdf = Some(file));
+    match shown {
+        Some(parsed) => show_molecule(parsed),
+        None => Promise::reject(&RangeError::new("The SD file has no records")),
+    }
+}
+
+/// Names of the records in the loaded SD file, in file order.
+#[wasm_bindgen(js_name = listRecords)]
+pub fn list_records() -> StringArray {
+    let names = with_state(|state| match state.sdf.as_ref() {
+        Some(file) => file
+            .records()
+            .iter()
+            .map(|record| JsValue::from_str(&record.name))
+            .collect(),
+        None => Array::new(),
+    });
+    names.unchecked_into()
+}
+
+#[wasm_bindgen(js_name = showRecord)]
+pub fn show_record(index: u32) -> Promise {
+    let parsed = with_state(|state| {
+        state
+            .sdf
+            .as_ref()
+            .and_then(|file| file.molecule(index as usize))
+    });
+    match parsed {
+        Some(parsed) => show_molecule(parsed),
+        None => Promise::reject(&RangeError::new(&format!(
+            "No SD record at index {}",
+            index
+        ))),
+    }
+}
+
+/// The `> <TAG>` data items of a record, keyed by tag.
+#[wasm_bindgen(js_name = getRecordProperties)]
+pub fn get_record_properties(index: u32) -> StringRecord {
+    let properties: HashMap<String, String> = with_state(|state| {
+        state
+            .sdf
+            .as_ref()
+            .and_then(|file| file.properties(index as usize))
+            .map(|properties| properties.into_iter().collect())
+            .unwrap_or_default()
+    });
+    serialize(&properties).unchecked_into()
+}
+
+/// Calls `handler` with each `kind` event on the canvas, for as long as the
+/// page lives.
+fn listen<E: FromWasmAbi + 'static>(
+    canvas: &HtmlCanvasElement,
+    kind: &str,
+    handler: impl FnMut(E) + 'static,
+) -> Result<(), JsValue> {
+    let handler = Closure::wrap(Box::new(handler) as Box<dyn FnMut(E)>);
+    canvas.add_event_listener_with_callback(kind, handler.as_ref().unchecked_ref())?;
+    handler.forget();
+    Ok(())
+}
+
+fn listen_for_input(canvas: &HtmlCanvasElement) -> Result<(), JsValue> {
+    let target = canvas.clone();
+    listen(canvas, "mousemove", move |event: MouseEvent| {
+        dispatch(input::web_sys::mouse_move(&target, &event));
+    })?;
+    let target = canvas.clone();
+    listen(canvas, "mousedown", move |event: MouseEvent| {
+        dispatch(input::web_sys::mouse_down(&target, &event));
+    })?;
+    let target = canvas.clone();
+    listen(canvas, "mouseup", move |event: MouseEvent| {
+        dispatch(input::web_sys::mouse_up(&target, &event));
+    })?;
+    listen(canvas, "wheel", move |event: WheelEvent| {
+        event.prevent_default();
+        dispatch(input::web_sys::wheel(&event));
+    })?;
+    // Right-dragging pans, so the menu would only get in the way
+    listen(canvas, "contextmenu", move |event: MouseEvent| {
+        event.prevent_default();
+    })?;
+    // Touches come as pointer events, which stop the browser from scrolling
+    // or zooming the page for them as well. The canvas takes focus to hear
+    // keys.
+    canvas.style().set_property("touch-action", "none")?;
+    canvas.set_tab_index(0);
+    listen(canvas, "keydown", move |event: KeyboardEvent| {
+        if let Some(key) = input::web_sys::key(&event) {
+            event.prevent_default();
+            dispatch(key);
+        }
+    })?;
+    let touches = [
+        ("pointerdown", TouchPhase::Start),
+        ("pointermove", TouchPhase::Move),
+        ("pointerup", TouchPhase::End),
+        ("pointercancel", TouchPhase::Cancel),
+    ];
+    for &(kind, phase) in touches.iter() {
+        let target = canvas.clone();
+        listen(canvas, kind, move |event: PointerEvent| {
+            if let Some(touch) = input::web_sys::touch(&target, &event, phase) {
+                if phase == TouchPhase::Start {
+                    event.prevent_default();
+                }
+                dispatch(touch);
+            }
+        })?;
+    }
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn render(element_id: &str) -> Result<(), JsValue> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("No document to render in")?;
+    let canvas: HtmlCanvasElement = document
+        .get_element_by_id(element_id)
+        .ok_or_else(|| format!("No element with the id {}", element_id))?
+        .dyn_into()?;
+    listen_for_input(&canvas)?;
+    if let Some(body) = document.body() {
+        body.append_child(&canvas)?;
+    }
+    let context: WebGl2RenderingContext = canvas
+        .get_context("webgl2")?
+        .ok_or("WebGL 2 isn't available")?
+        .dyn_into()?;
+    let gl = glow::Context::from_webgl2_context(context);
+    let render_loop = glow::RenderLoop::from_request_animation_frame();
+    // Todo: wrap unsafe around native fns only
+    unsafe {
+        let pipeline = Pipeline::new(&gl, "#version 300 es")?;
+
+        gl.clear_color(0.0, 0.0, 0.0, 0.0);
+        gl.enable(glow::CULL_FACE);
+        gl.cull_face(glow::BACK);
+        gl.enable(glow::DEPTH_TEST);
+
+        render_loop.run(move |running: &mut bool| {
+            let (width, height) = (canvas.width() as i32, canvas.height().max(1) as i32);
+            gl.viewport(0, 0, width, height);
+            gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+            // Nothing to draw until a molecule has been set successfully
+            with_state(|state| {
+                if let Some(colco) = state.colco.as_mut() {
+                    colco.camera.aspect = width as f32 / height as f32;
+                    colco.render_mol(&state.render_settings, &mut pipeline.renderer(&gl), now());
+                }
+            });
+            if !*running {
+                pipeline.delete(&gl);
+            }
+        });
+    }
+    Ok(())
+}
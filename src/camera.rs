@@ -0,0 +1,121 @@
+//! The camera the molecule is seen through, kept apart from the molecule so
+//! loading a file doesn't decide how it is viewed.
+use glam::{Mat4, Vec3};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    #[default]
+    Orthographic,
+    Perspective,
+}
+
+/// Vertical field of view cameras start with, in degrees
+pub const DEFAULT_FOV: f32 = 45.0;
+
+/// Closest the camera can zoom, as a fraction of the scene's radius
+const MINIMUM_DISTANCE: f32 = 0.05;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    /// Normalized
+    pub up: Vec3,
+    /// Vertical field of view in radians. Orthographic cameras show as much
+    /// around the target as a perspective camera would, so zooming works the
+    /// same way for both.
+    pub fov: f32,
+    pub projection: Projection,
+    /// Viewport width over height
+    pub aspect: f32,
+    /// A sphere around everything in the scene, which the clip planes are
+    /// fitted to
+    scene_center: Vec3,
+    scene_radius: f32,
+}
+
+impl Camera {
+    /// A camera looking down -Z at a sphere, from just far enough away to see
+    /// all of it.
+    pub fn framing(center: Vec3, radius: f32) -> Self {
+        let mut camera = Camera {
+            position: center + Vec3::unit_z(),
+            target: center,
+            up: Vec3::unit_y(),
+            fov: DEFAULT_FOV.to_radians(),
+            projection: Projection::Orthographic,
+            aspect: 1.0,
+            scene_center: center,
+            scene_radius: radius,
+        };
+        camera.frame(center, radius);
+        camera
+    }
+
+    /// Recenters on a sphere and moves back until it fits, keeping the
+    /// direction the camera looks in.
+    pub fn frame(&mut self, center: Vec3, radius: f32) {
+        let radius = radius.max(0.001);
+        let direction = (self.position - self.target).normalize();
+        self.scene_center = center;
+        self.scene_radius = radius;
+        self.target = center;
+        self.position = center + direction * (radius / (self.fov * 0.5).sin());
+    }
+
+    pub fn distance(&self) -> f32 {
+        (self.position - self.target).length()
+    }
+
+    /// Half the height of the view at the target, in world units.
+    fn half_height(&self) -> f32 {
+        self.distance() * (self.fov * 0.5).tan()
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.position, self.target, self.up)
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        // Clip planes hug the scene, wherever the camera has been panned to
+        let from_scene = (self.position - self.scene_center).length();
+        let far = from_scene + self.scene_radius;
+        match self.projection {
+            Projection::Orthographic => {
+                let height = self.half_height();
+                let width = height * self.aspect;
+                let near = from_scene - self.scene_radius;
+                Mat4::orthographic_rh_gl(-width, width, -height, height, near, far)
+            }
+            Projection::Perspective => {
+                let near = (from_scene - self.scene_radius).max(far * 0.001);
+                Mat4::perspective_rh_gl(self.fov, self.aspect, near, far)
+            }
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        self.projection == Projection::Orthographic
+    }
+
+    /// Moves towards the target for factors below 1 and away above it.
+    pub fn zoom(&mut self, factor: f32) {
+        let offset = self.position - self.target;
+        let distance = (offset.length() * factor).max(self.scene_radius * MINIMUM_DISTANCE);
+        self.position = self.target + offset.normalize() * distance;
+    }
+
+    /// Slides the camera so the point under the pointer follows it, given how
+    /// far the pointer moved in normalized device coordinates.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let forward = (self.target - self.position).normalize();
+        let right = forward.cross(self.up).normalize();
+        let up = right.cross(forward);
+        let height = self.half_height();
+        let shift = right * (-dx * height * self.aspect) + up * (-dy * height);
+        self.position += shift;
+        self.target += shift;
+    }
+}
@@ -0,0 +1,575 @@
+//! The viewer itself: a molecule, how it is drawn, and how it is turned,
+//! zoomed and picked. Nothing here knows which platform it runs on, which
+//! feeds it `Event`s and a `Renderer`.
+use crate::arcball::{Arcball, Motion};
+use crate::camera::{Camera, Projection, DEFAULT_FOV};
+use crate::color::ColorScheme;
+use crate::input::{Button, Controller, Event, Gesture, Gestures, Key, Response, TouchPhase};
+use crate::instances::Instances;
+use crate::mol::Mol;
+use crate::picking::{pick, Pick, Ray};
+use crate::renderer::{Renderer, View};
+use crate::representation::{Highlight, Layer, Representation, Shapes, WORLD_SCALE};
+use glam::{Mat4, Quat, Vec3};
+use serde::Deserialize;
+
+/// How far the pointer can move between pressing and releasing a button, in
+/// normalized device coordinates, for it to still count as a click rather
+/// than a drag
+const CLICK_SLOP: f32 = 0.01;
+
+/// Room left around the molecule when the camera frames it, in ångströms, so
+/// the outermost atoms' spheres fit too
+const FRAMING_MARGIN: f32 = 2.0;
+
+/// How far an arrow key turns the molecule, in radians
+const KEY_TURN: f32 = std::f32::consts::PI / 18.0;
+
+/// How far an arrow key pans with shift held, in normalized device
+/// coordinates
+const KEY_PAN: f32 = 0.1;
+
+/// How much `+` and `-` zoom by
+const KEY_ZOOM: f32 = 1.25;
+
+/// How atoms and bonds are turned into triangles.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Geometry {
+    /// Instanced sphere and cylinder meshes from `constants`
+    #[default]
+    Mesh,
+    /// Quads that ray-cast perfect spheres and cylinders
+    Impostor,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub atom_size: f32,
+    pub bond_size: f32,
+    #[serde(default)]
+    pub geometry: Geometry,
+    #[serde(default)]
+    pub projection: Projection,
+    /// Vertical field of view in degrees, 45 by default
+    #[serde(default)]
+    pub fov: Option<f32>,
+    #[serde(default)]
+    pub representation: Representation,
+    /// Parts of the molecule to draw, each in its own style. When empty the
+    /// whole molecule is drawn with the settings above.
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    /// How highlighted atoms are drawn
+    #[serde(default)]
+    pub highlight: Highlight,
+    /// Momentum after rotating, and spinning on its own
+    #[serde(default)]
+    pub motion: Motion,
+}
+
+impl RenderSettings {
+    /// Whether shapes built with `self` would come out the same as with
+    /// `other`. The projection, field of view and motion only move the
+    /// camera, so changing them needn't rebuild anything.
+    fn builds_same_shapes(&self, other: &RenderSettings) -> bool {
+        self.geometry == other.geometry
+            && self.representation == other.representation
+            && self.atom_size == other.atom_size
+            && self.bond_size == other.bond_size
+            && self.layers == other.layers
+            && self.highlight == other.highlight
+    }
+}
+
+// Lets the stdweb front end take settings straight from JavaScript
+#[cfg(feature = "std_web")]
+stdweb::js_deserializable!(RenderSettings);
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            atom_size: 2.0,
+            bond_size: 0.5,
+            geometry: Geometry::Mesh,
+            projection: Projection::Orthographic,
+            fov: None,
+            representation: Representation::BallAndStick,
+            layers: Vec::new(),
+            highlight: Highlight::default(),
+            motion: Motion::default(),
+        }
+    }
+}
+
+pub struct Colco {
+    is_mouse_down: bool,
+    /// Whether the drag in progress pans rather than rotates
+    is_panning: bool,
+    /// How far the pointer has moved since the button was pressed
+    dragged: f32,
+    /// Where the pointer was last seen, in normalized device coordinates
+    last_pointer: (f32, f32),
+    /// Turns the molecule about its centroid
+    arcball: Arcball,
+    /// The molecule's centroid, worked out once rather than every frame as
+    /// the molecule doesn't change while it's shown
+    centroid: Vec3,
+    gestures: Gestures,
+    pub camera: Camera,
+    pub mol: Mol,
+    /// Color of each atom under the current color scheme
+    colors: Vec<Vec3>,
+    /// Settings the instance buffers were built with, or `None` when they
+    /// need rebuilding
+    built_with: Option<RenderSettings>,
+    /// Number of atom, bond and line instances in the instance buffers
+    instance_counts: (i32, i32, i32),
+    /// What the instance buffers were built from, for picking
+    shapes: Shapes,
+    hovered: Option<Pick>,
+    /// Whether each atom is highlighted
+    highlighted: Vec<bool>,
+    /// Number of sphere and cylinder halos in the halo buffers
+    halo_counts: (i32, i32),
+}
+
+/// The sphere the camera frames: centered on the molecule's centroid and
+/// holding all of it, in world space.
+fn framing_sphere(mol: &Mol, centroid: Vec3) -> (Vec3, f32) {
+    let radius = mol.radius(centroid) + FRAMING_MARGIN;
+    (centroid * WORLD_SCALE, radius * WORLD_SCALE)
+}
+
+impl Colco {
+    pub fn new(mol: Mol, scheme: &ColorScheme) -> Self {
+        let centroid = mol.centroid();
+        Colco {
+            is_mouse_down: false,
+            is_panning: false,
+            dragged: 0.0,
+            last_pointer: (0.0, 0.0),
+            arcball: Arcball::new(Quat::from_xyzw(0.0, 1.0, 0.0, 0.0)),
+            centroid,
+            gestures: Gestures::default(),
+            camera: {
+                let (center, radius) = framing_sphere(&mol, centroid);
+                Camera::framing(center, radius)
+            },
+            colors: scheme.colors(&mol),
+            built_with: None,
+            instance_counts: (0, 0, 0),
+            shapes: Shapes::default(),
+            hovered: None,
+            highlighted: vec![false; mol.atoms.len()],
+            halo_counts: (0, 0),
+            mol,
+        }
+    }
+}
+
+impl Colco {
+    /// Rebuilds the shapes if the molecule, its colors or the parts of
+    /// `settings` they're built from have changed since they were last built, returning the instances to upload
+    /// for them and for their halos.
+    pub fn rebuild(&mut self, settings: &RenderSettings) -> Option<(Instances, Instances)> {
+        let built = self.built_with.as_ref();
+        if built.is_some_and(|built| built.builds_same_shapes(settings)) {
+            return None;
+        }
+        let shapes = Shapes::new(&self.mol, &self.colors, settings);
+        let instances = match settings.geometry {
+            Geometry::Mesh => Instances::meshes(&shapes),
+            Geometry::Impostor => Instances::impostors(&shapes),
+        };
+        self.instance_counts = (
+            instances.atom_count,
+            instances.bond_count,
+            instances.line_count,
+        );
+        let halos = shapes.halos(&self.mol, &self.highlighted, &settings.highlight);
+        let halos = Instances::impostors(&halos);
+        self.halo_counts = (halos.atom_count, halos.bond_count);
+        self.shapes = shapes;
+        self.built_with = Some(settings.clone());
+        Some((instances, halos))
+    }
+
+    /// Draws every atom, then every bond, then every line, with one instanced
+    /// draw call each, first rebuilding the instance buffers if needed.
+    /// `time` is in seconds, for momentum and spinning.
+    pub fn render_mol<R: Renderer>(
+        &mut self,
+        settings: &RenderSettings,
+        renderer: &mut R,
+        time: f64,
+    ) {
+        if let Some((instances, halos)) = self.rebuild(settings) {
+            renderer.upload(&instances);
+            renderer.upload_halos(&halos);
+        }
+        self.arcball.update(time, &settings.motion);
+        self.camera.projection = settings.projection;
+        self.camera.fov = settings.fov.unwrap_or(DEFAULT_FOV).to_radians();
+        let (atom_count, bond_count, line_count) = self.instance_counts;
+        let view = self.view();
+        match settings.geometry {
+            Geometry::Mesh => renderer.draw_meshes(&view, atom_count, bond_count),
+            Geometry::Impostor => renderer.draw_impostors(&view, atom_count, bond_count),
+        }
+        renderer.draw_lines(&view, line_count);
+        renderer.draw_halos(&view, self.halo_counts, settings.highlight.opacity);
+    }
+
+    /// Recolors the molecule with another scheme.
+    pub fn recolor(&mut self, scheme: &ColorScheme) {
+        self.colors = scheme.colors(&self.mol);
+        self.built_with = None;
+    }
+
+    /// Highlights exactly the atoms in `indices`, ignoring any out of range.
+    pub fn highlight(&mut self, indices: impl IntoIterator<Item = usize>) {
+        self.highlighted = vec![false; self.mol.atoms.len()];
+        for index in indices {
+            if let Some(atom) = self.highlighted.get_mut(index) {
+                *atom = true;
+            }
+        }
+        self.built_with = None;
+    }
+
+    /// The camera's view of the molecule, turned by the arcball. The camera
+    /// only ever moves, never turns, so the arcball's view space turns are
+    /// world space turns too.
+    pub fn view(&self) -> View {
+        let centroid = self.centroid * WORLD_SCALE;
+        let model = Mat4::from_translation(centroid)
+            * Mat4::from_quat(self.arcball.rotation)
+            * Mat4::from_translation(-centroid);
+        View {
+            view: self.camera.view() * model,
+            projection: self.camera.projection_matrix(),
+            orthographic: self.camera.is_orthographic(),
+        }
+    }
+
+    /// Frames the whole molecule again, undoing any zooming and panning.
+    pub fn reset_camera(&mut self) {
+        let (center, radius) = framing_sphere(&self.mol, self.centroid);
+        self.camera.frame(center, radius);
+    }
+
+    /// Turns the molecule about its centroid by an axis scaled by an angle in
+    /// radians, in view space, as the arrow keys do.
+    pub fn turn(&mut self, turn: Vec3) {
+        self.arcball.turn(turn);
+    }
+
+    /// The atom or bond under `pointer`, in normalized device coordinates,
+    /// among the shapes last drawn.
+    pub fn pick(&self, pointer: (f32, f32)) -> Option<Pick> {
+        pick(
+            &self.shapes,
+            &Ray::from_screen(&self.view(), pointer.0, pointer.1),
+        )
+    }
+
+    /// Rotates or pans while dragging, and otherwise reports what is now
+    /// under the pointer when that has changed.
+    fn on_pointer_move(&mut self, pointer: (f32, f32), time: f64) -> Response {
+        let last_pointer = std::mem::replace(&mut self.last_pointer, pointer);
+        if self.is_mouse_down {
+            let (dx, dy) = (pointer.0 - last_pointer.0, pointer.1 - last_pointer.1);
+            self.dragged += dx.abs() + dy.abs();
+            if self.is_panning {
+                self.camera.pan(dx, dy);
+            } else {
+                self.arcball.drag(pointer, self.camera.aspect, time);
+            }
+            return Response::Nothing;
+        }
+        let hovered = self.pick(pointer);
+        let target = |pick: Option<Pick>| pick.map(|pick| (pick.atom, pick.bond));
+        if target(hovered) == target(self.hovered) {
+            return Response::Nothing;
+        }
+        self.hovered = hovered;
+        Response::Hover(hovered)
+    }
+
+    /// Starts a drag, which pans with the secondary button or shift held and
+    /// rotates otherwise.
+    fn on_pointer_down(&mut self, pointer: (f32, f32), panning: bool, time: f64) {
+        self.is_mouse_down = true;
+        self.is_panning = panning;
+        self.dragged = 0.0;
+        self.last_pointer = pointer;
+        if self.is_panning {
+            self.arcball.stop();
+        } else {
+            self.arcball.begin(pointer, self.camera.aspect, time);
+        }
+    }
+
+    /// Reports what was clicked on, unless the pointer was dragged.
+    fn on_pointer_up(&mut self, pointer: (f32, f32), time: f64) -> Response {
+        let clicked = self.is_mouse_down && self.dragged < CLICK_SLOP;
+        self.is_mouse_down = false;
+        self.arcball.end(time);
+        if !clicked {
+            return Response::Nothing;
+        }
+        match self.pick(pointer) {
+            Some(picked) => Response::Click(picked),
+            None => Response::Nothing,
+        }
+    }
+
+    /// Turns with the arrow keys, or pans with shift held.
+    fn on_key(&mut self, key: Key, shift: bool) {
+        let (x, y) = match key {
+            Key::Left => (-1.0, 0.0),
+            Key::Right => (1.0, 0.0),
+            Key::Up => (0.0, 1.0),
+            Key::Down => (0.0, -1.0),
+            Key::ZoomIn => return self.camera.zoom(1.0 / KEY_ZOOM),
+            Key::ZoomOut => return self.camera.zoom(KEY_ZOOM),
+            Key::Reset => {
+                self.arcball.stop();
+                return self.reset_camera();
+            }
+        };
+        if shift {
+            self.camera.pan(x * KEY_PAN, y * KEY_PAN);
+        } else {
+            // Turning right spins about +Y, and turning up about -X
+            self.arcball.turn(Vec3::new(-y, x, 0.0) * KEY_TURN);
+        }
+    }
+
+    /// Rotates for one finger, zooms and pans for two, and recenters on a
+    /// double tap. Reports what a tap was on.
+    fn on_touch(&mut self, id: i32, phase: TouchPhase, pointer: (f32, f32), time: f64) -> Response {
+        let gestures = match phase {
+            TouchPhase::Start => self.gestures.down(id, pointer, time),
+            TouchPhase::Move => self.gestures.moved(id, pointer),
+            TouchPhase::End => self.gestures.up(id, time, false),
+            TouchPhase::Cancel => self.gestures.up(id, time, true),
+        };
+        let mut response = Response::Nothing;
+        for gesture in gestures {
+            match gesture {
+                Gesture::DragStart(pointer) => {
+                    self.arcball.begin(pointer, self.camera.aspect, time)
+                }
+                Gesture::Drag(pointer) => self.arcball.drag(pointer, self.camera.aspect, time),
+                Gesture::DragEnd => self.arcball.end(time),
+                Gesture::Pinch { zoom, pan } => {
+                    self.camera.zoom(zoom);
+                    self.camera.pan(pan.0, pan.1);
+                }
+                Gesture::Tap(pointer) => {
+                    if let Some(tapped) = self.pick(pointer) {
+                        response = Response::Click(tapped);
+                    }
+                }
+                Gesture::DoubleTap(_) => {
+                    self.arcball.stop();
+                    self.reset_camera();
+                }
+            }
+        }
+        response
+    }
+}
+
+impl Controller for Colco {
+    fn handle(&mut self, event: Event, time: f64) -> Response {
+        match event {
+            Event::PointerDown {
+                position,
+                button,
+                modifiers,
+            } => {
+                let panning = button != Button::Primary || modifiers.shift;
+                self.on_pointer_down(position, panning, time);
+                Response::Nothing
+            }
+            Event::PointerMove { position } => self.on_pointer_move(position, time),
+            Event::PointerUp { position, .. } => self.on_pointer_up(position, time),
+            // Zooms in when scrolling away, and for trackpad pinches, which
+            // browsers report as scrolling with control held
+            Event::Wheel { delta } => {
+                self.camera.zoom((delta * 0.002).exp());
+                Response::Nothing
+            }
+            Event::Key { key, modifiers } => {
+                self.on_key(key, modifiers.shift);
+                Response::Nothing
+            }
+            Event::Touch {
+                id,
+                phase,
+                position,
+            } => self.on_touch(id, phase, position, time),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Three atoms well apart, so each can be pointed at on its own
+    const SPREAD: &str = "\
+3
+
+C -2.0 0.0 0.0
+O 2.0 0.0 0.0
+H 0.0 0.0 3.0
+";
+
+    fn viewer() -> Colco {
+        let mol = Mol::from_str(SPREAD).expect("a molecule");
+        let mut colco = Colco::new(mol, &ColorScheme::default());
+        colco.rebuild(&RenderSettings::default());
+        colco
+    }
+
+    /// Where an atom is on screen, in normalized device coordinates
+    fn screen(colco: &Colco, atom: usize) -> (f32, f32) {
+        let view = colco.view();
+        let position = colco.mol.atoms[atom].position * WORLD_SCALE;
+        let clip = view.projection * view.view * position.extend(1.0);
+        (clip.x() / clip.w(), clip.y() / clip.w())
+    }
+
+    fn press(position: (f32, f32), button: Button) -> Event {
+        Event::PointerDown {
+            position,
+            button,
+            modifiers: Default::default(),
+        }
+    }
+
+    fn touch(id: i32, phase: TouchPhase, position: (f32, f32)) -> Event {
+        Event::Touch {
+            id,
+            phase,
+            position,
+        }
+    }
+
+    #[test]
+    fn clicks_on_atoms() {
+        let mut colco = viewer();
+        let carbon = screen(&colco, 0);
+        let picked = |response| match response {
+            Response::Hover(Some(pick)) | Response::Click(pick) => Some(pick.atom),
+            _ => None,
+        };
+        let hovered = colco.handle(Event::PointerMove { position: carbon }, 0.0);
+        assert_eq!(picked(hovered), Some(0));
+        let moved = colco.handle(Event::PointerMove { position: carbon }, 0.1);
+        assert_eq!(moved, Response::Nothing);
+        colco.handle(press(carbon, Button::Primary), 0.2);
+        let released = Event::PointerUp {
+            position: carbon,
+            button: Button::Primary,
+        };
+        assert_eq!(picked(colco.handle(released, 0.3)), Some(0));
+    }
+
+    #[test]
+    fn drags_to_rotate() {
+        let mut colco = viewer();
+        let carbon = screen(&colco, 0);
+        let rotation = colco.arcball.rotation;
+        colco.handle(press(carbon, Button::Primary), 0.0);
+        let moved = (carbon.0 + 0.3, carbon.1);
+        let response = colco.handle(Event::PointerMove { position: moved }, 0.05);
+        assert_eq!(response, Response::Nothing);
+        assert_ne!(colco.arcball.rotation, rotation);
+        assert_ne!(screen(&colco, 0), carbon);
+        // A drag isn't a click, even back where it started
+        let released = Event::PointerUp {
+            position: carbon,
+            button: Button::Primary,
+        };
+        assert_eq!(colco.handle(released, 0.1), Response::Nothing);
+    }
+
+    #[test]
+    fn scrolls_to_zoom() {
+        let mut colco = viewer();
+        let distance = colco.camera.distance();
+        colco.handle(Event::Wheel { delta: 100.0 }, 0.0);
+        assert!(colco.camera.distance() > distance);
+        colco.handle(Event::Wheel { delta: -200.0 }, 0.0);
+        assert!(colco.camera.distance() < distance);
+    }
+
+    #[test]
+    fn taps_and_double_taps_to_recenter() {
+        let mut colco = viewer();
+        let camera = colco.camera.clone();
+        let carbon = screen(&colco, 0);
+        colco.handle(touch(1, TouchPhase::Start, carbon), 0.0);
+        match colco.handle(touch(1, TouchPhase::End, carbon), 0.05) {
+            Response::Click(pick) => assert_eq!(pick.atom, 0),
+            response => panic!("expected a click, got {:?}", response),
+        }
+
+        colco.handle(Event::Wheel { delta: -200.0 }, 1.0);
+        colco.handle(press((0.0, 0.0), Button::Secondary), 1.0);
+        colco.handle(
+            Event::PointerMove {
+                position: (0.2, 0.1),
+            },
+            1.1,
+        );
+        assert_ne!(colco.camera, camera);
+        for &time in [2.0, 2.2].iter() {
+            colco.handle(touch(2, TouchPhase::Start, (0.9, 0.9)), time);
+            colco.handle(touch(2, TouchPhase::End, (0.9, 0.9)), time + 0.05);
+        }
+        assert!((colco.camera.distance() - camera.distance()).abs() < 1e-3);
+        assert!((colco.camera.target - camera.target).length() < 1e-3);
+    }
+
+    #[test]
+    fn pinches_to_zoom() {
+        let mut colco = viewer();
+        let distance = colco.camera.distance();
+        colco.handle(touch(1, TouchPhase::Start, (-0.1, 0.0)), 0.0);
+        colco.handle(touch(2, TouchPhase::Start, (0.1, 0.0)), 0.0);
+        colco.handle(touch(2, TouchPhase::Move, (0.3, 0.0)), 0.1);
+        assert!(colco.camera.distance() < distance);
+        // Lifting after a pinch picks nothing
+        assert_eq!(
+            colco.handle(touch(1, TouchPhase::End, (-0.1, 0.0)), 0.2),
+            Response::Nothing
+        );
+    }
+
+    #[test]
+    fn rebuilds_only_for_shape_settings() {
+        let mut colco = viewer();
+        let mut settings = RenderSettings {
+            projection: Projection::Perspective,
+            fov: Some(30.0),
+            motion: Motion {
+                auto_spin: 20.0,
+                ..Motion::default()
+            },
+            ..RenderSettings::default()
+        };
+        assert!(colco.rebuild(&settings).is_none());
+        settings.atom_size = 1.0;
+        assert!(colco.rebuild(&settings).is_some());
+        settings.representation = Representation::Licorice;
+        assert!(colco.rebuild(&settings).is_some());
+        assert!(colco.rebuild(&settings).is_none());
+    }
+}
@@ -0,0 +1,305 @@
+//! Color schemes, which pick a color for every atom of a molecule when it is
+//! drawn. Bonds are drawn in the colors of their two atoms.
+//!
+//! Schemes deserialize from JS as a name, such as `"jmol"`, or for gradients
+//! an object such as `{ "gradient": { "property": "b_factor" } }`.
+use crate::mol::{rgb, ElementData, Mol};
+use glam::Vec3;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Color of atoms a scheme has nothing to say about.
+const NEUTRAL: u32 = 0xB0B0B0;
+
+/// Tableau 10, which keeps neighbouring chains easy to tell apart.
+const CHAIN_PALETTE: [u32; 10] = [
+    0x4E79A7, 0xF28E2B, 0xE15759, 0x76B7B2, 0x59A14F, 0xEDC948, 0xB07AA1, 0xFF9DA7, 0x9C755F,
+    0xBAB0AC,
+];
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorScheme {
+    /// Colco's own element colors
+    #[default]
+    Element,
+    Cpk,
+    Jmol,
+    Rasmol,
+    Chain,
+    /// Acidic, basic, polar and hydrophobic amino acids, nucleotides, water
+    /// and everything else
+    ResidueType,
+    /// Red for negative through white to blue for positive, from partial
+    /// charges where the file has them and formal charges otherwise
+    PartialCharge,
+    /// Blue through white to red over a numeric property, spanning `range`
+    /// or the property's smallest and largest values
+    Gradient {
+        property: AtomProperty,
+        #[serde(default)]
+        range: Option<(f32, f32)>,
+    },
+}
+
+// Lets the stdweb front end take schemes straight from JavaScript
+#[cfg(feature = "std_web")]
+stdweb::js_deserializable!(ColorScheme);
+
+/// A numeric per-atom property for gradients.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AtomProperty {
+    BFactor,
+    Occupancy,
+    PartialCharge,
+    Charge,
+    Mass,
+    ResidueNumber,
+    Index,
+    /// One value per atom, in atom order
+    Custom(Vec<f32>),
+}
+
+impl AtomProperty {
+    fn value(&self, mol: &Mol, index: usize) -> Option<f32> {
+        let atom = &mol.atoms[index];
+        match self {
+            AtomProperty::BFactor => atom.b_factor,
+            AtomProperty::Occupancy => atom.occupancy,
+            AtomProperty::PartialCharge => atom.partial_charge,
+            AtomProperty::Charge => Some(atom.charge as f32),
+            AtomProperty::Mass => match atom.isotope {
+                Some(isotope) => Some(isotope as f32),
+                None => atom.element.data().map(|data| data.mass),
+            },
+            AtomProperty::ResidueNumber => atom.residue_number.map(|number| number as f32),
+            AtomProperty::Index => Some(index as f32),
+            AtomProperty::Custom(values) => values.get(index).copied(),
+        }
+    }
+}
+
+/// RasMol's CPK colors, which only distinguish the common elements.
+fn rasmol(data: &ElementData) -> u32 {
+    match data.symbol {
+        "H" => 0xFFFFFF,
+        "He" => 0xFFC0CB,
+        "Li" => 0xB22222,
+        "B" | "Cl" => 0x00FF00,
+        "C" => 0xC8C8C8,
+        "N" => 0x8F8FFF,
+        "O" => 0xF00000,
+        "F" | "Si" | "Au" => 0xDAA520,
+        "Na" => 0x0000FF,
+        "Mg" => 0x228B22,
+        "Al" | "Ca" | "Ti" | "Cr" | "Mn" | "Ag" => 0x808090,
+        "P" | "Fe" | "Ba" => 0xFFA500,
+        "S" => 0xFFC832,
+        "Ni" | "Cu" | "Zn" | "Br" => 0xA52A2A,
+        "I" => 0xA020F0,
+        _ => 0xFF1493,
+    }
+}
+
+fn residue_type(residue: &str) -> u32 {
+    match residue {
+        "ASP" | "GLU" => 0xE60A0A,
+        "LYS" | "ARG" | "HIS" => 0x145AFF,
+        "SER" | "THR" | "ASN" | "GLN" | "CYS" | "TYR" => 0x3CB44B,
+        "ALA" | "VAL" | "LEU" | "ILE" | "MET" | "MSE" | "PHE" | "TRP" | "PRO" | "GLY" => 0xE8E8E8,
+        "A" | "G" | "C" | "U" | "DA" | "DG" | "DC" | "DT" | "DU" => 0xFF8C1A,
+        "HOH" | "WAT" | "DOD" => 0x9AD0F5,
+        _ => 0xD050D0,
+    }
+}
+
+/// Interpolates blue (-1) through white (0) to red (1).
+fn diverging(value: f32) -> Vec3 {
+    let value = value.clamp(-1.0, 1.0);
+    let white = Vec3::one();
+    if value < 0.0 {
+        white.lerp(Vec3::new(0.0, 0.0, 1.0), -value)
+    } else {
+        white.lerp(Vec3::new(1.0, 0.0, 0.0), value)
+    }
+}
+
+impl ColorScheme {
+    /// Returns the color of every atom, in atom order.
+    pub fn colors(&self, mol: &Mol) -> Vec<Vec3> {
+        let by_element = |pick: fn(&ElementData) -> u32| -> Vec<Vec3> {
+            mol.atoms
+                .iter()
+                .map(|atom| match atom.element.data() {
+                    Some(data) => rgb(pick(data)),
+                    None => atom.element.color,
+                })
+                .collect()
+        };
+        match self {
+            ColorScheme::Element => mol.atoms.iter().map(|atom| atom.element.color).collect(),
+            ColorScheme::Cpk => by_element(|data| data.cpk),
+            ColorScheme::Jmol => by_element(|data| data.jmol),
+            ColorScheme::Rasmol => by_element(rasmol),
+            ColorScheme::Chain => {
+                // Chains are numbered in the order they first appear
+                let mut chains: HashMap<&str, usize> = HashMap::new();
+                mol.atoms
+                    .iter()
+                    .map(|atom| match atom.chain_id.as_deref() {
+                        Some(chain) => {
+                            let next = chains.len();
+                            let number = *chains.entry(chain).or_insert(next);
+                            rgb(CHAIN_PALETTE[number % CHAIN_PALETTE.len()])
+                        }
+                        None => rgb(NEUTRAL),
+                    })
+                    .collect()
+            }
+            ColorScheme::ResidueType => mol
+                .atoms
+                .iter()
+                .map(|atom| match atom.residue_name.as_deref() {
+                    Some(residue) => rgb(residue_type(residue)),
+                    None => atom.element.color,
+                })
+                .collect(),
+            ColorScheme::PartialCharge => {
+                let charges = mol
+                    .atoms
+                    .iter()
+                    .map(|atom| atom.partial_charge.unwrap_or(atom.charge as f32))
+                    .collect::<Vec<_>>();
+                // Scaled so the most charged atom is fully saturated
+                let largest = charges
+                    .iter()
+                    .fold(0.0, |largest: f32, charge| largest.max(charge.abs()));
+                let scale = if largest > 0.0 { -1.0 / largest } else { 0.0 };
+                charges
+                    .into_iter()
+                    .map(|charge| diverging(charge * scale))
+                    .collect()
+            }
+            ColorScheme::Gradient { property, range } => {
+                let values = (0..mol.atoms.len())
+                    .map(|index| property.value(mol, index))
+                    .collect::<Vec<_>>();
+                let (low, high) = range.unwrap_or_else(|| {
+                    values
+                        .iter()
+                        .flatten()
+                        .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), &value| {
+                            (low.min(value), high.max(value))
+                        })
+                });
+                values
+                    .into_iter()
+                    .map(|value| match value {
+                        Some(value) if high > low => {
+                            diverging((value - low) / (high - low) * 2.0 - 1.0)
+                        }
+                        Some(_) => diverging(0.0),
+                        None => rgb(NEUTRAL),
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mol::Atom;
+
+    /// An alanine carbon in chain A, a water in chain B, an iron outside any
+    /// residue and an aspartate nitrogen back in chain A
+    fn mol() -> Mol {
+        let residues = [
+            ("C", Some("A"), Some("ALA"), Some(10.0)),
+            ("O", Some("B"), Some("HOH"), Some(30.0)),
+            ("Fe", None, None, None),
+            ("N", Some("A"), Some("ASP"), Some(20.0)),
+        ];
+        let atoms = residues.iter().map(|&(symbol, chain, residue, b_factor)| {
+            let mut atom = Atom::new(symbol, Vec3::zero());
+            atom.chain_id = chain.map(str::to_string);
+            atom.residue_name = residue.map(str::to_string);
+            atom.b_factor = b_factor;
+            atom
+        });
+        Mol::new(atoms.collect(), Vec::new())
+    }
+
+    fn gradient(property: AtomProperty, range: Option<(f32, f32)>) -> Vec<Vec3> {
+        ColorScheme::Gradient { property, range }.colors(&mol())
+    }
+
+    #[test]
+    fn colors_chains_in_order_of_appearance() {
+        let colors = ColorScheme::Chain.colors(&mol());
+        assert_eq!(colors[0], rgb(CHAIN_PALETTE[0]));
+        assert_eq!(colors[1], rgb(CHAIN_PALETTE[1]));
+        assert_eq!(colors[2], rgb(NEUTRAL));
+        assert_eq!(colors[3], colors[0]);
+    }
+
+    #[test]
+    fn colors_residue_types() {
+        let mol = mol();
+        let colors = ColorScheme::ResidueType.colors(&mol);
+        assert_eq!(colors[0], rgb(0xE8E8E8));
+        assert_eq!(colors[1], rgb(0x9AD0F5));
+        // Atoms outside residues keep their element's color
+        assert_eq!(colors[2], mol.atoms[2].element.color);
+        assert_eq!(colors[3], rgb(0xE60A0A));
+    }
+
+    #[test]
+    fn colors_partial_charges() {
+        let mut mol = mol();
+        mol.atoms[0].partial_charge = Some(-0.5);
+        mol.atoms[1].partial_charge = Some(0.25);
+        // Formal charges stand in for missing partial charges
+        mol.atoms[3].charge = 1;
+        let colors = ColorScheme::PartialCharge.colors(&mol);
+        assert_eq!(colors[2], Vec3::one());
+        assert_eq!(colors[3], Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(colors[0], Vec3::new(1.0, 0.5, 0.5));
+        assert_eq!(colors[1], Vec3::new(0.75, 0.75, 1.0));
+
+        let uncharged = Mol::new(vec![Atom::new("C", Vec3::zero())], Vec::new());
+        assert_eq!(ColorScheme::PartialCharge.colors(&uncharged), [Vec3::one()]);
+    }
+
+    #[test]
+    fn spans_gradients_over_the_values_found() {
+        let colors = gradient(AtomProperty::BFactor, None);
+        assert_eq!(colors[0], Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(colors[3], Vec3::one());
+        assert_eq!(colors[1], Vec3::new(1.0, 0.0, 0.0));
+        // Atoms without the property are neutral
+        assert_eq!(colors[2], rgb(NEUTRAL));
+    }
+
+    #[test]
+    fn spans_gradients_over_a_given_range() {
+        let colors = gradient(AtomProperty::BFactor, Some((0.0, 20.0)));
+        assert_eq!(colors[0], Vec3::one());
+        // Values outside the range are clamped to its ends
+        assert_eq!(colors[1], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(colors[3], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(colors[2], rgb(NEUTRAL));
+    }
+
+    #[test]
+    fn colors_flat_and_missing_properties() {
+        let flat = gradient(AtomProperty::Custom(vec![2.0; 4]), None);
+        assert!(flat.iter().all(|&color| color == Vec3::one()));
+        let empty = gradient(AtomProperty::Custom(vec![2.0; 4]), Some((1.0, 1.0)));
+        assert!(empty.iter().all(|&color| color == Vec3::one()));
+        let missing = gradient(AtomProperty::PartialCharge, None);
+        assert!(missing.iter().all(|&color| color == rgb(NEUTRAL)));
+    }
+}
@@ -0,0 +1,262 @@
+//! A native window showing a molecule file, through glutin or SDL2:
+//!
+//! ```shell
+//! colco-desktop molecule.pdb
+//! ```
+//!
+//! Dropping another file onto the window shows that one instead.
+#![deny(clippy::all)]
+use ::colco::colco::{Colco, RenderSettings};
+use ::colco::color::ColorScheme;
+use ::colco::input::{Controller, Event};
+use ::colco::mol::Mol;
+use ::colco::pipeline::Pipeline;
+use glow::HasContext;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::time::Instant;
+
+#[cfg(not(any(feature = "window-glutin", feature = "window-sdl2")))]
+compile_error!("Pick a window with the window-glutin or window-sdl2 feature");
+#[cfg(all(feature = "window-glutin", feature = "window-sdl2"))]
+compile_error!("Please enable only one of the window-glutin and window-sdl2 features");
+
+/// Both windows ask for a 3.3 core context, the oldest that draws instances
+/// like WebGL2 does
+const SHADER_VERSION: &str = "#version 330 core";
+const TITLE: &str = "colco";
+const SIZE: (u32, u32) = (1024, 768);
+
+/// The molecule on screen and what draws it, whichever window it's in.
+struct Viewer {
+    gl: glow::Context,
+    pipeline: Pipeline<glow::Context>,
+    colco: Option<Colco>,
+    color_scheme: ColorScheme,
+    render_settings: RenderSettings,
+    started: Instant,
+}
+
+/// Reads and parses a molecule file, in any format `Mol` reads.
+fn load(path: &Path) -> Result<Mol, String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    Mol::from_str(&text).map_err(|error| error.to_string())
+}
+
+/// The window's title while it shows a file.
+fn title(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => format!("{} - {}", name.to_string_lossy(), TITLE),
+        None => TITLE.to_string(),
+    }
+}
+
+impl Viewer {
+    /// # Safety
+    ///
+    /// `gl` must be current.
+    unsafe fn new(gl: glow::Context) -> Result<Self, String> {
+        let pipeline = Pipeline::new(&gl, SHADER_VERSION)?;
+        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+        gl.enable(glow::CULL_FACE);
+        gl.cull_face(glow::BACK);
+        gl.enable(glow::DEPTH_TEST);
+        Ok(Viewer {
+            gl,
+            pipeline,
+            colco: None,
+            color_scheme: ColorScheme::default(),
+            render_settings: RenderSettings::default(),
+            started: Instant::now(),
+        })
+    }
+
+    /// Seconds since the window opened, which drags and momentum are timed
+    /// with.
+    fn now(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    /// Shows the molecule in a file. Whatever was shown stays when the file
+    /// can't be read.
+    fn open(&mut self, path: &Path) -> Result<(), String> {
+        let mol = load(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        self.colco = Some(Colco::new(mol, &self.color_scheme));
+        Ok(())
+    }
+
+    fn dispatch(&mut self, event: Event) {
+        let now = self.now();
+        if let Some(state) = self.colco.as_mut() {
+            state.handle(event, now);
+        }
+    }
+
+    /// Draws a frame into the drawable, which is `width` by `height` pixels.
+    ///
+    /// # Safety
+    ///
+    /// `gl` must be current.
+    unsafe fn draw(&mut self, width: u32, height: u32) {
+        let now = self.now();
+        let (width, height) = (width as i32, height.max(1) as i32);
+        self.gl.viewport(0, 0, width, height);
+        self.gl
+            .clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+        // Nothing to draw until a file has been opened successfully
+        if let Some(state) = self.colco.as_mut() {
+            state.camera.aspect = width as f32 / height as f32;
+            let mut renderer = self.pipeline.renderer(&self.gl);
+            state.render_mol(&self.render_settings, &mut renderer, now);
+        }
+    }
+}
+
+#[cfg(feature = "window-glutin")]
+mod window {
+    use super::{title, Viewer, SIZE, TITLE};
+    use ::colco::input::winit::WindowInput;
+    use glutin::dpi::LogicalSize;
+    use glutin::event::{Event as WinitEvent, WindowEvent};
+    use glutin::event_loop::{ControlFlow, EventLoop};
+    use glutin::window::WindowBuilder;
+    use glutin::{Api, ContextBuilder, GlProfile, GlRequest};
+    use std::path::Path;
+
+    pub fn run(path: Option<&Path>) -> Result<(), String> {
+        let event_loop = EventLoop::new();
+        let size = LogicalSize::new(f64::from(SIZE.0), f64::from(SIZE.1));
+        let window = WindowBuilder::new().with_title(TITLE).with_inner_size(size);
+        let context = ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+            .with_gl_profile(GlProfile::Core)
+            .with_depth_buffer(24)
+            .with_vsync(true)
+            .build_windowed(window, &event_loop)
+            .map_err(|error| error.to_string())?;
+        let context = unsafe { context.make_current() }.map_err(|(_, error)| error.to_string())?;
+        let gl =
+            glow::Context::from_loader_function(|name| context.get_proc_address(name) as *const _);
+        let mut viewer = unsafe { Viewer::new(gl) }?;
+        if let Some(path) = path {
+            viewer.open(path)?;
+            context.window().set_title(&title(path));
+        }
+
+        let mut input = WindowInput::new(context.window().inner_size());
+        event_loop.run(move |event, _, control_flow| {
+            // Momentum and auto-spin keep the molecule moving between events
+            *control_flow = ControlFlow::Poll;
+            match event {
+                WinitEvent::EventsCleared => {
+                    let window = context.window();
+                    let size = window.inner_size().to_physical(window.hidpi_factor());
+                    unsafe { viewer.draw(size.width as u32, size.height as u32) };
+                    if let Err(error) = context.swap_buffers() {
+                        eprintln!("{}", error);
+                    }
+                }
+                WinitEvent::WindowEvent { event, .. } => match event {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::DroppedFile(path) => match viewer.open(&path) {
+                        Ok(()) => context.window().set_title(&title(&path)),
+                        Err(error) => eprintln!("{}", error),
+                    },
+                    WindowEvent::Resized(size) => {
+                        let window = context.window();
+                        context.resize(size.to_physical(window.hidpi_factor()));
+                        input.event(&event);
+                    }
+                    WindowEvent::HiDpiFactorChanged(factor) => {
+                        context.resize(context.window().inner_size().to_physical(factor));
+                    }
+                    event => {
+                        if let Some(event) = input.event(&event) {
+                            viewer.dispatch(event);
+                        }
+                    }
+                },
+                WinitEvent::LoopDestroyed => viewer.pipeline.delete(&viewer.gl),
+                _ => {}
+            }
+        })
+    }
+}
+
+#[cfg(feature = "window-sdl2")]
+mod window {
+    use super::{title, Viewer, SIZE, TITLE};
+    use ::colco::input::sdl2::WindowInput;
+    use sdl2::event::Event as SdlEvent;
+    use sdl2::video::GLProfile;
+    use std::path::Path;
+
+    pub fn run(path: Option<&Path>) -> Result<(), String> {
+        let sdl = sdl2::init()?;
+        let video = sdl.video()?;
+        let attributes = video.gl_attr();
+        attributes.set_context_profile(GLProfile::Core);
+        attributes.set_context_version(3, 3);
+        attributes.set_depth_size(24);
+        let mut window = video
+            .window(TITLE, SIZE.0, SIZE.1)
+            .opengl()
+            .resizable()
+            .allow_highdpi()
+            .build()
+            .map_err(|error| error.to_string())?;
+        // Dropping the context would end it, so it's kept until the loop ends
+        let _context = window.gl_create_context()?;
+        // Without vsync frames are drawn as fast as they can be, which is
+        // harmless, so a failure here isn't fatal
+        video.gl_set_swap_interval(1).ok();
+        let gl =
+            glow::Context::from_loader_function(|name| video.gl_get_proc_address(name) as *const _);
+        let mut viewer = unsafe { Viewer::new(gl) }?;
+        if let Some(path) = path {
+            viewer.open(path)?;
+            window.set_title(&title(path)).ok();
+        }
+
+        let mut events = sdl.event_pump()?;
+        let mut input = WindowInput::new(window.size());
+        'running: loop {
+            for event in events.poll_iter() {
+                match event {
+                    SdlEvent::Quit { .. } => break 'running,
+                    SdlEvent::DropFile { ref filename, .. } => {
+                        let path = Path::new(filename);
+                        match viewer.open(path) {
+                            Ok(()) => {
+                                window.set_title(&title(path)).ok();
+                            }
+                            Err(error) => eprintln!("{}", error),
+                        }
+                    }
+                    event => {
+                        if let Some(event) = input.event(&event) {
+                            viewer.dispatch(event);
+                        }
+                    }
+                }
+            }
+            // The drawable is larger than the window on high DPI screens
+            let (width, height) = window.drawable_size();
+            unsafe { viewer.draw(width, height) };
+            window.gl_swap_window();
+        }
+        viewer.pipeline.delete(&viewer.gl);
+        Ok(())
+    }
+}
+
+fn main() {
+    let path = env::args_os().nth(1).map(PathBuf::from);
+    if let Err(error) = window::run(path.as_deref()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
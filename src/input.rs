@@ -0,0 +1,135 @@
+//! Input events in colco's own terms, so the viewer can be driven from any
+//! platform and tested without one. Adapters turn each platform's events
+//! into `Event`s, and a `Controller` such as `Colco` acts on them.
+//!
+//! Positions are in normalized device coordinates, from -1 to 1 with +y up,
+//! and times are in seconds.
+use crate::picking::Pick;
+
+mod gestures;
+
+#[cfg(feature = "window-sdl2")]
+pub mod sdl2;
+#[cfg(feature = "std_web")]
+pub mod stdweb;
+#[cfg(feature = "web-sys")]
+pub mod web_sys;
+#[cfg(feature = "window-glutin")]
+pub mod winit;
+
+pub use gestures::{Gesture, Gestures};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Button {
+    /// Usually the left button
+    Primary,
+    /// Usually the right button
+    Secondary,
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+}
+
+/// Keys the viewer responds to. Adapters drop any others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// `+` or `=`
+    ZoomIn,
+    /// `-`
+    ZoomOut,
+    /// Home
+    Reset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TouchPhase {
+    Start,
+    Move,
+    End,
+    /// The platform took the touch over, such as for a system gesture
+    Cancel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    PointerDown {
+        position: (f32, f32),
+        button: Button,
+        modifiers: Modifiers,
+    },
+    PointerMove {
+        position: (f32, f32),
+    },
+    PointerUp {
+        position: (f32, f32),
+        button: Button,
+    },
+    /// Scrolling, in pixels, positive towards the user
+    Wheel {
+        delta: f32,
+    },
+    Key {
+        key: Key,
+        modifiers: Modifiers,
+    },
+    /// A finger on a touch screen. `id` tells fingers apart while they are
+    /// down.
+    Touch {
+        id: i32,
+        phase: TouchPhase,
+        position: (f32, f32),
+    },
+}
+
+/// What an event meant, for whoever is listening for atoms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Response {
+    Nothing,
+    /// The pointer moved onto another atom or bond, or off of one
+    Hover(Option<Pick>),
+    /// An atom or bond was clicked or tapped
+    Click(Pick),
+}
+
+pub trait Controller {
+    /// Acts on an event that happened at `time`.
+    fn handle(&mut self, event: Event, time: f64) -> Response;
+}
+
+/// Converts a position in pixels from the top left of a viewport into
+/// normalized device coordinates.
+pub fn normalize(x: f64, y: f64, width: f64, height: f64) -> (f32, f32) {
+    let (width, height) = (width.max(1.0), height.max(1.0));
+    (
+        (x / width * 2.0 - 1.0) as f32,
+        (1.0 - y / height * 2.0) as f32,
+    )
+}
+
+/// The key a browser names in `KeyboardEvent.key`.
+#[cfg(any(feature = "std_web", feature = "web-sys"))]
+fn browser_key(name: &str) -> Option<Key> {
+    match name {
+        "ArrowLeft" => Some(Key::Left),
+        "ArrowRight" => Some(Key::Right),
+        "ArrowUp" => Some(Key::Up),
+        "ArrowDown" => Some(Key::Down),
+        "+" | "=" => Some(Key::ZoomIn),
+        "-" => Some(Key::ZoomOut),
+        "Home" => Some(Key::Reset),
+        _ => None,
+    }
+}
+
+/// Pixels a notch of a mouse wheel scrolls, about as far as in browsers.
+#[cfg(any(feature = "window-glutin", feature = "window-sdl2"))]
+const WHEEL_NOTCH: f32 = 100.0;
@@ -0,0 +1,223 @@
+//! Turns raw touches into gestures, without knowing where the touches came
+//! from, so any platform can feed it. Positions are in normalized device
+//! coordinates and times in seconds.
+//!
+//! - One finger drags to rotate
+//! - Two fingers pinch to zoom and move together to pan
+//! - A quick touch is a tap, and two taps close together are a double tap
+
+/// Furthest a finger can wander, in normalized device coordinates, and still
+/// be tapping rather than dragging
+const TAP_SLOP: f32 = 0.03;
+
+/// Longest a tap can last, in seconds
+const TAP_TIME: f64 = 0.3;
+
+/// Longest gap between the taps of a double tap, in seconds
+const DOUBLE_TAP_TIME: f64 = 0.35;
+
+/// Furthest apart the taps of a double tap can be
+const DOUBLE_TAP_SLOP: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// One finger went down, and may be about to drag
+    DragStart((f32, f32)),
+    Drag((f32, f32)),
+    DragEnd,
+    /// Two fingers moved. `zoom` is the old distance between them over the
+    /// new, and `pan` how far their midpoint moved.
+    Pinch {
+        zoom: f32,
+        pan: (f32, f32),
+    },
+    Tap((f32, f32)),
+    DoubleTap((f32, f32)),
+}
+
+struct Touch {
+    id: i32,
+    position: (f32, f32),
+}
+
+/// Where and when a single finger touch began.
+struct Press {
+    position: (f32, f32),
+    time: f64,
+    /// Whether the finger has strayed too far to be tapping
+    moved: bool,
+}
+
+#[derive(Default)]
+pub struct Gestures {
+    touches: Vec<Touch>,
+    /// The single finger touch in progress, if any. Gone once a second
+    /// finger touches, until every finger has lifted.
+    press: Option<Press>,
+    /// Whether a second finger joined the current touch
+    multiple: bool,
+    last_tap: Option<((f32, f32), f64)>,
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+fn midpoint(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    ((a.0 + b.0) * 0.5, (a.1 + b.1) * 0.5)
+}
+
+impl Gestures {
+    pub fn down(&mut self, id: i32, position: (f32, f32), time: f64) -> Vec<Gesture> {
+        self.touches.retain(|touch| touch.id != id);
+        self.touches.push(Touch { id, position });
+        match self.touches.len() {
+            1 => {
+                self.multiple = false;
+                self.press = Some(Press {
+                    position,
+                    time,
+                    moved: false,
+                });
+                vec![Gesture::DragStart(position)]
+            }
+            2 if !self.multiple => {
+                self.multiple = true;
+                self.press = None;
+                vec![Gesture::DragEnd]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn moved(&mut self, id: i32, position: (f32, f32)) -> Vec<Gesture> {
+        let index = match self.touches.iter().position(|touch| touch.id == id) {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+        if self.multiple {
+            // Only the first two fingers count
+            if index > 1 || self.touches.len() < 2 {
+                self.touches[index].position = position;
+                return Vec::new();
+            }
+            let other = self.touches[1 - index].position;
+            let before = self.touches[index].position;
+            self.touches[index].position = position;
+            let (old_middle, new_middle) = (midpoint(before, other), midpoint(position, other));
+            let new_span = distance(position, other);
+            let zoom = if new_span > 0.0 {
+                distance(before, other) / new_span
+            } else {
+                1.0
+            };
+            let pan = (new_middle.0 - old_middle.0, new_middle.1 - old_middle.1);
+            return vec![Gesture::Pinch { zoom, pan }];
+        }
+        self.touches[index].position = position;
+        match self.press.as_mut() {
+            Some(press) => {
+                press.moved |= distance(press.position, position) > TAP_SLOP;
+                vec![Gesture::Drag(position)]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// A finger lifted. Cancelled touches should come here too, with
+    /// `cancelled` set so they don't count as taps.
+    pub fn up(&mut self, id: i32, time: f64, cancelled: bool) -> Vec<Gesture> {
+        let before = self.touches.len();
+        self.touches.retain(|touch| touch.id != id);
+        if self.touches.len() == before {
+            return Vec::new();
+        }
+        let press = match self.press.take() {
+            Some(press) => press,
+            None => return Vec::new(),
+        };
+        let mut gestures = vec![Gesture::DragEnd];
+        if cancelled || press.moved || time - press.time > TAP_TIME {
+            return gestures;
+        }
+        let double = self.last_tap.is_some_and(|(position, tapped)| {
+            time - tapped <= DOUBLE_TAP_TIME
+                && distance(position, press.position) <= DOUBLE_TAP_SLOP
+        });
+        if double {
+            self.last_tap = None;
+            gestures.push(Gesture::DoubleTap(press.position));
+        } else {
+            self.last_tap = Some((press.position, time));
+            gestures.push(Gesture::Tap(press.position));
+        }
+        gestures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_finger_drags() {
+        let mut gestures = Gestures::default();
+        let start = gestures.down(1, (0.0, 0.0), 0.0);
+        assert_eq!(start, [Gesture::DragStart((0.0, 0.0))]);
+        let dragged = gestures.moved(1, (0.2, 0.1));
+        assert_eq!(dragged, [Gesture::Drag((0.2, 0.1))]);
+        // Too far to be a tap, however quick
+        assert_eq!(gestures.up(1, 0.1, false), [Gesture::DragEnd]);
+        assert!(gestures.moved(1, (0.3, 0.1)).is_empty());
+    }
+
+    #[test]
+    fn taps_and_double_taps() {
+        let mut gestures = Gestures::default();
+        let mut tap = |position, time| {
+            gestures.down(1, position, time);
+            gestures.up(1, time + 0.05, false)
+        };
+        let tapped = [Gesture::DragEnd, Gesture::Tap((0.5, 0.5))];
+        assert_eq!(tap((0.5, 0.5), 0.0), tapped);
+        let double = [Gesture::DragEnd, Gesture::DoubleTap((0.52, 0.5))];
+        assert_eq!(tap((0.52, 0.5), 0.2), double);
+        // A third tap starts over
+        assert_eq!(tap((0.5, 0.5), 0.4), tapped);
+        // Too late, and too far, for a double tap
+        assert_eq!(tap((0.5, 0.5), 1.0), tapped);
+        let elsewhere = [Gesture::DragEnd, Gesture::Tap((-0.5, 0.5))];
+        assert_eq!(tap((-0.5, 0.5), 1.2), elsewhere);
+    }
+
+    #[test]
+    fn long_or_cancelled_touches_arent_taps() {
+        let mut gestures = Gestures::default();
+        gestures.down(1, (0.0, 0.0), 0.0);
+        assert_eq!(gestures.up(1, 1.0, false), [Gesture::DragEnd]);
+        gestures.down(2, (0.0, 0.0), 2.0);
+        assert_eq!(gestures.up(2, 2.05, true), [Gesture::DragEnd]);
+    }
+
+    #[test]
+    fn two_fingers_pinch() {
+        let mut gestures = Gestures::default();
+        gestures.down(1, (-0.25, 0.0), 0.0);
+        // The second finger ends the first's drag
+        assert_eq!(gestures.down(2, (0.25, 0.0), 0.0), [Gesture::DragEnd]);
+        let pinched = gestures.moved(2, (0.75, 0.0));
+        assert_eq!(
+            pinched,
+            [Gesture::Pinch {
+                zoom: 0.5,
+                pan: (0.25, 0.0)
+            }]
+        );
+        // Nothing more until every finger has lifted, and no tap after
+        assert!(gestures.up(2, 0.1, false).is_empty());
+        assert!(gestures.moved(1, (0.0, 0.0)).is_empty());
+        assert!(gestures.up(1, 0.1, false).is_empty());
+        let start = gestures.down(1, (0.0, 0.0), 1.0);
+        assert_eq!(start, [Gesture::DragStart((0.0, 0.0))]);
+    }
+}
@@ -0,0 +1,147 @@
+//! Turns SDL2's events into colco's.
+use super::{normalize, Button, Event, Key, Modifiers, TouchPhase, WHEEL_NOTCH};
+use sdl2::event::{Event as SdlEvent, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::{MouseButton, MouseWheelDirection};
+
+/// The mouse SDL says mouse events came from when it makes them up from
+/// touches, which come as finger events too
+const TOUCH_MOUSE: u32 = u32::MAX;
+
+/// SDL's mouse buttons don't say which modifier keys are held, so this keeps
+/// track of them along with the window's size.
+pub struct WindowInput {
+    size: (u32, u32),
+    modifiers: Modifiers,
+}
+
+fn button(button: MouseButton) -> Option<Button> {
+    match button {
+        MouseButton::Left => Some(Button::Primary),
+        MouseButton::Right => Some(Button::Secondary),
+        MouseButton::Middle => Some(Button::Middle),
+        _ => None,
+    }
+}
+
+fn key(code: Keycode) -> Option<Key> {
+    match code {
+        Keycode::Left => Some(Key::Left),
+        Keycode::Right => Some(Key::Right),
+        Keycode::Up => Some(Key::Up),
+        Keycode::Down => Some(Key::Down),
+        Keycode::Plus | Keycode::Equals | Keycode::KpPlus => Some(Key::ZoomIn),
+        Keycode::Minus | Keycode::KpMinus => Some(Key::ZoomOut),
+        Keycode::Home => Some(Key::Reset),
+        _ => None,
+    }
+}
+
+fn modifiers(keymod: Mod) -> Modifiers {
+    Modifiers {
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        control: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+    }
+}
+
+impl WindowInput {
+    /// `size` is the window's size in the units mouse events use.
+    pub fn new(size: (u32, u32)) -> Self {
+        WindowInput {
+            size,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    fn position(&self, x: i32, y: i32) -> (f32, f32) {
+        let (width, height) = self.size;
+        normalize(
+            f64::from(x),
+            f64::from(y),
+            f64::from(width),
+            f64::from(height),
+        )
+    }
+
+    fn touch(id: i64, phase: TouchPhase, x: f32, y: f32) -> Event {
+        Event::Touch {
+            id: id as i32,
+            phase,
+            // Fingers are given from 0 to 1 across the window
+            position: normalize(f64::from(x), f64::from(y), 1.0, 1.0),
+        }
+    }
+
+    /// `None` for events the viewer doesn't use.
+    pub fn event(&mut self, event: &SdlEvent) -> Option<Event> {
+        match *event {
+            SdlEvent::Window {
+                win_event: WindowEvent::SizeChanged(width, height),
+                ..
+            } => {
+                self.size = (width.max(0) as u32, height.max(0) as u32);
+                None
+            }
+            SdlEvent::MouseMotion { which, x, y, .. } if which != TOUCH_MOUSE => {
+                Some(Event::PointerMove {
+                    position: self.position(x, y),
+                })
+            }
+            SdlEvent::MouseButtonDown {
+                which,
+                mouse_btn,
+                x,
+                y,
+                ..
+            } if which != TOUCH_MOUSE => Some(Event::PointerDown {
+                position: self.position(x, y),
+                button: button(mouse_btn)?,
+                modifiers: self.modifiers,
+            }),
+            SdlEvent::MouseButtonUp {
+                which,
+                mouse_btn,
+                x,
+                y,
+                ..
+            } if which != TOUCH_MOUSE => Some(Event::PointerUp {
+                position: self.position(x, y),
+                button: button(mouse_btn)?,
+            }),
+            SdlEvent::MouseWheel { y, direction, .. } => {
+                // SDL scrolls positive away from the user, unless flipped
+                let notches = match direction {
+                    MouseWheelDirection::Flipped => -y,
+                    _ => y,
+                };
+                Some(Event::Wheel {
+                    delta: -notches as f32 * WHEEL_NOTCH,
+                })
+            }
+            SdlEvent::KeyDown {
+                keycode, keymod, ..
+            } => {
+                self.modifiers = modifiers(keymod);
+                Some(Event::Key {
+                    key: key(keycode?)?,
+                    modifiers: self.modifiers,
+                })
+            }
+            SdlEvent::KeyUp { keymod, .. } => {
+                self.modifiers = modifiers(keymod);
+                None
+            }
+            SdlEvent::FingerDown {
+                finger_id, x, y, ..
+            } => Some(Self::touch(finger_id, TouchPhase::Start, x, y)),
+            SdlEvent::FingerMotion {
+                finger_id, x, y, ..
+            } => Some(Self::touch(finger_id, TouchPhase::Move, x, y)),
+            SdlEvent::FingerUp {
+                finger_id, x, y, ..
+            } => Some(Self::touch(finger_id, TouchPhase::End, x, y)),
+            _ => None,
+        }
+    }
+}
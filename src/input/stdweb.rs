@@ -0,0 +1,95 @@
+//! Turns stdweb's DOM events on a canvas into colco's.
+use super::{browser_key, normalize, Button, Event, Modifiers, TouchPhase};
+use ::stdweb::traits::*;
+use ::stdweb::web::event::{
+    IKeyboardEvent, IMouseEvent, IPointerEvent, KeyDownEvent, MouseButton, MouseWheelDeltaMode,
+    MouseWheelEvent,
+};
+use ::stdweb::web::html_element::CanvasElement;
+
+/// Where a mouse event happened on the canvas.
+fn position<E: IMouseEvent>(canvas: &CanvasElement, event: &E) -> (f32, f32) {
+    normalize(
+        event.offset_x(),
+        event.offset_y(),
+        f64::from(canvas.offset_width()),
+        f64::from(canvas.offset_height()),
+    )
+}
+
+fn button<E: IMouseEvent>(event: &E) -> Button {
+    match event.button() {
+        MouseButton::Right => Button::Secondary,
+        MouseButton::Wheel => Button::Middle,
+        _ => Button::Primary,
+    }
+}
+
+fn modifiers<E: IMouseEvent>(event: &E) -> Modifiers {
+    Modifiers {
+        shift: event.shift_key(),
+        control: event.ctrl_key(),
+        alt: event.alt_key(),
+    }
+}
+
+pub fn mouse_down<E: IMouseEvent>(canvas: &CanvasElement, event: &E) -> Event {
+    Event::PointerDown {
+        position: position(canvas, event),
+        button: button(event),
+        modifiers: modifiers(event),
+    }
+}
+
+pub fn mouse_move<E: IMouseEvent>(canvas: &CanvasElement, event: &E) -> Event {
+    Event::PointerMove {
+        position: position(canvas, event),
+    }
+}
+
+pub fn mouse_up<E: IMouseEvent>(canvas: &CanvasElement, event: &E) -> Event {
+    Event::PointerUp {
+        position: position(canvas, event),
+        button: button(event),
+    }
+}
+
+pub fn wheel(event: &MouseWheelEvent) -> Event {
+    let pixels = match event.delta_mode() {
+        MouseWheelDeltaMode::Pixel => event.delta_y(),
+        MouseWheelDeltaMode::Line => event.delta_y() * 16.0,
+        MouseWheelDeltaMode::Page => event.delta_y() * 400.0,
+    };
+    Event::Wheel {
+        delta: pixels as f32,
+    }
+}
+
+/// `None` for keys the viewer doesn't use.
+pub fn key(event: &KeyDownEvent) -> Option<Event> {
+    let key = browser_key(&event.key())?;
+    Some(Event::Key {
+        key,
+        modifiers: Modifiers {
+            shift: event.shift_key(),
+            control: event.ctrl_key(),
+            alt: event.alt_key(),
+        },
+    })
+}
+
+/// `None` for pointers other than fingers, which come as mouse events too.
+pub fn touch<E: IPointerEvent>(
+    canvas: &CanvasElement,
+    event: &E,
+    phase: TouchPhase,
+) -> Option<Event> {
+    if event.pointer_type() != "touch" {
+        return None;
+    }
+    Some(Event::Touch {
+        id: event.pointer_id(),
+        phase,
+        position: position(canvas, event),
+    })
+}
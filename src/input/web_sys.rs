@@ -0,0 +1,82 @@
+//! Turns web-sys DOM events on a canvas into colco's.
+use super::{browser_key, normalize, Button, Event, Modifiers, TouchPhase};
+use web_sys::{HtmlCanvasElement, KeyboardEvent, MouseEvent, PointerEvent, WheelEvent};
+
+/// Where a mouse event happened on the canvas.
+fn position(canvas: &HtmlCanvasElement, event: &MouseEvent) -> (f32, f32) {
+    normalize(
+        f64::from(event.offset_x()),
+        f64::from(event.offset_y()),
+        f64::from(canvas.offset_width()),
+        f64::from(canvas.offset_height()),
+    )
+}
+
+fn button(event: &MouseEvent) -> Button {
+    match event.button() {
+        1 => Button::Middle,
+        2 => Button::Secondary,
+        _ => Button::Primary,
+    }
+}
+
+pub fn mouse_down(canvas: &HtmlCanvasElement, event: &MouseEvent) -> Event {
+    Event::PointerDown {
+        position: position(canvas, event),
+        button: button(event),
+        modifiers: Modifiers {
+            shift: event.shift_key(),
+            control: event.ctrl_key(),
+            alt: event.alt_key(),
+        },
+    }
+}
+
+pub fn mouse_move(canvas: &HtmlCanvasElement, event: &MouseEvent) -> Event {
+    Event::PointerMove {
+        position: position(canvas, event),
+    }
+}
+
+pub fn mouse_up(canvas: &HtmlCanvasElement, event: &MouseEvent) -> Event {
+    Event::PointerUp {
+        position: position(canvas, event),
+        button: button(event),
+    }
+}
+
+pub fn wheel(event: &WheelEvent) -> Event {
+    let pixels = match event.delta_mode() {
+        WheelEvent::DOM_DELTA_LINE => event.delta_y() * 16.0,
+        WheelEvent::DOM_DELTA_PAGE => event.delta_y() * 400.0,
+        _ => event.delta_y(),
+    };
+    Event::Wheel {
+        delta: pixels as f32,
+    }
+}
+
+/// `None` for keys the viewer doesn't use.
+pub fn key(event: &KeyboardEvent) -> Option<Event> {
+    let key = browser_key(&event.key())?;
+    Some(Event::Key {
+        key,
+        modifiers: Modifiers {
+            shift: event.shift_key(),
+            control: event.ctrl_key(),
+            alt: event.alt_key(),
+        },
+    })
+}
+
+/// `None` for pointers other than fingers, which come as mouse events too.
+pub fn touch(canvas: &HtmlCanvasElement, event: &PointerEvent, phase: TouchPhase) -> Option<Event> {
+    if event.pointer_type() != "touch" {
+        return None;
+    }
+    Some(Event::Touch {
+        id: event.pointer_id(),
+        phase,
+        position: position(canvas, event),
+    })
+}
@@ -0,0 +1,114 @@
+//! Turns winit's window events, as re-exported by glutin, into colco's.
+use super::{normalize, Button, Event, Key, Modifiers, TouchPhase, WHEEL_NOTCH};
+use glutin::dpi::{LogicalPosition, LogicalSize};
+use glutin::event::{
+    ElementState, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// Winit's mouse buttons don't say where the cursor is, so this keeps track
+/// of it along with the window's size.
+pub struct WindowInput {
+    size: LogicalSize,
+    cursor: LogicalPosition,
+}
+
+fn modifiers(state: ModifiersState) -> Modifiers {
+    Modifiers {
+        shift: state.shift,
+        control: state.ctrl,
+        alt: state.alt,
+    }
+}
+
+fn button(button: MouseButton) -> Option<Button> {
+    match button {
+        MouseButton::Left => Some(Button::Primary),
+        MouseButton::Right => Some(Button::Secondary),
+        MouseButton::Middle => Some(Button::Middle),
+        MouseButton::Other(_) => None,
+    }
+}
+
+fn key(code: VirtualKeyCode) -> Option<Key> {
+    match code {
+        VirtualKeyCode::Left => Some(Key::Left),
+        VirtualKeyCode::Right => Some(Key::Right),
+        VirtualKeyCode::Up => Some(Key::Up),
+        VirtualKeyCode::Down => Some(Key::Down),
+        VirtualKeyCode::Add | VirtualKeyCode::Equals => Some(Key::ZoomIn),
+        VirtualKeyCode::Subtract | VirtualKeyCode::Minus => Some(Key::ZoomOut),
+        VirtualKeyCode::Home => Some(Key::Reset),
+        _ => None,
+    }
+}
+
+impl WindowInput {
+    pub fn new(size: LogicalSize) -> Self {
+        WindowInput {
+            size,
+            cursor: LogicalPosition::new(0.0, 0.0),
+        }
+    }
+
+    fn position(&self, position: LogicalPosition) -> (f32, f32) {
+        normalize(position.x, position.y, self.size.width, self.size.height)
+    }
+
+    /// `None` for events the viewer doesn't use.
+    pub fn event(&mut self, event: &WindowEvent) -> Option<Event> {
+        match *event {
+            WindowEvent::Resized(size) => {
+                self.size = size;
+                None
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = position;
+                Some(Event::PointerMove {
+                    position: self.position(position),
+                })
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: pressed,
+                modifiers: held,
+                ..
+            } => {
+                let (position, button) = (self.position(self.cursor), button(pressed)?);
+                Some(match state {
+                    ElementState::Pressed => Event::PointerDown {
+                        position,
+                        button,
+                        modifiers: modifiers(held),
+                    },
+                    ElementState::Released => Event::PointerUp { position, button },
+                })
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                // Winit scrolls positive away from the user
+                let pixels = match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => lines * WHEEL_NOTCH,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                };
+                Some(Event::Wheel { delta: -pixels })
+            }
+            WindowEvent::KeyboardInput { input, .. } => match input.state {
+                ElementState::Pressed => Some(Event::Key {
+                    key: key(input.virtual_keycode?)?,
+                    modifiers: modifiers(input.modifiers),
+                }),
+                ElementState::Released => None,
+            },
+            WindowEvent::Touch(touch) => Some(Event::Touch {
+                id: touch.id as i32,
+                phase: match touch.phase {
+                    glutin::event::TouchPhase::Started => TouchPhase::Start,
+                    glutin::event::TouchPhase::Moved => TouchPhase::Move,
+                    glutin::event::TouchPhase::Ended => TouchPhase::End,
+                    glutin::event::TouchPhase::Cancelled => TouchPhase::Cancel,
+                },
+                position: self.position(touch.location),
+            }),
+            _ => None,
+        }
+    }
+}
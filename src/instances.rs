@@ -0,0 +1,119 @@
+//! Per-instance data, so every atom is drawn with one instanced draw call and
+//! every bond with another. The data only depends on the molecule, its colors
+//! and the render settings, so it is rebuilt when those change rather than
+//! every frame.
+//!
+//! Meshes take a column-major model matrix and a color per instance.
+//! Impostors take the shape itself: a sphere's center and radius, or a
+//! cylinder's two ends and radius, followed by a color. Lines take their two
+//! ends and a color either way.
+use crate::mol::rotation_from_y;
+use crate::representation::{Cylinder, Shapes};
+use glam::{Mat4, Vec3};
+
+/// `(attribute location, float count)` of each per-instance attribute, in the
+/// order they are packed
+pub type Layout = &'static [(u32, i32)];
+
+pub const MESH_LAYOUT: Layout = &[(2, 4), (3, 4), (4, 4), (5, 4), (6, 3)];
+pub const SPHERE_LAYOUT: Layout = &[(1, 3), (2, 1), (3, 3)];
+pub const CYLINDER_LAYOUT: Layout = &[(1, 3), (2, 3), (3, 1), (4, 3)];
+pub const LINE_LAYOUT: Layout = &[(1, 3), (2, 3), (3, 3)];
+
+/// Floats per instance in `layout`
+pub fn instance_floats(layout: Layout) -> usize {
+    layout.iter().map(|&(_, size)| size as usize).sum()
+}
+
+pub struct Instances {
+    /// One instance per sphere, which is usually one per atom
+    pub atoms: Vec<f32>,
+    /// One instance per cylinder, which is usually one for each half of
+    /// each line of a bond
+    pub bonds: Vec<f32>,
+    pub lines: Vec<f32>,
+    pub atom_count: i32,
+    pub bond_count: i32,
+    pub line_count: i32,
+}
+
+fn push_vec3(data: &mut Vec<f32>, vector: Vec3) {
+    data.extend_from_slice(&[vector.x(), vector.y(), vector.z()]);
+}
+
+/// The mesh cylinder runs from 0 to 2 along +Y with a radius of 1.
+fn cylinder_model(cylinder: &Cylinder) -> Mat4 {
+    let axis = cylinder.end - cylinder.start;
+    let length = axis.length();
+    let rotation = if length > 0.0 {
+        rotation_from_y(axis / length)
+    } else {
+        Default::default()
+    };
+    Mat4::from_translation(cylinder.start)
+        * Mat4::from_quat(rotation)
+        * Mat4::from_scale(Vec3::new(cylinder.radius, length * 0.5, cylinder.radius))
+}
+
+impl Instances {
+    /// Model matrices for the sphere and cylinder meshes.
+    pub fn meshes(shapes: &Shapes) -> Self {
+        let mut atoms = Vec::with_capacity(shapes.spheres.len() * instance_floats(MESH_LAYOUT));
+        for sphere in &shapes.spheres {
+            let model = Mat4::from_translation(sphere.center)
+                * Mat4::from_scale(Vec3::new(sphere.radius, sphere.radius, sphere.radius));
+            atoms.extend_from_slice(model.as_ref());
+            push_vec3(&mut atoms, sphere.color);
+        }
+
+        let mut bonds = Vec::with_capacity(shapes.cylinders.len() * instance_floats(MESH_LAYOUT));
+        for cylinder in &shapes.cylinders {
+            bonds.extend_from_slice(cylinder_model(cylinder).as_ref());
+            push_vec3(&mut bonds, cylinder.color);
+        }
+        Instances::counted(atoms, bonds, shapes, MESH_LAYOUT, MESH_LAYOUT)
+    }
+
+    /// Spheres and cylinders for the ray-cast impostors.
+    pub fn impostors(shapes: &Shapes) -> Self {
+        let mut atoms = Vec::with_capacity(shapes.spheres.len() * instance_floats(SPHERE_LAYOUT));
+        for sphere in &shapes.spheres {
+            push_vec3(&mut atoms, sphere.center);
+            atoms.push(sphere.radius);
+            push_vec3(&mut atoms, sphere.color);
+        }
+
+        let mut bonds =
+            Vec::with_capacity(shapes.cylinders.len() * instance_floats(CYLINDER_LAYOUT));
+        for cylinder in &shapes.cylinders {
+            push_vec3(&mut bonds, cylinder.start);
+            push_vec3(&mut bonds, cylinder.end);
+            bonds.push(cylinder.radius);
+            push_vec3(&mut bonds, cylinder.color);
+        }
+        Instances::counted(atoms, bonds, shapes, SPHERE_LAYOUT, CYLINDER_LAYOUT)
+    }
+
+    fn counted(
+        atoms: Vec<f32>,
+        bonds: Vec<f32>,
+        shapes: &Shapes,
+        atom_layout: Layout,
+        bond_layout: Layout,
+    ) -> Self {
+        let mut lines = Vec::with_capacity(shapes.lines.len() * instance_floats(LINE_LAYOUT));
+        for line in &shapes.lines {
+            push_vec3(&mut lines, line.start);
+            push_vec3(&mut lines, line.end);
+            push_vec3(&mut lines, line.color);
+        }
+        Instances {
+            atom_count: (atoms.len() / instance_floats(atom_layout)) as i32,
+            bond_count: (bonds.len() / instance_floats(bond_layout)) as i32,
+            line_count: shapes.lines.len() as i32,
+            atoms,
+            bonds,
+            lines,
+        }
+    }
+}
@@ -0,0 +1,18 @@
+#![deny(clippy::all)]
+pub mod arcball;
+pub mod assets;
+pub mod camera;
+pub mod colco;
+pub mod color;
+pub mod constants;
+pub mod input;
+pub mod instances;
+pub mod mol;
+pub mod picking;
+pub mod pipeline;
+pub mod renderer;
+pub mod representation;
+pub mod selection;
+
+#[cfg(feature = "web-sys")]
+mod bindgen;
@@ -1,238 +1,336 @@
 // TODO: Reduce bundle size from 890kb to ~300kb
 
 #![deny(clippy::all)]
-use glam::{Mat4, Quat, Vec3};
 use glow::HasContext as Context;
 use glow::*;
 use lazy_static::lazy_static;
-use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Mutex;
 use stdweb::{
-    console, js_deserializable, js_export,
+    console, js, js_export,
     traits::*,
     unstable::TryInto,
     web::{
         document,
-        event::{IMouseEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent},
+        event::{
+            ContextMenuEvent, KeyDownEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent,
+            MouseWheelEvent, PointerCancelEvent, PointerDownEvent, PointerMoveEvent,
+            PointerUpEvent,
+        },
         html_element::*,
+        Date,
     },
+    Value,
 };
 use webgl_stdweb::WebGL2RenderingContext;
 
-pub mod assets;
-pub mod constants;
-pub mod mol;
+use ::colco::colco::{Colco, RenderSettings};
+use ::colco::color::ColorScheme;
+use ::colco::input::{self, Controller, Event, Response, TouchPhase};
+use ::colco::mol::{Mol, ParseError, Sdf};
+use ::colco::picking::Pick;
+use ::colco::pipeline::Pipeline;
+use ::colco::selection::Selection;
 
-use assets::init_buffers_from_constants;
-use constants::{MESHES_SIZE, SPHERE_SIZE};
-use mol::Mol;
+lazy_static! {
+    static ref colco: Mutex<Option<Colco>> = Mutex::new(None);
+    static ref sdf: Mutex<Option<Sdf>> = Mutex::new(None);
+    static ref color_scheme: Mutex<ColorScheme> = Mutex::new(ColorScheme::default());
+    static ref render_settings: Mutex<Option<RenderSettings>> =
+        Mutex::new(Some(RenderSettings::default()));
+}
 
-struct Colco {
-    is_mouse_down: bool,
-    rotation: Quat,
-    mol: Mol,
+thread_local! {
+    static ATOM_HOVER: RefCell<Option<Value>> = RefCell::new(None);
+    static ATOM_CLICK: RefCell<Option<Value>> = RefCell::new(None);
 }
 
-impl Colco {
-    fn new(mol: Mol) -> Self {
-        Colco {
-            is_mouse_down: false,
-            rotation: Quat::from_xyzw(0.0, 1.0, 0.0, 0.0),
-            mol,
-        }
+// TODO: Get render_settings via js for spreading new settings into old ones
+
+#[js_export]
+fn setRenderSettings(render: RenderSettings) {
+    let mut settings = render_settings.lock().unwrap();
+    *settings = Some(render);
+}
+
+/// Recolors the molecule, and any loaded later, with a scheme such as
+/// `"cpk"`, `"chain"` or `{ gradient: { property: "b_factor" } }`.
+#[js_export]
+fn setColorScheme(scheme: ColorScheme) {
+    if let Some(state) = colco.lock().unwrap().as_mut() {
+        state.recolor(&scheme);
     }
+    *color_scheme.lock().unwrap() = scheme;
 }
 
-impl Colco {
-    pub unsafe fn render_mol<B: Context>(
-        &self,
-        settings: &RenderSettings,
-        transform_uniform: &Option<<B as Context>::UniformLocation>,
-        light_uniform: &Option<<B as Context>::UniformLocation>,
-        color_uniform: &Option<<B as Context>::UniformLocation>,
-        view_uniform: &Option<<B as Context>::UniformLocation>,
-        gl: &B,
-    ) {
-        let view_vector = self.rotation * Vec3::unit_z();
-        let view_location = Some(view_uniform.as_ref().unwrap().clone());
-        &gl.uniform_3_f32(
-            view_location,
-            view_vector.x(),
-            view_vector.y(),
-            view_vector.z(),
-        );
-        let light_location_true = Some(light_uniform.as_ref().unwrap().clone());
-        &gl.uniform_1_i32(light_location_true, glow::TRUE as i32);
-        for atom in &self.mol.atoms {
-            let transform_location = Some(transform_uniform.as_ref().unwrap().clone());
-            let color_location = Some(color_uniform.as_ref().unwrap().clone());
-            &gl.uniform_3_f32(
-                color_location,
-                atom.element.color.x(),
-                atom.element.color.y(),
-                atom.element.color.z(),
-            );
-            &gl.uniform_matrix_4_f32_slice(
-                transform_location,
-                false,
-                (self.mol.bounding_projection
-                    * Mat4::from_quat(self.rotation).transpose()
-                    * Mat4::from_translation(atom.position * 4.5)
-                    * Mat4::from_scale(
-                        Vec3::new(atom.element.scale, atom.element.scale, atom.element.scale)
-                            * settings.atom_size,
-                    ))
-                .as_ref(),
-            );
-            &gl.draw_elements(glow::TRIANGLES, SPHERE_SIZE as i32, glow::UNSIGNED_INT, 0);
-        }
-        let light_location_false = Some(light_uniform.as_ref().unwrap().clone());
-        &gl.uniform_1_i32(light_location_false, glow::FALSE as i32);
-        for bond in &self.mol.bonds {
-            let transform_location = Some(transform_uniform.as_ref().unwrap().clone());
-            for bond_num in 0..bond.bond_type {
-                let color_location = Some(color_uniform.as_ref().unwrap().clone());
-                &gl.uniform_3_f32(
-                    color_location,
-                    bond.from_color.x(),
-                    bond.from_color.y(),
-                    bond.from_color.z(),
-                );
-                &gl.uniform_matrix_4_f32_slice(
-                    transform_location.clone(),
-                    false,
-                    (self.mol.bounding_projection
-                        * Mat4::from_quat(self.rotation).transpose()
-                        * Mat4::from_translation(bond.position * 4.5)
-                        * Mat4::from_quat(bond.rotation)
-                        * Mat4::from_translation(Vec3::new(
-                            bond_num as f32 - (0.5 * (bond.bond_type - 1) as f32),
-                            0.0,
-                            0.0,
-                        ))
-                        * Mat4::from_scale(Vec3::new(
-                            settings.bond_size / bond.bond_type as f32,
-                            bond.length * 1.15,
-                            settings.bond_size / bond.bond_type as f32,
-                        )))
-                    .as_ref(),
-                );
-                &gl.draw_elements(
-                    glow::TRIANGLES,
-                    (MESHES_SIZE - SPHERE_SIZE) as i32,
-                    glow::UNSIGNED_INT,
-                    (SPHERE_SIZE * std::mem::size_of::<u32>()) as i32,
-                );
-                let color_location = Some(color_uniform.as_ref().unwrap().clone());
-                &gl.uniform_3_f32(
-                    color_location,
-                    bond.to_color.x(),
-                    bond.to_color.y(),
-                    bond.to_color.z(),
-                );
-                &gl.uniform_matrix_4_f32_slice(
-                    transform_location.clone(),
-                    false,
-                    (self.mol.bounding_projection
-                        * Mat4::from_quat(self.rotation).transpose()
-                        * Mat4::from_translation(bond.position * 4.5)
-                        * Mat4::from_quat(bond.rotation)
-                        * Mat4::from_translation(Vec3::new(
-                            bond_num as f32 - (0.5 * (bond.bond_type - 1) as f32),
-                            bond.length * 2.25,
-                            0.0,
-                        ))
-                        * Mat4::from_scale(Vec3::new(
-                            settings.bond_size / bond.bond_type as f32,
-                            bond.length * 1.15,
-                            settings.bond_size / bond.bond_type as f32,
-                        )))
-                    .as_ref(),
-                );
-                &gl.draw_elements(
-                    glow::TRIANGLES,
-                    (MESHES_SIZE - SPHERE_SIZE) as i32,
-                    glow::UNSIGNED_INT,
-                    (SPHERE_SIZE * std::mem::size_of::<u32>()) as i32,
-                );
-            }
+/// Indices of the loaded molecule's atoms matching a selection query, such
+/// as `"chain A and resi 10-50"`. Logs an error and returns nothing when the
+/// query can't be read.
+#[js_export]
+fn selectAtoms(query: String) -> Option<Vec<u32>> {
+    let selection = match query.parse::<Selection>() {
+        Ok(selection) => selection,
+        Err(error) => {
+            console!(error, format!("Invalid selection: {}", error));
+            return None;
         }
+    };
+    let state = colco.lock().unwrap();
+    let indices = state
+        .as_ref()
+        .map_or_else(Vec::new, |state| selection.indices(&state.mol));
+    Some(indices.into_iter().map(|index| index as u32).collect())
+}
+
+/// Zooms and pans back to show the whole molecule.
+#[js_export]
+fn resetCamera() {
+    if let Some(state) = colco.lock().unwrap().as_mut() {
+        state.reset_camera();
     }
+}
 
-    // TODO: Decouple, for desktop version
-    pub fn on_mouse_move<MouseMoveEvent: IMouseEvent>(&mut self, location: MouseMoveEvent) {
-        if self.is_mouse_down {
-            self.rotation = self.rotation
-                * Quat::from_rotation_x(-location.movement_y() as f32 * 0.025)
-                * Quat::from_rotation_y(-location.movement_x() as f32 * 0.025);
+/// Highlights the atoms matching a selection query, and bonds between them,
+/// replacing any earlier highlight. Returns whether the query could be read.
+#[js_export]
+fn setHighlight(query: String) -> bool {
+    let selection = match query.parse::<Selection>() {
+        Ok(selection) => selection,
+        Err(error) => {
+            console!(error, format!("Invalid selection: {}", error));
+            return false;
         }
+    };
+    if let Some(state) = colco.lock().unwrap().as_mut() {
+        let indices = selection.indices(&state.mol);
+        state.highlight(indices);
     }
+    true
+}
 
-    pub fn on_mouse_down<MouseDownEvent: IMouseEvent>(&mut self, _location: MouseDownEvent) {
-        self.is_mouse_down = true;
+/// Highlights the atoms with the given indices, such as ones picked with
+/// `onAtomClick`, replacing any earlier highlight.
+#[js_export]
+fn highlightAtoms(indices: Vec<u32>) {
+    if let Some(state) = colco.lock().unwrap().as_mut() {
+        state.highlight(indices.into_iter().map(|index| index as usize));
     }
+}
 
-    pub fn on_mouse_up<MouseUpEvent: IMouseEvent>(&mut self, _location: MouseUpEvent) {
-        self.is_mouse_down = false;
+/// Calls `callback(atom)` when the pointer moves onto an atom or bond, and
+/// `callback(null)` when it leaves. `atom` has the atom's `index`, `element`
+/// and `x`, `y` and `z` coordinates from the file, and `bond` with the bond's
+/// index when a bond was hovered rather than an atom.
+#[js_export]
+fn onAtomHover(callback: Value) {
+    ATOM_HOVER.with(|hover| *hover.borrow_mut() = Some(callback));
+}
+
+/// Calls `callback(atom)` when an atom or bond is clicked, with the same
+/// `atom` as `onAtomHover`.
+#[js_export]
+fn onAtomClick(callback: Value) {
+    ATOM_CLICK.with(|click| *click.borrow_mut() = Some(callback));
+}
+
+/// Calls a callback set by `onAtomHover` or `onAtomClick`. This has to happen
+/// after `colco` is unlocked, since callbacks are free to call back in.
+fn call_atom_callback(
+    callback: &'static std::thread::LocalKey<RefCell<Option<Value>>>,
+    picked: Option<Pick>,
+) {
+    let atom = picked.and_then(|picked| {
+        let state = colco.lock().unwrap();
+        let atom = &state.as_ref()?.mol.atoms[picked.atom];
+        let position = atom.position;
+        let bond = picked.bond.map(|bond| bond as u32);
+        let (index, element) = (picked.atom as u32, atom.symbol.clone());
+        let (x, y, z) = (position.x(), position.y(), position.z());
+        Some(js! {
+            return { index: @{index}, element: @{element}, x: @{x}, y: @{y}, z: @{z}, bond: @{bond} };
+        })
+    });
+    callback.with(|callback| {
+        if let Some(callback) = callback.borrow().as_ref() {
+            js! { @(no_return) @{callback}(@{atom}); }
+        }
+    });
+}
+
+/// Seconds since the epoch, which drags and momentum are timed with.
+fn now() -> f64 {
+    Date::now() / 1000.0
+}
+
+/// Hands an event to the viewer, then calls any callbacks it set off.
+fn dispatch(event: Event) {
+    let response = match colco.lock().unwrap().as_mut() {
+        Some(state) => state.handle(event, now()),
+        None => Response::Nothing,
+    };
+    match response {
+        Response::Hover(hovered) => call_atom_callback(&ATOM_HOVER, hovered),
+        Response::Click(clicked) => call_atom_callback(&ATOM_CLICK, Some(clicked)),
+        Response::Nothing => {}
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
-struct RenderSettings {
-    atom_size: f32,
-    bond_size: f32,
+/// Builds a promise rejected with a `SyntaxError` carrying `line` and
+/// `column` properties.
+fn reject_parse_error(error: ParseError) -> Value {
+    let message = error.to_string();
+    let line = error.line() as u32;
+    let column = error.column().map(|column| column as u32);
+    js! {
+        var error = new SyntaxError(@{message});
+        error.line = @{line};
+        error.column = @{column};
+        return Promise.reject(error);
+    }
 }
 
-lazy_static! {
-    static ref colco: Mutex<Option<Colco>> = Mutex::new(None);
-    static ref render_settings: Mutex<Option<RenderSettings>> = Mutex::new(Some(RenderSettings {
-        atom_size: 2.0,
-        bond_size: 0.5,
-    }));
+fn show_molecule(parsed: Result<Mol, ParseError>) -> Value {
+    match parsed {
+        Ok(mol) => {
+            let mut state = colco.lock().unwrap();
+            *state = Some(Colco::new(mol, &color_scheme.lock().unwrap()));
+            js! { return Promise.resolve(); }
+        }
+        Err(error) => reject_parse_error(error),
+    }
 }
 
-js_deserializable!(RenderSettings);
+/// Returns a promise that rejects with a `SyntaxError` carrying `line` and
+/// `column` properties when the molecule can't be parsed.
+#[js_export]
+fn setMolecule(molecule_data: &str) -> Value {
+    show_molecule(Mol::from_str(molecule_data))
+}
 
-// TODO: Get render_settings via js for spreading new settings into old ones
+/// Loads a multi-record SD file and shows its first record. Records are only
+/// parsed when shown, so the returned promise only reflects the first one.
+#[js_export]
+fn setSdf(sdf_data: String) -> Value {
+    let file = Sdf::new(sdf_data);
+    let shown = file.molecule(0);
+    *sdf.lock().unwrap() = Some(file);
+    match shown {
+        Some(parsed) => show_molecule(parsed),
+        None => js! { return Promise.reject(new RangeError("The SD file has no records")); },
+    }
+}
 
+/// Names of the records in the loaded SD file, in file order.
 #[js_export]
-fn setRenderSettings(render: RenderSettings) {
-    let mut settings = render_settings.lock().unwrap();
-    *settings = Some(render);
+fn listRecords() -> Vec<String> {
+    match sdf.lock().unwrap().as_ref() {
+        Some(file) => file
+            .records()
+            .iter()
+            .map(|record| record.name.clone())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+#[js_export]
+fn showRecord(index: u32) -> Value {
+    let parsed = sdf
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|file| file.molecule(index as usize));
+    match parsed {
+        Some(parsed) => show_molecule(parsed),
+        None => js! { return Promise.reject(new RangeError("No SD record at index " + @{index})); },
+    }
 }
 
+/// The `> <TAG>` data items of a record, keyed by tag.
 #[js_export]
-fn setMolecule(molecule_data: &str) {
-    let mut state = colco.lock().unwrap();
-    *state = Some(Colco::new(Mol::from_str(molecule_data).unwrap()));
+fn getRecordProperties(index: u32) -> HashMap<String, String> {
+    sdf.lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|file| file.properties(index as usize))
+        .map(|properties| properties.into_iter().collect())
+        .unwrap_or_default()
 }
 
 #[js_export]
 fn render(element_id: &str) {
     // Todo: wrap unsafe around native fns only
     unsafe {
-        let (_window, gl, _events_loop, render_loop, shader_version) = {
+        let (canvas, gl, _events_loop, render_loop, shader_version) = {
             let canvas: CanvasElement = document()
                 .get_element_by_id(element_id)
                 .unwrap()
                 .try_into()
                 .unwrap();
-            // TODO: Decouple for desktop version
+            let target = canvas.clone();
             canvas.add_event_listener(move |event: MouseMoveEvent| {
-                colco.lock().unwrap().as_mut().unwrap().on_mouse_move(event);
+                dispatch(input::stdweb::mouse_move(&target, &event));
             });
+            let target = canvas.clone();
             canvas.add_event_listener(move |event: MouseDownEvent| {
-                colco.lock().unwrap().as_mut().unwrap().on_mouse_down(event);
+                dispatch(input::stdweb::mouse_down(&target, &event));
             });
+            let target = canvas.clone();
             canvas.add_event_listener(move |event: MouseUpEvent| {
-                colco.lock().unwrap().as_mut().unwrap().on_mouse_up(event);
+                dispatch(input::stdweb::mouse_up(&target, &event));
+            });
+            canvas.add_event_listener(move |event: MouseWheelEvent| {
+                event.prevent_default();
+                dispatch(input::stdweb::wheel(&event));
+            });
+            // Right-dragging pans, so the menu would only get in the way
+            canvas.add_event_listener(move |event: ContextMenuEvent| {
+                event.prevent_default();
+            });
+            // Touches come as pointer events, which stop the browser from
+            // scrolling or zooming the page for them as well. The canvas
+            // takes focus to hear keys.
+            js! { @(no_return)
+                @{&canvas}.style.touchAction = "none";
+                @{&canvas}.tabIndex = 0;
+            }
+            canvas.add_event_listener(move |event: KeyDownEvent| {
+                if let Some(key) = input::stdweb::key(&event) {
+                    event.prevent_default();
+                    dispatch(key);
+                }
+            });
+            let target = canvas.clone();
+            canvas.add_event_listener(move |event: PointerDownEvent| {
+                if let Some(touch) = input::stdweb::touch(&target, &event, TouchPhase::Start) {
+                    event.prevent_default();
+                    dispatch(touch);
+                }
+            });
+            let target = canvas.clone();
+            canvas.add_event_listener(move |event: PointerMoveEvent| {
+                if let Some(touch) = input::stdweb::touch(&target, &event, TouchPhase::Move) {
+                    dispatch(touch);
+                }
+            });
+            let target = canvas.clone();
+            canvas.add_event_listener(move |event: PointerUpEvent| {
+                if let Some(touch) = input::stdweb::touch(&target, &event, TouchPhase::End) {
+                    dispatch(touch);
+                }
+            });
+            let target = canvas.clone();
+            canvas.add_event_listener(move |event: PointerCancelEvent| {
+                if let Some(touch) = input::stdweb::touch(&target, &event, TouchPhase::Cancel) {
+                    dispatch(touch);
+                }
             });
             document().body().unwrap().append_child(&canvas);
-            // TODO: Desktop context
             let webgl2_context: WebGL2RenderingContext = canvas.get_context().unwrap();
             (
-                (),
+                canvas,
                 glow::Context::from_webgl2_context(webgl2_context),
                 (),
                 glow::RenderLoop::from_request_animation_frame(),
@@ -240,92 +338,35 @@ fn render(element_id: &str) {
             )
         };
 
-        let program = gl.create_program().expect("Cannot create program");
-
-        let shader_sources = [
-            (
-                glow::VERTEX_SHADER,
-                r#"layout(location = 0) in vec3 vert_in;
-                layout(location = 1) in vec3 norm_in;
-                out vec3 norm_out;
-                out vec3 vert_out;
-                uniform mat4 transform;
-                void main() {
-                    vert_out = -normalize (vert_in);
-                    norm_out = norm_in;
-                    gl_Position = transform * vec4(vert_in, 1.0);
-                }"#,
-            ),
-            (
-                glow::FRAGMENT_SHADER,
-                r#"precision mediump float;
-                uniform vec3 u_color;
-                uniform bool u_light;
-                uniform vec3 u_view;
-                in vec3 norm_out;
-                in vec3 vert_out;
-                out vec4 color;
-                void main() {
-                    if (u_light) {
-                        color = vec4(u_color * 0.1 * vec3(dot(vert_out, normalize(reflect(u_view, norm_out)))) + u_color * 0.9, 1.0);
-                    } else {
-                        color = vec4(u_color, 1.0);
-                    }
-                }"#,
-            ),
-        ];
-
-        let mut shaders = Vec::with_capacity(shader_sources.len());
-
-        for (shader_type, shader_source) in shader_sources.iter() {
-            let shader = gl
-                .create_shader(*shader_type)
-                .expect("Cannot create shader");
-            gl.shader_source(shader, &format!("{}\n{}", shader_version, shader_source));
-            gl.compile_shader(shader);
-            if !gl.get_shader_compile_status(shader) {
-                console!(error, gl.get_shader_info_log(shader));
+        let pipeline = match Pipeline::new(&gl, shader_version) {
+            Ok(pipeline) => pipeline,
+            Err(log) => {
+                console!(error, log);
+                return;
             }
-            gl.attach_shader(program, shader);
-            shaders.push(shader);
-        }
-
-        gl.link_program(program);
-        if !gl.get_program_link_status(program) {
-            console!(error, gl.get_program_info_log(program));
-        }
-
-        for shader in shaders {
-            gl.detach_shader(program, shader);
-            gl.delete_shader(shader);
-        }
+        };
 
         // TODO: Compile flags for color, cullface, enables...
-        gl.use_program(Some(program));
         gl.clear_color(0.0, 0.0, 0.0, 0.0);
         gl.enable(glow::CULL_FACE);
         gl.cull_face(glow::BACK);
         gl.enable(glow::DEPTH_TEST);
 
-        let vertex_array = init_buffers_from_constants(&gl);
-        let transform_location = gl.get_uniform_location(program, "transform");
-        let light_uniform = gl.get_uniform_location(program, "u_light");
-        let color_location = gl.get_uniform_location(program, "u_color");
-        let view_location = gl.get_uniform_location(program, "u_view");
-
         render_loop.run(move |running: &mut bool| {
-            gl.clear(glow::COLOR_BUFFER_BIT);
-            colco.lock().unwrap().as_ref().unwrap().render_mol(
-                &render_settings.lock().unwrap().as_ref().unwrap(),
-                &transform_location,
-                &light_uniform,
-                &color_location,
-                &view_location,
-                &gl,
-            );
+            let (width, height) = (canvas.width() as i32, canvas.height().max(1) as i32);
+            gl.viewport(0, 0, width, height);
+            gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+            // Nothing to draw until a molecule has been set successfully
+            if let Some(state) = colco.lock().unwrap().as_mut() {
+                state.camera.aspect = width as f32 / height as f32;
+                state.render_mol(
+                    render_settings.lock().unwrap().as_ref().unwrap(),
+                    &mut pipeline.renderer(&gl),
+                    now(),
+                );
+            }
             if !*running {
-                gl.delete_program(program);
-                gl.delete_vertex_array(vertex_array);
+                pipeline.delete(&gl);
             }
         });
     }
@@ -1,137 +1,281 @@
-use glam::{Mat4, Quat, Vec3};
-use regex::Regex;
+use glam::{Quat, Vec3};
 use std::str::FromStr;
 
+mod cif;
+mod elements;
+mod error;
+mod molfile;
+mod pdb;
+mod perception;
+mod residues;
+mod sdf;
+mod v3000;
+mod xyz;
+
+pub use elements::{rgb, ElementData, PERIODIC_TABLE};
+pub use error::ParseError;
+pub use perception::{assign_bond_orders, perceive_bonds, perceive_pairs, Grid, BOND_TOLERANCE};
+pub use sdf::{Sdf, SdfRecord};
+pub use xyz::XyzFrames;
+
 pub struct Element {
+    /// 0 for query atoms, R-groups and other symbols that aren't elements
+    pub atomic_number: u8,
     pub color: Vec3,
     pub scale: f32,
 }
 
+impl Element {
+    pub fn from_symbol(symbol: &str) -> Self {
+        let data = match ElementData::from_symbol(symbol) {
+            Some(data) => data,
+            None => {
+                return Element {
+                    atomic_number: 0,
+                    color: Vec3::new(0.969, 0.949, 0.824),
+                    scale: 1.0,
+                }
+            }
+        };
+        // Colco's own palette for the common organic elements, Jmol's for
+        // everything else
+        let color = match data.atomic_number {
+            1 => Vec3::new(0.88, 0.88, 0.93),
+            6 => Vec3::new(0.106, 0.149, 0.169),
+            7 => Vec3::new(0.56, 0.89, 0.60),
+            8 => Vec3::new(0.94, 0.33, 0.40),
+            _ => data.jmol_color(),
+        };
+        Element {
+            atomic_number: data.atomic_number,
+            color,
+            // Sized relative to carbon
+            scale: data.vdw_radius / PERIODIC_TABLE[5].vdw_radius,
+        }
+    }
+
+    /// The periodic table entry, or `None` for symbols that aren't elements.
+    pub fn data(&self) -> Option<&'static ElementData> {
+        ElementData::from_atomic_number(self.atomic_number as usize)
+    }
+
+    pub fn is_known(symbol: &str) -> bool {
+        PERIODIC_TABLE.iter().any(|data| data.symbol == symbol)
+    }
+}
+
 pub struct Bond {
-    pub from_color: Vec3,
-    pub to_color: Vec3,
+    /// Index into `Mol::atoms` of the atom the bond starts at
+    pub first_atom: usize,
+    /// Index into `Mol::atoms` of the atom the bond ends at
+    pub second_atom: usize,
     pub position: Vec3,
     pub rotation: Quat,
     pub bond_type: u8,
+    /// Molfile bond stereo code (0 = not stereo, 1 = up, 4 = either, 6 = down)
+    pub stereo: u8,
     pub length: f32,
 }
 
+/// The rotation taking +Y to `direction`, which must be normalized.
+pub fn rotation_from_y(direction: Vec3) -> Quat {
+    let dot = direction.dot(Vec3::unit_y());
+    if (dot + 1.0).abs() < 0.000_001 {
+        Quat::from_axis_angle(Vec3::unit_z(), std::f32::consts::PI)
+    } else if (dot - 1.0).abs() < 0.000_001 {
+        Quat::identity()
+    } else {
+        Quat::from_axis_angle(Vec3::unit_y().cross(direction).normalize(), dot.acos())
+    }
+}
+
+impl Bond {
+    /// Builds a bond between two atoms, placing the rod at the first atom and
+    /// rotating it from +Y to point at the second.
+    pub fn new(first_atom: usize, second_atom: usize, bond_type: u8, atoms: &[Atom]) -> Self {
+        let first = &atoms[first_atom];
+        let second = &atoms[second_atom];
+        let position = first.position;
+        let dest = second.position - position;
+        let rotation = rotation_from_y(dest.normalize());
+        Bond {
+            first_atom,
+            second_atom,
+            position,
+            rotation,
+            bond_type,
+            stereo: 0,
+            length: dest.length(),
+        }
+    }
+}
+
 pub struct Atom {
     pub position: Vec3,
+    pub symbol: String,
     pub element: Element,
+    /// Formal charge, from the atom block, an `M  CHG` property or V3000 `CHG`
+    pub charge: i8,
+    /// Partial charge, from PubChem's `PUBCHEM_MMFF94_PARTIAL_CHARGES` item
+    pub partial_charge: Option<f32>,
+    /// Molfile mass difference (-3..4) from the most common isotope
+    pub mass_difference: i8,
+    /// Absolute isotope mass, from an `M  ISO` property or V3000 `MASS`
+    pub isotope: Option<u16>,
+    /// Molfile stereo parity (0 = none, 1 = odd, 2 = even, 3 = either)
+    pub stereo_parity: u8,
+    /// Atom name within its residue, such as `CA`
+    pub name: Option<String>,
+    /// Serial number the file gave the atom
+    pub serial: Option<u32>,
+    pub residue_name: Option<String>,
+    pub residue_number: Option<i32>,
+    pub chain_id: Option<String>,
+    pub b_factor: Option<f32>,
+    pub occupancy: Option<f32>,
+    /// Whether the atom came from a `HETATM` record
+    pub hetero: bool,
+}
+
+impl Atom {
+    pub fn new(symbol: &str, position: Vec3) -> Self {
+        Atom {
+            position,
+            symbol: symbol.to_string(),
+            element: Element::from_symbol(symbol),
+            charge: 0,
+            partial_charge: None,
+            mass_difference: 0,
+            isotope: None,
+            stereo_parity: 0,
+            name: None,
+            serial: None,
+            residue_name: None,
+            residue_number: None,
+            chain_id: None,
+            b_factor: None,
+            occupancy: None,
+            hetero: false,
+        }
+    }
 }
 
 pub struct Mol {
-    pub bounding_projection: Mat4,
-    pub bounding_size: f32,
+    /// The molfile's first header line, or a PDB entry's ID code
+    pub name: String,
+    /// The molfile's third header line, or a PDB entry's title
+    pub comment: String,
     pub atoms: Vec<Atom>,
     pub bonds: Vec<Bond>,
 }
 
-impl FromStr for Mol {
-    type Err = regex::Error;
+impl Mol {
+    pub fn new(atoms: Vec<Atom>, bonds: Vec<Bond>) -> Self {
+        Mol {
+            name: String::new(),
+            comment: String::new(),
+            atoms,
+            bonds,
+        }
+    }
 
-    fn from_str(mol: &str) -> Result<Self, Self::Err> {
-        let mut bounding_size = 0.0;
-        let atoms = Regex::new(r#"(\-?[0-9][\.][0-9]+[0-9][ \t]+)(\-?[0-9][\.][0-9]+[0-9][ \t]+)(\-?[0-9][\.][0-9]+[0-9][ \t]+)(\w)"#)?
-            .captures_iter(mol)
-            .filter_map(|cap| {
-                let groups = (cap.get(1), cap.get(2), cap.get(3), cap.get(4));
-                match groups {
-                    (Some(x), Some(y), Some(z), Some(a)) => {
-                        let position = Vec3::new(
-                            x.as_str().trim().parse().unwrap(),
-                            y.as_str().trim().parse().unwrap(),
-                            z.as_str().trim().parse().unwrap(),
-                        );
-                        let max_element = position.max_element();
-                        if max_element > bounding_size {
-                            bounding_size = max_element;
-                        }
-                        // TODO: More atom types
-                        Some(Atom {
-                            position,
-                            element: match a.as_str().trim() {
-                                "C" => Element {
-                                    color: Vec3::new(0.106, 0.149, 0.169),
-                                    scale: 1.0,
-                                },
-                                "O" => Element {
-                                    color: Vec3::new(0.94, 0.33, 0.40),
-                                    scale: 1.0,
-                                },
-                                "N" => Element {
-                                    color: Vec3::new(0.56, 0.89, 0.60),
-                                    scale: 1.0,
-                                },
-                                "H" => Element {
-                                    color: Vec3::new(0.88, 0.88, 0.93),
-                                    scale: 0.8,
-                                },
-                                _ => Element {
-                                    color: Vec3::new(0.969, 0.949, 0.824),
-                                    scale: 1.0,
-                                },
-                            },
-                        }
-                    )},
-                    _x => None,
-                }
-            })
+    /// The mean atom position, which the camera centers on.
+    pub fn centroid(&self) -> Vec3 {
+        if self.atoms.is_empty() {
+            return Vec3::zero();
+        }
+        let sum = self
+            .atoms
+            .iter()
+            .fold(Vec3::zero(), |sum, atom| sum + atom.position);
+        sum / self.atoms.len() as f32
+    }
+
+    /// The distance from `center` to the furthest atom.
+    pub fn radius(&self, center: Vec3) -> f32 {
+        self.atoms
+            .iter()
+            .map(|atom| (atom.position - center).length())
+            .fold(0.0, f32::max)
+    }
+}
+
+/// The file formats `Mol` can be read from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Molfile,
+    Pdb,
+    Cif,
+    Xyz,
+}
+
+impl Format {
+    /// Guesses the format from the content. Molfiles are recognised by the
+    /// version on their counts line, mmCIF files by opening with a `data_`
+    /// block, XYZ files by an atom count followed by element lines, PDB files
+    /// by their `ATOM`/`HETATM` records, and anything else is read as a
+    /// molfile.
+    pub fn detect(text: &str) -> Self {
+        let counts = text.lines().nth(3).unwrap_or("").trim_end();
+        let first = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .unwrap_or("");
+        if counts.ends_with("V2000") || counts.ends_with("V3000") {
+            Format::Molfile
+        } else if first.to_ascii_lowercase().starts_with("data_") {
+            Format::Cif
+        } else if first.parse::<usize>().is_ok() && Format::looks_like_xyz_atom(text) {
+            Format::Xyz
+        } else if text
+            .lines()
+            .any(|line| line.starts_with("ATOM  ") || line.starts_with("HETATM"))
+        {
+            Format::Pdb
+        } else {
+            Format::Molfile
+        }
+    }
+
+    /// Whether the third line reads as `Element x y z`.
+    fn looks_like_xyz_atom(text: &str) -> bool {
+        let fields = text
+            .lines()
+            .skip_while(|line| line.trim().is_empty())
+            .nth(2)
+            .unwrap_or("")
+            .split_whitespace()
             .collect::<Vec<_>>();
+        fields.len() >= 4
+            && fields[0].starts_with(|character: char| character.is_ascii_alphanumeric())
+            && fields[1..4]
+                .iter()
+                .all(|field| field.parse::<f32>().is_ok())
+    }
+}
 
-        let bonds =
-            // std regex doesn't support lookbehinds, so we match for the line before
-            // ours to have a number at the end (i.e [0-9]\s)
-            Regex::new(r#"(?mi)^\s*((?:0|[1-9])[0-9]*)\s+((?:0|[1-9])[0-9]*)\s+((?:0|[1-9])[0-9]*)\s+((?:0|[1-9])[0-9]*)\s*$"#)?
-                .captures_iter(mol)
-                .filter_map(|cap| {
-                    let groups = (cap.get(1), cap.get(2), cap.get(3), cap.get(4));
-                    match groups {
-                        (Some(first_atom), Some(second_atom), Some(bond_type), Some(_useless_zeroes_mol_gives_me)) => {
-                            let first_atom: &Atom = &atoms[
-                                first_atom.as_str().trim().parse::<usize>().unwrap() - 1
-                            ];
-                            let second_atom: &Atom = &atoms[
-                                second_atom.as_str().trim().parse::<usize>().unwrap() - 1
-                            ];
-                            let position = first_atom.position;
-                            let dest = second_atom.position.clone() - position;
-                            let forward = (dest).normalize();
-                            let dot = forward.dot(Vec3::unit_y());
-                            let rotation = if (dot + 1.0).abs() < 0.000_001 {
-                                Quat::from_axis_angle(Vec3::unit_z(), std::f32::consts::PI)
-                            } else if (dot - 1.0).abs() < 0.000_001 {
-                                Quat::identity()
-                            } else {
-                                Quat::from_axis_angle(Vec3::unit_y().cross(forward).normalize(), dot.acos())
-                            };
-                            Some(Bond {
-                                from_color: first_atom.element.color,
-                                to_color: second_atom.element.color,
-                                position,
-                                rotation,
-                                bond_type: bond_type.as_str().trim().parse().unwrap(),
-                                length: dest.length()
-                            })
-                        }
-                        _x => None,
-                    }
-                })
-                .collect::<Vec<_>>();
-        let bounding_projection = Mat4::orthographic_rh_gl(
-            -bounding_size * 4.5 * 2.0,
-            bounding_size * 4.5 * 2.0,
-            -bounding_size * 4.5 * 2.0,
-            bounding_size * 4.5 * 2.0,
-            -bounding_size * 4.5 * 2.0,
-            bounding_size * 4.5 * 2.0,
-        );
-        Ok(Mol {
-            bounding_projection,
-            bounding_size,
-            atoms,
-            bonds,
-        })
+impl Mol {
+    pub fn parse(text: &str, format: Format) -> Result<Self, ParseError> {
+        match format {
+            Format::Molfile => molfile::parse(text),
+            Format::Pdb => pdb::parse(text),
+            Format::Cif => cif::parse(text),
+            // The first frame of a trajectory, use `XyzFrames` for the rest
+            Format::Xyz => XyzFrames::new(text)
+                .next()
+                .unwrap_or(Err(ParseError::TruncatedBlock {
+                    line: 1,
+                    block: "atom count line",
+                })),
+        }
+    }
+}
+
+impl FromStr for Mol {
+    type Err = ParseError;
+
+    fn from_str(mol: &str) -> Result<Self, Self::Err> {
+        Mol::parse(mol, Format::detect(mol))
     }
 }
@@ -0,0 +1,719 @@
+//! Reader for mmCIF (PDBx) files, which the RCSB distributes for every entry
+//! and exclusively for assemblies too large for the PDB format.
+//!
+//! A CIF file is a stream of `_category.item value` pairs and `loop_` tables.
+//! Atoms come from the `_atom_site` loop and are read the way the PDB reader
+//! reads `ATOM` records, with author numbering and chain IDs, so both formats
+//! give the same `Mol`. Bonds come from the standard residue templates,
+//! `_chem_comp_bond` for other residues, and `_struct_conn` links. Residues
+//! none of those cover, such as ligands in files from the RCSB, which leave
+//! out `_chem_comp_bond`, get bonds from perception.
+use super::pdb::capitalize_symbol;
+use super::residues::{self, BondSet};
+use super::{perception, Atom, Element, Mol, ParseError};
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+#[derive(Clone, Copy)]
+struct Value<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+    quoted: bool,
+}
+
+impl<'a> Value<'a> {
+    /// Unquoted `?` (unknown) and `.` (not applicable) stand for no value.
+    fn is_missing(&self) -> bool {
+        !self.quoted && (self.text == "?" || self.text == ".")
+    }
+
+    fn parse<T: FromStr>(&self, expected: &'static str) -> Result<T, ParseError> {
+        self.text.parse().map_err(|_| ParseError::InvalidField {
+            line: self.line,
+            column: self.column,
+            expected,
+            found: self.text.to_string(),
+        })
+    }
+}
+
+enum Token<'a> {
+    Data,
+    Loop,
+    Tag(&'a str),
+    Value(Value<'a>),
+}
+
+/// Splits CIF text into tokens, handling `#` comments, quoted values, and
+/// `;` delimited values that span several lines.
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>, ParseError> {
+    let mut tokens = Vec::new();
+    // Byte offset and line of the start of an open `;` value
+    let mut multiline: Option<(usize, usize)> = None;
+    let mut offset = 0;
+    let mut number = 0;
+    for raw in text.split('\n') {
+        let line_start = offset;
+        offset += raw.len() + 1;
+        number += 1;
+        let line = raw.trim_end_matches('\r');
+
+        let mut position = 0;
+        if let Some((start, start_line)) = multiline {
+            if !line.starts_with(';') {
+                continue;
+            }
+            let value = text[start..line_start - 1].trim_end_matches('\r');
+            tokens.push(Token::Value(Value {
+                text: value,
+                line: start_line,
+                column: 1,
+                quoted: true,
+            }));
+            multiline = None;
+            position = 1;
+        } else if line.starts_with(';') {
+            multiline = Some((line_start + 1, number));
+            continue;
+        }
+
+        let bytes = line.as_bytes();
+        while position < bytes.len() {
+            let character = bytes[position];
+            if character.is_ascii_whitespace() {
+                position += 1;
+                continue;
+            }
+            if character == b'#' {
+                break;
+            }
+            let column = position + 1;
+            if character == b'\'' || character == b'"' {
+                // A quote only closes a value when whitespace follows it
+                let close = (position + 1..bytes.len()).find(|&index| {
+                    bytes[index] == character
+                        && bytes
+                            .get(index + 1)
+                            .is_none_or(|next| next.is_ascii_whitespace())
+                });
+                let close = match close {
+                    Some(close) => close,
+                    None => {
+                        return Err(ParseError::InvalidField {
+                            line: number,
+                            column,
+                            expected: "a closing quote",
+                            found: line[position..].to_string(),
+                        })
+                    }
+                };
+                tokens.push(Token::Value(Value {
+                    text: &line[position + 1..close],
+                    line: number,
+                    column,
+                    quoted: true,
+                }));
+                position = close + 1;
+                continue;
+            }
+            let end = (position..bytes.len())
+                .find(|&index| bytes[index].is_ascii_whitespace())
+                .unwrap_or(bytes.len());
+            let word = &line[position..end];
+            let lowercase = word.to_ascii_lowercase();
+            tokens.push(if word.starts_with('_') {
+                Token::Tag(word)
+            } else if lowercase == "loop_" {
+                Token::Loop
+            } else if lowercase.starts_with("data_") {
+                Token::Data
+            } else {
+                Token::Value(Value {
+                    text: word,
+                    line: number,
+                    column,
+                    quoted: false,
+                })
+            });
+            position = end;
+        }
+    }
+    if let Some((_, start_line)) = multiline {
+        return Err(ParseError::TruncatedBlock {
+            line: start_line,
+            block: "multi-line string",
+        });
+    }
+    Ok(tokens)
+}
+
+/// The items of one category, either a `loop_` or single `_category.item`
+/// pairs, as rows of values.
+#[derive(Default)]
+struct Table<'a> {
+    columns: Vec<&'a str>,
+    values: Vec<Value<'a>>,
+}
+
+impl<'a> Table<'a> {
+    fn rows(&self) -> std::slice::Chunks<'_, Value<'a>> {
+        self.values.chunks(self.columns.len().max(1))
+    }
+
+    /// Index of the first of `names` that is a column of the table.
+    fn column(&self, names: &[&str]) -> Option<usize> {
+        names
+            .iter()
+            .find_map(|name| self.columns.iter().position(|column| column == name))
+    }
+
+    /// The first non-missing value among `names` in `row`.
+    fn get(&self, row: &[Value<'a>], names: &[&str]) -> Option<Value<'a>> {
+        names
+            .iter()
+            .filter_map(|name| self.columns.iter().position(|column| column == name))
+            .map(|index| row[index])
+            .find(|value| !value.is_missing())
+    }
+}
+
+/// Splits `_category.item` into its two halves.
+fn split_tag(tag: &str) -> (&str, &str) {
+    match tag.find('.') {
+        Some(dot) => (&tag[..dot], &tag[dot + 1..]),
+        None => (tag, ""),
+    }
+}
+
+/// Gathers the first data block's tokens into tables keyed by category.
+fn tables(tokens: Vec<Token<'_>>) -> Result<HashMap<&str, Table<'_>>, ParseError> {
+    let mut tables: HashMap<&str, Table> = HashMap::new();
+    let mut tokens = tokens.into_iter().peekable();
+    let mut blocks = 0;
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Data => {
+                blocks += 1;
+                if blocks > 1 {
+                    break;
+                }
+            }
+            Token::Tag(tag) => {
+                if let Some(Token::Value(value)) = tokens.peek() {
+                    let (category, item) = split_tag(tag);
+                    let table = tables.entry(category).or_default();
+                    table.columns.push(item);
+                    table.values.push(*value);
+                    tokens.next();
+                }
+            }
+            Token::Loop => {
+                let mut tags = Vec::new();
+                while let Some(Token::Tag(tag)) = tokens.peek() {
+                    tags.push(*tag);
+                    tokens.next();
+                }
+                let mut values = Vec::new();
+                while let Some(Token::Value(value)) = tokens.peek() {
+                    values.push(*value);
+                    tokens.next();
+                }
+                if tags.is_empty() {
+                    continue;
+                }
+                if values.len() % tags.len() != 0 {
+                    return Err(ParseError::TruncatedBlock {
+                        line: values.last().map_or(0, |value| value.line),
+                        block: "loop",
+                    });
+                }
+                let (category, _) = split_tag(tags[0]);
+                tables.insert(
+                    category,
+                    Table {
+                        columns: tags.iter().map(|tag| split_tag(tag).1).collect(),
+                        values,
+                    },
+                );
+            }
+            Token::Value(_) => {}
+        }
+    }
+    Ok(tables)
+}
+
+fn parse_optional<T: FromStr>(
+    value: Option<Value>,
+    expected: &'static str,
+) -> Result<Option<T>, ParseError> {
+    value.map(|value| value.parse(expected)).transpose()
+}
+
+fn bond_order(value: &str) -> u8 {
+    match value.to_ascii_uppercase().as_str() {
+        "DOUB" => 2,
+        "TRIP" => 3,
+        _ => 1,
+    }
+}
+
+/// Guesses the element from an atom name, for files without `type_symbol`.
+/// Like the PDB reader, a name is a one letter element after any leading
+/// digits, so `CA` is an α-carbon and `1HB` a hydrogen. Ions, whose one atom
+/// is named after the residue, and het group names whose letters make a two
+/// letter element, such as `FE` or `CL1`, are read whole. Het group names
+/// starting with H are hydrogens all the same, which ligands have far more of
+/// than mercury or holmium.
+fn symbol_from_name(name: &str, residue: &str, hetero: bool) -> String {
+    let whole = capitalize_symbol(name);
+    if name == residue && Element::is_known(&whole) {
+        return whole;
+    }
+    let letters = name
+        .trim_start_matches(|character: char| character.is_ascii_digit())
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .collect::<String>();
+    let two = capitalize_symbol(&letters);
+    if hetero && two.len() == 2 && !two.starts_with('H') && Element::is_known(&two) {
+        return two;
+    }
+    two.get(0..1).unwrap_or("").to_string()
+}
+
+pub fn parse(text: &str) -> Result<Mol, ParseError> {
+    let last_line = text.lines().count();
+    let tables = tables(tokenize(text)?)?;
+    let site = tables
+        .get("_atom_site")
+        .ok_or(ParseError::MissingCategory {
+            line: last_line,
+            category: "_atom_site",
+        })?;
+    for required in &["Cartn_x", "Cartn_y", "Cartn_z"] {
+        if site.column(&[required]).is_none() {
+            return Err(ParseError::MissingCategory {
+                line: last_line,
+                category: "_atom_site coordinates",
+            });
+        }
+    }
+
+    let mut atoms = Vec::new();
+    let mut first_model = None;
+    let mut first_alternates = HashMap::new();
+    for row in site.rows() {
+        let coordinate = |name: &str, expected| match site.get(row, &[name]) {
+            Some(value) => value.parse(expected),
+            None => Err(ParseError::InvalidField {
+                line: row[0].line,
+                column: row[0].column,
+                expected,
+                found: String::new(),
+            }),
+        };
+        let model = site
+            .get(row, &["pdbx_PDB_model_num"])
+            .map(|value| value.text);
+        if *first_model.get_or_insert(model) != model {
+            continue;
+        }
+        // The first conformer of each atom, found by its name and residue,
+        // whatever it is labelled
+        if let Some(alternate) = site.get(row, &["label_alt_id"]) {
+            let atom_key = [
+                &["auth_atom_id", "label_atom_id"][..],
+                &["auth_asym_id", "label_asym_id"],
+                &["auth_seq_id", "label_seq_id"],
+                &["pdbx_PDB_ins_code"],
+            ]
+            .iter()
+            .map(|names| site.get(row, names).map_or("", |value| value.text))
+            .collect::<Vec<_>>();
+            if *first_alternates.entry(atom_key).or_insert(alternate.text) != alternate.text {
+                continue;
+            }
+        }
+
+        let position = Vec3::new(
+            coordinate("Cartn_x", "an x coordinate")?,
+            coordinate("Cartn_y", "a y coordinate")?,
+            coordinate("Cartn_z", "a z coordinate")?,
+        );
+        let text = |names: &[&str]| site.get(row, names).map(|value| value.text.to_string());
+        let name = text(&["auth_atom_id", "label_atom_id"]);
+        let residue_name = text(&["auth_comp_id", "label_comp_id"]);
+        let hetero = site
+            .get(row, &["group_PDB"])
+            .is_some_and(|value| value.text == "HETATM");
+        let (symbol, symbol_value) = match site.get(row, &["type_symbol"]) {
+            Some(value) => (capitalize_symbol(value.text), value),
+            None => {
+                let value = site
+                    .get(row, &["auth_atom_id", "label_atom_id"])
+                    .unwrap_or(row[0]);
+                let residue = residue_name.as_deref().unwrap_or("");
+                (symbol_from_name(value.text, residue, hetero), value)
+            }
+        };
+        if !Element::is_known(&symbol) && symbol != "D" {
+            return Err(ParseError::UnknownElement {
+                line: symbol_value.line,
+                column: symbol_value.column,
+                symbol,
+            });
+        }
+
+        let mut atom = Atom::new(&symbol, position);
+        atom.name = name;
+        atom.serial = parse_optional(site.get(row, &["id"]), "an atom serial number")?;
+        atom.residue_name = residue_name;
+        atom.residue_number = parse_optional(
+            site.get(row, &["auth_seq_id", "label_seq_id"]),
+            "a residue number",
+        )?;
+        atom.chain_id = text(&["auth_asym_id", "label_asym_id"]);
+        atom.occupancy = parse_optional(site.get(row, &["occupancy"]), "an occupancy")?;
+        atom.b_factor = parse_optional(site.get(row, &["B_iso_or_equiv"]), "a temperature factor")?;
+        atom.charge =
+            parse_optional(site.get(row, &["pdbx_formal_charge"]), "a formal charge")?.unwrap_or(0);
+        atom.hetero = hetero;
+        atoms.push(atom);
+    }
+
+    let mut bonds = BondSet::new();
+    for (from, to, bond_type) in residues::infer_bonds(&atoms) {
+        bonds.insert(from, to, bond_type);
+    }
+
+    // Bonds for ligands and other non-standard residues, by residue and atom name
+    let mut described = HashSet::new();
+    if let Some(chem_comp_bond) = tables.get("_chem_comp_bond") {
+        let mut templates: HashMap<&str, Vec<(&str, &str, u8)>> = HashMap::new();
+        for row in chem_comp_bond.rows() {
+            let field = |name| chem_comp_bond.get(row, &[name]).map(|value| value.text);
+            if let (Some(residue), Some(from), Some(to)) =
+                (field("comp_id"), field("atom_id_1"), field("atom_id_2"))
+            {
+                let order = field("value_order").map_or(1, bond_order);
+                templates
+                    .entry(residue)
+                    .or_default()
+                    .push((from, to, order));
+            }
+        }
+        described.extend(templates.keys().map(|residue| residue.to_string()));
+        for range in residues::residue_ranges(&atoms) {
+            let residue = atoms[range.start].residue_name.as_deref().unwrap_or("");
+            let template = match templates.get(residue) {
+                Some(template) => template,
+                None => continue,
+            };
+            let names: HashMap<&str, usize> = range
+                .filter_map(|index| Some((atoms[index].name.as_deref()?, index)))
+                .collect();
+            for (from, to, order) in template {
+                if let (Some(&from), Some(&to)) = (names.get(from), names.get(to)) {
+                    bonds.insert(from, to, *order);
+                }
+            }
+        }
+    }
+
+    // Disulfides, covalent links between residues and metal coordination
+    if let Some(struct_conn) = tables.get("_struct_conn") {
+        let mut lookup = HashMap::new();
+        for (index, atom) in atoms.iter().enumerate() {
+            lookup.insert(
+                (
+                    atom.chain_id.as_deref(),
+                    atom.residue_number,
+                    atom.name.as_deref(),
+                ),
+                index,
+            );
+        }
+        for row in struct_conn.rows() {
+            let kind = struct_conn
+                .get(row, &["conn_type_id"])
+                .map_or("", |value| value.text);
+            if !["covale", "disulf", "metalc"].contains(&kind) {
+                continue;
+            }
+            let mut partners = [None, None];
+            for (partner, prefix) in partners.iter_mut().zip(&["ptnr1", "ptnr2"]) {
+                let field = |item: &str| {
+                    let auth = format!("{}_auth_{}", prefix, item);
+                    let label = format!("{}_label_{}", prefix, item);
+                    struct_conn.get(row, &[auth.as_str(), label.as_str()])
+                };
+                let residue_number = match field("seq_id") {
+                    Some(value) => Some(value.parse("a residue number")?),
+                    None => None,
+                };
+                *partner = lookup
+                    .get(&(
+                        field("asym_id").map(|value| value.text),
+                        residue_number,
+                        field("atom_id").map(|value| value.text),
+                    ))
+                    .copied();
+            }
+            if let [Some(from), Some(to)] = partners {
+                bonds.insert(from, to, 1);
+            }
+        }
+    }
+
+    perception::fill_missing(&atoms, &mut bonds, |index| {
+        let atom = &atoms[index];
+        residues::has_template(atom)
+            || atom
+                .residue_name
+                .as_ref()
+                .is_some_and(|residue| described.contains(residue))
+    });
+
+    let single = |category: &str, item: &str| {
+        tables
+            .get(category)
+            .and_then(|table| table.get(&table.values, &[item]))
+            .map_or(String::new(), |value| value.text.trim().to_string())
+    };
+    let bonds = bonds.into_bonds(&atoms);
+    Ok(Mol {
+        name: single("_entry", "id"),
+        // Multi-line titles are joined with spaces, as PDB files' are
+        comment: single("_struct", "title")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+        ..Mol::new(atoms, bonds)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Alanine and glycine with an alternate location, a heme iron linked to
+    /// a ligand oxygen with a quoted name, an ethanol described by
+    /// `_chem_comp_bond`, and a second model
+    const PEPTIDE: &str = "\
+data_1ABC
+# comment
+_entry.id   1ABC
+_struct.title
+;A SMALL
+TEST
+;
+loop_
+_atom_site.group_PDB
+_atom_site.id
+_atom_site.type_symbol
+_atom_site.label_atom_id
+_atom_site.label_alt_id
+_atom_site.label_comp_id
+_atom_site.label_asym_id
+_atom_site.label_seq_id
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+_atom_site.occupancy
+_atom_site.B_iso_or_equiv
+_atom_site.pdbx_formal_charge
+_atom_site.auth_seq_id
+_atom_site.auth_asym_id
+_atom_site.pdbx_PDB_model_num
+ATOM   1 N  N   . ALA A 1 -0.677 -1.230 -0.491 1.00 0.00 ? 1 A 1
+ATOM   2 C  CA  . ALA A 1 -0.001 0.064 -0.491 1.00 0.00 ? 1 A 1
+ATOM   3 C  C   . ALA A 1 1.499 -0.110 -0.491 1.00 0.00 ? 1 A 1
+ATOM   4 O  O   . ALA A 1 2.030 -1.227 -0.502 1.00 0.00 ? 1 A 1
+ATOM   5 C  CB  . ALA A 1 -0.509 0.856 0.727 1.00 0.00 ? 1 A 1
+ATOM   6 H  H   . ALA A 1 -1.680 -1.230 -0.491 1.00 0.00 ? 1 A 1
+ATOM   7 N  N   . GLY A 2 2.250 0.987 -0.491 1.00 0.00 ? 2 A 1
+ATOM   8 C  CA  A GLY A 2 3.700 0.987 -0.491 0.60 0.00 ? 2 A 1
+ATOM   9 C  CA  B GLY A 2 3.650 1.100 -0.400 0.40 0.00 ? 2 A 1
+ATOM   10 C C   . GLY A 2 4.200 2.400 -0.491 1.00 0.00 ? 2 A 1
+ATOM   11 O O   . GLY A 2 3.500 3.400 -0.491 1.00 0.00 ? 2 A 1
+HETATM 12 FE FE  . HEM B . 10.000 10.000 10.000 1.00 20.00 2 101 A 1
+HETATM 13 O  \"O1'\" . LIG C . 12.000 10.000 10.000 1.00 20.00 ? 102 A 1
+HETATM 14 C  C1  . EOH D . 20.000 0.000 0.000 1.00 20.00 ? 103 A 1
+HETATM 15 C  C2  . EOH D . 21.500 0.000 0.000 1.00 20.00 ? 103 A 1
+HETATM 16 O  O   . EOH D . 22.200 1.200 0.000 1.00 20.00 ? 103 A 1
+ATOM   17 N  N   . ALA A 1 -0.677 -1.230 -0.491 1.00 0.00 ? 1 A 2
+#
+loop_
+_chem_comp_bond.comp_id
+_chem_comp_bond.atom_id_1
+_chem_comp_bond.atom_id_2
+_chem_comp_bond.value_order
+EOH C1 C2 SING
+EOH C2 O  DOUB
+loop_
+_struct_conn.id
+_struct_conn.conn_type_id
+_struct_conn.ptnr1_auth_asym_id
+_struct_conn.ptnr1_auth_seq_id
+_struct_conn.ptnr1_label_atom_id
+_struct_conn.ptnr2_auth_asym_id
+_struct_conn.ptnr2_auth_seq_id
+_struct_conn.ptnr2_label_atom_id
+metalc1 metalc A 101 FE A 102 \"O1'\"
+hydrog1 hydrog A 1 N A 2 O
+";
+
+    fn parse_error(text: &str) -> ParseError {
+        parse(text).err().expect("an error")
+    }
+
+    /// The values of `text`, which holds no tags or loops.
+    fn values(text: &str) -> Vec<(String, usize, usize)> {
+        tokenize(text)
+            .unwrap()
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Value(value) => Some((value.text.to_string(), value.line, value.column)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_atom_site() {
+        let mol = parse(PEPTIDE).unwrap();
+        assert_eq!(mol.name, "1ABC");
+        let iron = &mol.atoms[10];
+        assert_eq!(iron.symbol, "Fe");
+        assert_eq!(iron.charge, 2);
+        // Author numbering and chains, as in the PDB format
+        assert_eq!(iron.residue_number, Some(101));
+        assert_eq!(iron.chain_id.as_deref(), Some("A"));
+        assert_eq!(iron.position, Vec3::new(10.0, 10.0, 10.0));
+        assert!(iron.hetero && !mol.atoms[0].hetero);
+    }
+
+    #[test]
+    fn keeps_the_first_alternate_location_and_model() {
+        let mol = parse(PEPTIDE).unwrap();
+        assert_eq!(mol.atoms.len(), 15);
+        assert_eq!(mol.atoms[7].occupancy, Some(0.6));
+        assert_eq!(mol.atoms[8].name.as_deref(), Some("C"));
+    }
+
+    #[test]
+    fn keeps_atoms_with_only_a_later_alternate_location() {
+        let text = PEPTIDE
+            .replace("CA  A GLY", "CA  B GLY")
+            .replace("CA  B GLY A 2 3.650", "CA  C GLY A 2 3.650")
+            .replace("CB  . ALA", "CB  B ALA");
+        let mol = parse(&text).unwrap();
+        assert_eq!(mol.atoms.len(), 15);
+        assert_eq!(mol.atoms[4].name.as_deref(), Some("CB"));
+        assert_eq!(mol.atoms[7].position, Vec3::new(3.7, 0.987, -0.491));
+    }
+
+    #[test]
+    fn reads_multi_line_values() {
+        let mol = parse(PEPTIDE).unwrap();
+        assert_eq!(mol.comment, "A SMALL TEST");
+        assert_eq!(
+            values("_a.b\n;first\n second\n;\n_a.c 1"),
+            [
+                ("first\n second".to_string(), 2, 1),
+                ("1".to_string(), 5, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_quoted_values() {
+        let mol = parse(PEPTIDE).unwrap();
+        assert_eq!(mol.atoms[11].name.as_deref(), Some("O1'"));
+        // A quote only closes a value when whitespace follows it
+        assert_eq!(
+            values("'N'A' \"two words\" . # comment"),
+            [
+                ("N'A".to_string(), 1, 1),
+                ("two words".to_string(), 1, 7),
+                (".".to_string(), 1, 19),
+            ]
+        );
+        assert!(!tokenize("_a.b '.'")
+            .unwrap()
+            .iter()
+            .any(|token| matches!(token, Token::Value(value) if value.is_missing())));
+    }
+
+    #[test]
+    fn reads_bonds() {
+        let mol = parse(PEPTIDE).unwrap();
+        let bonds = mol
+            .bonds
+            .iter()
+            .map(|bond| (bond.first_atom, bond.second_atom, bond.bond_type))
+            .collect::<Vec<_>>();
+        // From _struct_conn, and _chem_comp_bond with its orders
+        assert!(bonds.contains(&(10, 11, 1)));
+        assert!(bonds.contains(&(12, 13, 1)));
+        assert!(bonds.contains(&(13, 14, 2)));
+        // Hydrogen bonds aren't drawn
+        assert!(!bonds.contains(&(0, 9, 1)));
+    }
+
+    #[test]
+    fn locates_errors() {
+        let text = PEPTIDE.replace("-0.001 0.064", "-0.0x1 0.064");
+        let line = text.lines().nth(26).unwrap();
+        let column = line.find("-0.0x1").unwrap() + 1;
+        let error = parse_error(&text);
+        assert_eq!((error.line(), error.column()), (27, Some(column)));
+
+        assert_eq!(
+            parse_error("data_x\n_a.b\n;abc\n"),
+            ParseError::TruncatedBlock {
+                line: 3,
+                block: "multi-line string",
+            }
+        );
+        let error = parse_error("data_x\n_a.b 'abc\n");
+        assert_eq!((error.line(), error.column()), (2, Some(6)));
+        assert!(matches!(
+            parse_error("data_x\n_a.b 1\n"),
+            ParseError::MissingCategory { .. }
+        ));
+    }
+
+    #[test]
+    fn reads_elements_from_names_without_type_symbol() {
+        let text = "\
+data_names
+loop_
+_atom_site.group_PDB
+_atom_site.label_atom_id
+_atom_site.label_comp_id
+_atom_site.label_seq_id
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+ATOM   CA  ALA 1 0.0 0.0 0.0
+ATOM   HG  SER 2 2.0 0.0 0.0
+ATOM   NE2 HIS 3 4.0 0.0 0.0
+ATOM   1HB ALA 1 6.0 0.0 0.0
+HETATM CA  CA  . 8.0 0.0 0.0
+HETATM FE  HEM . 10.0 0.0 0.0
+HETATM CHA HEM . 12.0 0.0 0.0
+HETATM CL1 LIG . 14.0 0.0 0.0
+HETATM HG1 LIG . 16.0 0.0 0.0
+";
+        let mol = parse(text).unwrap();
+        let symbols = mol
+            .atoms
+            .iter()
+            .map(|atom| atom.symbol.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(symbols, ["C", "H", "N", "H", "Ca", "Fe", "C", "Cl", "H"]);
+    }
+}
@@ -0,0 +1,231 @@
+//! Properties of every element, indexed by atomic number.
+//!
+//! Masses are standard atomic weights, or the mass number of the longest
+//! lived isotope for elements without one. Covalent radii are from Cordero et
+//! al. (2008), with Pyykkö and Atsumi (2009) for elements past curium. Van der
+//! Waals radii are Bondi's, with Blue Obelisk values where he gives none.
+//! Jmol stops at meitnerium, so heavier elements reuse its color.
+use glam::Vec3;
+
+pub struct ElementData {
+    pub atomic_number: u8,
+    pub symbol: &'static str,
+    /// Atomic mass in daltons
+    pub mass: f32,
+    /// Single bond covalent radius in ångströms
+    pub covalent_radius: f32,
+    /// Van der Waals radius in ångströms
+    pub vdw_radius: f32,
+    /// CPK color as `0xRRGGBB`
+    pub cpk: u32,
+    /// Jmol color as `0xRRGGBB`
+    pub jmol: u32,
+}
+
+impl ElementData {
+    const fn new(
+        atomic_number: u8,
+        symbol: &'static str,
+        mass: f32,
+        covalent_radius: f32,
+        vdw_radius: f32,
+        cpk: u32,
+        jmol: u32,
+    ) -> Self {
+        ElementData {
+            atomic_number,
+            symbol,
+            mass,
+            covalent_radius,
+            vdw_radius,
+            cpk,
+            jmol,
+        }
+    }
+
+    /// Looks an element up by symbol. Deuterium and tritium read as hydrogen.
+    pub fn from_symbol(symbol: &str) -> Option<&'static ElementData> {
+        match symbol {
+            "D" | "T" => Some(&PERIODIC_TABLE[0]),
+            _ => PERIODIC_TABLE.iter().find(|data| data.symbol == symbol),
+        }
+    }
+
+    pub fn from_atomic_number(atomic_number: usize) -> Option<&'static ElementData> {
+        PERIODIC_TABLE.get(atomic_number.checked_sub(1)?)
+    }
+
+    pub fn cpk_color(&self) -> Vec3 {
+        rgb(self.cpk)
+    }
+
+    pub fn jmol_color(&self) -> Vec3 {
+        rgb(self.jmol)
+    }
+}
+
+/// Converts `0xRRGGBB` to a color with components from 0 to 1.
+pub fn rgb(hex: u32) -> Vec3 {
+    Vec3::new(
+        ((hex >> 16) & 0xFF) as f32 / 255.0,
+        ((hex >> 8) & 0xFF) as f32 / 255.0,
+        (hex & 0xFF) as f32 / 255.0,
+    )
+}
+
+pub static PERIODIC_TABLE: [ElementData; 118] = [
+    ElementData::new(1, "H", 1.008, 0.31, 1.20, 0xFFFFFF, 0xFFFFFF),
+    ElementData::new(2, "He", 4.0026, 0.28, 1.40, 0x00FFFF, 0xD9FFFF),
+    ElementData::new(3, "Li", 6.94, 1.28, 1.82, 0x7700FF, 0xCC80FF),
+    ElementData::new(4, "Be", 9.0122, 0.96, 1.53, 0x007700, 0xC2FF00),
+    ElementData::new(5, "B", 10.81, 0.84, 1.92, 0xFFAA77, 0xFFB5B5),
+    ElementData::new(6, "C", 12.011, 0.76, 1.70, 0x222222, 0x909090),
+    ElementData::new(7, "N", 14.007, 0.71, 1.55, 0x2233FF, 0x3050F8),
+    ElementData::new(8, "O", 15.999, 0.66, 1.52, 0xFF2200, 0xFF0D0D),
+    ElementData::new(9, "F", 18.998, 0.57, 1.47, 0x1FF01F, 0x90E050),
+    ElementData::new(10, "Ne", 20.180, 0.58, 1.54, 0x00FFFF, 0xB3E3F5),
+    ElementData::new(11, "Na", 22.990, 1.66, 2.27, 0x7700FF, 0xAB5CF2),
+    ElementData::new(12, "Mg", 24.305, 1.41, 1.73, 0x007700, 0x8AFF00),
+    ElementData::new(13, "Al", 26.982, 1.21, 1.84, 0xDD77FF, 0xBFA6A6),
+    ElementData::new(14, "Si", 28.085, 1.11, 2.10, 0xDD77FF, 0xF0C8A0),
+    ElementData::new(15, "P", 30.974, 1.07, 1.80, 0xFF9900, 0xFF8000),
+    ElementData::new(16, "S", 32.06, 1.05, 1.80, 0xDDDD00, 0xFFFF30),
+    ElementData::new(17, "Cl", 35.45, 1.02, 1.75, 0x1FF01F, 0x1FF01F),
+    ElementData::new(18, "Ar", 39.948, 1.06, 1.88, 0x00FFFF, 0x80D1E3),
+    ElementData::new(19, "K", 39.098, 2.03, 2.75, 0x7700FF, 0x8F40D4),
+    ElementData::new(20, "Ca", 40.078, 1.76, 2.31, 0x007700, 0x3DFF00),
+    ElementData::new(21, "Sc", 44.956, 1.70, 2.30, 0xDD77FF, 0xE6E6E6),
+    ElementData::new(22, "Ti", 47.867, 1.60, 2.15, 0x999999, 0xBFC2C7),
+    ElementData::new(23, "V", 50.942, 1.53, 2.05, 0xDD77FF, 0xA6A6AB),
+    ElementData::new(24, "Cr", 51.996, 1.39, 2.05, 0xDD77FF, 0x8A99C7),
+    ElementData::new(25, "Mn", 54.938, 1.39, 2.05, 0xDD77FF, 0x9C7AC7),
+    ElementData::new(26, "Fe", 55.845, 1.32, 2.05, 0xDD7700, 0xE06633),
+    ElementData::new(27, "Co", 58.933, 1.26, 2.00, 0xDD77FF, 0xF090A0),
+    ElementData::new(28, "Ni", 58.693, 1.24, 2.00, 0xDD77FF, 0x50D050),
+    ElementData::new(29, "Cu", 63.546, 1.32, 2.00, 0xDD77FF, 0xC88033),
+    ElementData::new(30, "Zn", 65.38, 1.22, 2.10, 0xDD77FF, 0x7D80B0),
+    ElementData::new(31, "Ga", 69.723, 1.22, 1.87, 0xDD77FF, 0xC28F8F),
+    ElementData::new(32, "Ge", 72.630, 1.20, 2.11, 0xDD77FF, 0x668F8F),
+    ElementData::new(33, "As", 74.922, 1.19, 1.85, 0xDD77FF, 0xBD80E3),
+    ElementData::new(34, "Se", 78.971, 1.20, 1.90, 0xDD77FF, 0xFFA100),
+    ElementData::new(35, "Br", 79.904, 1.20, 1.83, 0x992200, 0xA62929),
+    ElementData::new(36, "Kr", 83.798, 1.16, 2.02, 0x00FFFF, 0x5CB8D1),
+    ElementData::new(37, "Rb", 85.468, 2.20, 3.03, 0x7700FF, 0x702EB0),
+    ElementData::new(38, "Sr", 87.62, 1.95, 2.49, 0x007700, 0x00FF00),
+    ElementData::new(39, "Y", 88.906, 1.90, 2.40, 0xDD77FF, 0x94FFFF),
+    ElementData::new(40, "Zr", 91.224, 1.75, 2.30, 0xDD77FF, 0x94E0E0),
+    ElementData::new(41, "Nb", 92.906, 1.64, 2.15, 0xDD77FF, 0x73C2C9),
+    ElementData::new(42, "Mo", 95.95, 1.54, 2.10, 0xDD77FF, 0x54B5B5),
+    ElementData::new(43, "Tc", 98.0, 1.47, 2.05, 0xDD77FF, 0x3B9E9E),
+    ElementData::new(44, "Ru", 101.07, 1.46, 2.05, 0xDD77FF, 0x248F8F),
+    ElementData::new(45, "Rh", 102.91, 1.42, 2.00, 0xDD77FF, 0x0A7D8C),
+    ElementData::new(46, "Pd", 106.42, 1.39, 2.05, 0xDD77FF, 0x006985),
+    ElementData::new(47, "Ag", 107.87, 1.45, 2.10, 0xDD77FF, 0xC0C0C0),
+    ElementData::new(48, "Cd", 112.41, 1.44, 2.20, 0xDD77FF, 0xFFD98F),
+    ElementData::new(49, "In", 114.82, 1.42, 2.20, 0xDD77FF, 0xA67573),
+    ElementData::new(50, "Sn", 118.71, 1.39, 1.93, 0xDD77FF, 0x668080),
+    ElementData::new(51, "Sb", 121.76, 1.39, 2.17, 0xDD77FF, 0x9E63B5),
+    ElementData::new(52, "Te", 127.60, 1.38, 2.06, 0xDD77FF, 0xD47A00),
+    ElementData::new(53, "I", 126.90, 1.39, 1.98, 0x6600BB, 0x940094),
+    ElementData::new(54, "Xe", 131.29, 1.40, 2.16, 0x00FFFF, 0x429EB0),
+    ElementData::new(55, "Cs", 132.91, 2.44, 3.43, 0x7700FF, 0x57178F),
+    ElementData::new(56, "Ba", 137.33, 2.15, 2.68, 0x007700, 0x00C900),
+    ElementData::new(57, "La", 138.91, 2.07, 2.50, 0xDD77FF, 0x70D4FF),
+    ElementData::new(58, "Ce", 140.12, 2.04, 2.48, 0xDD77FF, 0xFFFFC7),
+    ElementData::new(59, "Pr", 140.91, 2.03, 2.47, 0xDD77FF, 0xD9FFC7),
+    ElementData::new(60, "Nd", 144.24, 2.01, 2.45, 0xDD77FF, 0xC7FFC7),
+    ElementData::new(61, "Pm", 145.0, 1.99, 2.43, 0xDD77FF, 0xA3FFC7),
+    ElementData::new(62, "Sm", 150.36, 1.98, 2.42, 0xDD77FF, 0x8FFFC7),
+    ElementData::new(63, "Eu", 151.96, 1.98, 2.40, 0xDD77FF, 0x61FFC7),
+    ElementData::new(64, "Gd", 157.25, 1.96, 2.38, 0xDD77FF, 0x45FFC7),
+    ElementData::new(65, "Tb", 158.93, 1.94, 2.37, 0xDD77FF, 0x30FFC7),
+    ElementData::new(66, "Dy", 162.50, 1.92, 2.35, 0xDD77FF, 0x1FFFC7),
+    ElementData::new(67, "Ho", 164.93, 1.92, 2.33, 0xDD77FF, 0x00FF9C),
+    ElementData::new(68, "Er", 167.26, 1.89, 2.32, 0xDD77FF, 0x00E675),
+    ElementData::new(69, "Tm", 168.93, 1.90, 2.30, 0xDD77FF, 0x00D452),
+    ElementData::new(70, "Yb", 173.05, 1.87, 2.28, 0xDD77FF, 0x00BF38),
+    ElementData::new(71, "Lu", 174.97, 1.87, 2.27, 0xDD77FF, 0x00AB24),
+    ElementData::new(72, "Hf", 178.49, 1.75, 2.25, 0xDD77FF, 0x4DC2FF),
+    ElementData::new(73, "Ta", 180.95, 1.70, 2.20, 0xDD77FF, 0x4DA6FF),
+    ElementData::new(74, "W", 183.84, 1.62, 2.10, 0xDD77FF, 0x2194D6),
+    ElementData::new(75, "Re", 186.21, 1.51, 2.05, 0xDD77FF, 0x267DAB),
+    ElementData::new(76, "Os", 190.23, 1.44, 2.00, 0xDD77FF, 0x266696),
+    ElementData::new(77, "Ir", 192.22, 1.41, 2.00, 0xDD77FF, 0x175487),
+    ElementData::new(78, "Pt", 195.08, 1.36, 2.05, 0xDD77FF, 0xD0D0E0),
+    ElementData::new(79, "Au", 196.97, 1.36, 2.10, 0xDD77FF, 0xFFD123),
+    ElementData::new(80, "Hg", 200.59, 1.32, 2.05, 0xDD77FF, 0xB8B8D0),
+    ElementData::new(81, "Tl", 204.38, 1.45, 1.96, 0xDD77FF, 0xA6544D),
+    ElementData::new(82, "Pb", 207.2, 1.46, 2.02, 0xDD77FF, 0x575961),
+    ElementData::new(83, "Bi", 208.98, 1.48, 2.07, 0xDD77FF, 0x9E4FB5),
+    ElementData::new(84, "Po", 209.0, 1.40, 1.97, 0xDD77FF, 0xAB5C00),
+    ElementData::new(85, "At", 210.0, 1.50, 2.02, 0xDD77FF, 0x754F45),
+    ElementData::new(86, "Rn", 222.0, 1.50, 2.20, 0x00FFFF, 0x428296),
+    ElementData::new(87, "Fr", 223.0, 2.60, 3.48, 0x7700FF, 0x420066),
+    ElementData::new(88, "Ra", 226.0, 2.21, 2.83, 0x007700, 0x007D00),
+    ElementData::new(89, "Ac", 227.0, 2.15, 2.00, 0xDD77FF, 0x70ABFA),
+    ElementData::new(90, "Th", 232.04, 2.06, 2.40, 0xDD77FF, 0x00BAFF),
+    ElementData::new(91, "Pa", 231.04, 2.00, 2.00, 0xDD77FF, 0x00A1FF),
+    ElementData::new(92, "U", 238.03, 1.96, 2.30, 0xDD77FF, 0x008FFF),
+    ElementData::new(93, "Np", 237.0, 1.90, 2.00, 0xDD77FF, 0x0080FF),
+    ElementData::new(94, "Pu", 244.0, 1.87, 2.00, 0xDD77FF, 0x006BFF),
+    ElementData::new(95, "Am", 243.0, 1.80, 2.00, 0xDD77FF, 0x545CF2),
+    ElementData::new(96, "Cm", 247.0, 1.69, 2.00, 0xDD77FF, 0x785CE3),
+    ElementData::new(97, "Bk", 247.0, 1.68, 2.00, 0xDD77FF, 0x8A4FE3),
+    ElementData::new(98, "Cf", 251.0, 1.68, 2.00, 0xDD77FF, 0xA136D4),
+    ElementData::new(99, "Es", 252.0, 1.65, 2.00, 0xDD77FF, 0xB31FD4),
+    ElementData::new(100, "Fm", 257.0, 1.67, 2.00, 0xDD77FF, 0xB31FBA),
+    ElementData::new(101, "Md", 258.0, 1.73, 2.00, 0xDD77FF, 0xB30DA6),
+    ElementData::new(102, "No", 259.0, 1.76, 2.00, 0xDD77FF, 0xBD0D87),
+    ElementData::new(103, "Lr", 266.0, 1.61, 2.00, 0xDD77FF, 0xC70066),
+    ElementData::new(104, "Rf", 267.0, 1.57, 2.00, 0xDD77FF, 0xCC0059),
+    ElementData::new(105, "Db", 268.0, 1.49, 2.00, 0xDD77FF, 0xD1004F),
+    ElementData::new(106, "Sg", 269.0, 1.43, 2.00, 0xDD77FF, 0xD90045),
+    ElementData::new(107, "Bh", 270.0, 1.41, 2.00, 0xDD77FF, 0xE00038),
+    ElementData::new(108, "Hs", 277.0, 1.34, 2.00, 0xDD77FF, 0xE6002E),
+    ElementData::new(109, "Mt", 278.0, 1.29, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(110, "Ds", 281.0, 1.28, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(111, "Rg", 282.0, 1.21, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(112, "Cn", 285.0, 1.22, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(113, "Nh", 286.0, 1.36, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(114, "Fl", 289.0, 1.43, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(115, "Mc", 290.0, 1.62, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(116, "Lv", 293.0, 1.75, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(117, "Ts", 294.0, 1.65, 2.00, 0xDD77FF, 0xEB0026),
+    ElementData::new(118, "Og", 294.0, 1.57, 2.00, 0x00FFFF, 0xEB0026),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mol::Element;
+    use std::collections::HashSet;
+
+    #[test]
+    fn numbers_every_element_in_order() {
+        for (index, data) in PERIODIC_TABLE.iter().enumerate() {
+            assert_eq!(data.atomic_number as usize, index + 1, "{}", data.symbol);
+        }
+    }
+
+    #[test]
+    fn gives_each_element_its_own_symbol() {
+        let symbols = PERIODIC_TABLE.iter().map(|data| data.symbol);
+        assert_eq!(symbols.collect::<HashSet<_>>().len(), PERIODIC_TABLE.len());
+    }
+
+    #[test]
+    fn has_positive_radii() {
+        for data in PERIODIC_TABLE.iter() {
+            assert!(data.covalent_radius > 0.0, "{}", data.symbol);
+            assert!(data.vdw_radius > 0.0, "{}", data.symbol);
+        }
+    }
+
+    #[test]
+    fn sizes_elements_relative_to_carbon() {
+        let carbon = Element::from_symbol("C");
+        assert_eq!(carbon.atomic_number, 6);
+        assert_eq!(carbon.scale, 1.0);
+        assert!(Element::from_symbol("H").scale < 1.0);
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+
+/// Why a molecule file couldn't be read. Every variant carries the 1-based
+/// line the problem was found on, and `column` (also 1-based) where the
+/// offending field starts when it is known.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The counts line is missing its atom or bond count
+    BadCountsLine {
+        line: usize,
+        column: usize,
+        found: String,
+    },
+    /// The counts line names a format version this reader doesn't handle
+    UnsupportedVersion { line: usize, version: String },
+    /// A field doesn't hold the kind of value its position calls for
+    InvalidField {
+        line: usize,
+        column: usize,
+        expected: &'static str,
+        found: String,
+    },
+    /// A bond or property points at an atom number that doesn't exist
+    AtomIndexOutOfRange {
+        line: usize,
+        column: usize,
+        index: i64,
+        atom_count: usize,
+    },
+    /// An atom symbol that isn't an element or a molfile query atom
+    UnknownElement {
+        line: usize,
+        column: usize,
+        symbol: String,
+    },
+    /// The file ended before a block was complete
+    TruncatedBlock { line: usize, block: &'static str },
+    /// A data category the format requires, such as mmCIF's `_atom_site`,
+    /// isn't in the file
+    MissingCategory { line: usize, category: &'static str },
+}
+
+impl ParseError {
+    pub fn line(&self) -> usize {
+        match *self {
+            ParseError::BadCountsLine { line, .. }
+            | ParseError::UnsupportedVersion { line, .. }
+            | ParseError::InvalidField { line, .. }
+            | ParseError::AtomIndexOutOfRange { line, .. }
+            | ParseError::UnknownElement { line, .. }
+            | ParseError::TruncatedBlock { line, .. }
+            | ParseError::MissingCategory { line, .. } => line,
+        }
+    }
+
+    /// Moves the error `lines` lines further down, for errors found in a
+    /// record that was cut out of a larger file.
+    pub fn offset(mut self, lines: usize) -> Self {
+        match self {
+            ParseError::BadCountsLine { ref mut line, .. }
+            | ParseError::UnsupportedVersion { ref mut line, .. }
+            | ParseError::InvalidField { ref mut line, .. }
+            | ParseError::AtomIndexOutOfRange { ref mut line, .. }
+            | ParseError::UnknownElement { ref mut line, .. }
+            | ParseError::TruncatedBlock { ref mut line, .. }
+            | ParseError::MissingCategory { ref mut line, .. } => *line += lines,
+        }
+        self
+    }
+
+    pub fn column(&self) -> Option<usize> {
+        match *self {
+            ParseError::BadCountsLine { column, .. }
+            | ParseError::InvalidField { column, .. }
+            | ParseError::AtomIndexOutOfRange { column, .. }
+            | ParseError::UnknownElement { column, .. } => Some(column),
+            ParseError::UnsupportedVersion { .. }
+            | ParseError::TruncatedBlock { .. }
+            | ParseError::MissingCategory { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.column() {
+            Some(column) => write!(f, "line {}, column {}: ", self.line(), column)?,
+            None => write!(f, "line {}: ", self.line())?,
+        }
+        match self {
+            ParseError::BadCountsLine { found, .. } => {
+                write!(f, "bad counts line, expected a count but found {:?}", found)
+            }
+            ParseError::UnsupportedVersion { version, .. } => {
+                write!(f, "unsupported format version {:?}", version)
+            }
+            ParseError::InvalidField {
+                expected, found, ..
+            } => write!(f, "expected {}, found {:?}", expected, found),
+            ParseError::AtomIndexOutOfRange {
+                index, atom_count, ..
+            } => write!(
+                f,
+                "atom number {} is out of range, the molecule has {} atoms",
+                index, atom_count
+            ),
+            ParseError::UnknownElement { symbol, .. } => {
+                write!(f, "unknown element {:?}", symbol)
+            }
+            ParseError::TruncatedBlock { block, .. } => {
+                write!(f, "file ended inside the {}", block)
+            }
+            ParseError::MissingCategory { category, .. } => {
+                write!(f, "the file has no {} category", category)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_moves_only_the_line() {
+        let error = ParseError::InvalidField {
+            line: 3,
+            column: 11,
+            expected: "a y coordinate",
+            found: "x".to_string(),
+        };
+        let error = error.offset(40);
+        assert_eq!((error.line(), error.column()), (43, Some(11)));
+        assert_eq!(
+            error.to_string(),
+            "line 43, column 11: expected a y coordinate, found \"x\""
+        );
+    }
+
+    #[test]
+    fn displays_without_a_column() {
+        let error = ParseError::TruncatedBlock {
+            line: 7,
+            block: "atom block",
+        };
+        assert_eq!(error.column(), None);
+        assert_eq!(
+            error.to_string(),
+            "line 7: file ended inside the atom block"
+        );
+    }
+}
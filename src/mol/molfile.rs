@@ -0,0 +1,430 @@
+//! Reader for MDL molfiles, as written by RDKit, Open Babel, ChemDraw and
+//! PubChem.
+//!
+//! A molfile is a three line header, a counts line, then a connection table
+//! followed by an `M  ` properties block ending in `M  END`. The version on
+//! the counts line picks between V2000's fixed-column atom and bond blocks,
+//! read here, and V3000's `M  V30` records, read in `v3000`.
+use super::{v3000, Atom, Bond, Element, Mol, ParseError};
+use glam::Vec3;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Symbols a molfile may use in place of an element: hydrogen isotopes,
+/// query atoms, lone pairs and R-groups.
+pub(super) const SPECIAL_SYMBOLS: [&str; 8] = ["D", "T", "A", "Q", "*", "L", "LP", "R#"];
+
+/// Line iterator that remembers the 1-based number of the last line read.
+pub(super) struct Lines<'a> {
+    inner: std::str::Lines<'a>,
+    pub(super) number: usize,
+}
+
+impl<'a> Lines<'a> {
+    pub(super) fn new(text: &'a str) -> Self {
+        Lines {
+            inner: text.lines(),
+            number: 0,
+        }
+    }
+
+    pub(super) fn next(&mut self) -> Option<&'a str> {
+        let line = self.inner.next()?;
+        self.number += 1;
+        Some(line)
+    }
+
+    /// Reads the next line of `block`, failing if the file ends first.
+    pub(super) fn expect(&mut self, block: &'static str) -> Result<&'a str, ParseError> {
+        match self.next() {
+            Some(line) => Ok(line),
+            None => Err(ParseError::TruncatedBlock {
+                line: self.number + 1,
+                block,
+            }),
+        }
+    }
+}
+
+/// Returns the trimmed fixed-width field at `start..end`, clipped to the line.
+pub(super) fn column(line: &str, start: usize, end: usize) -> &str {
+    line.get(start..end.min(line.len())).unwrap_or("").trim()
+}
+
+pub(super) fn parse_column<T: FromStr>(
+    line: &str,
+    number: usize,
+    start: usize,
+    end: usize,
+    expected: &'static str,
+) -> Result<T, ParseError> {
+    let text = column(line, start, end);
+    text.parse().map_err(|_| ParseError::InvalidField {
+        line: number,
+        column: start + 1,
+        expected,
+        found: text.to_string(),
+    })
+}
+
+/// Like `parse_column`, but a blank field reads as zero.
+fn parse_code(
+    line: &str,
+    number: usize,
+    start: usize,
+    end: usize,
+    expected: &'static str,
+) -> Result<i8, ParseError> {
+    if column(line, start, end).is_empty() {
+        Ok(0)
+    } else {
+        parse_column(line, number, start, end, expected)
+    }
+}
+
+fn parse_count(line: &str, number: usize, start: usize, end: usize) -> Result<usize, ParseError> {
+    let text = column(line, start, end);
+    text.parse().map_err(|_| ParseError::BadCountsLine {
+        line: number,
+        column: start + 1,
+        found: text.to_string(),
+    })
+}
+
+/// Maps the atom block charge code onto a formal charge. Code 4 is a doublet
+/// radical, which carries no charge.
+fn charge_from_code(code: i8) -> i8 {
+    match code {
+        1..=3 | 5..=7 => 4 - code,
+        _ => 0,
+    }
+}
+
+fn parse_atom(line: &str, number: usize) -> Result<Atom, ParseError> {
+    let position = Vec3::new(
+        parse_column(line, number, 0, 10, "an x coordinate")?,
+        parse_column(line, number, 10, 20, "a y coordinate")?,
+        parse_column(line, number, 20, 30, "a z coordinate")?,
+    );
+    let symbol = column(line, 31, 34);
+    check_symbol(symbol, number, 32)?;
+    let mut atom = Atom::new(symbol, position);
+    atom.mass_difference = parse_code(line, number, 34, 36, "a mass difference")?;
+    atom.charge = charge_from_code(parse_code(line, number, 36, 39, "a charge code")?);
+    atom.stereo_parity = parse_code(line, number, 39, 42, "a stereo parity")? as u8;
+    Ok(atom)
+}
+
+pub(super) fn check_symbol(symbol: &str, number: usize, column: usize) -> Result<(), ParseError> {
+    if Element::is_known(symbol) || SPECIAL_SYMBOLS.contains(&symbol) {
+        Ok(())
+    } else {
+        Err(ParseError::UnknownElement {
+            line: number,
+            column,
+            symbol: symbol.to_string(),
+        })
+    }
+}
+
+/// Checks a 1-based atom number against the atom block and makes it 0-based.
+pub(super) fn atom_index(
+    index: i64,
+    number: usize,
+    column: usize,
+    atom_count: usize,
+) -> Result<usize, ParseError> {
+    if index < 1 || index as usize > atom_count {
+        return Err(ParseError::AtomIndexOutOfRange {
+            line: number,
+            column,
+            index,
+            atom_count,
+        });
+    }
+    Ok(index as usize - 1)
+}
+
+fn parse_bond(line: &str, number: usize, atoms: &[Atom]) -> Result<Bond, ParseError> {
+    let first_atom = atom_index(
+        parse_column(line, number, 0, 3, "an atom number")?,
+        number,
+        1,
+        atoms.len(),
+    )?;
+    let second_atom = atom_index(
+        parse_column(line, number, 3, 6, "an atom number")?,
+        number,
+        4,
+        atoms.len(),
+    )?;
+    let bond_type = parse_column(line, number, 6, 9, "a bond type")?;
+    let mut bond = Bond::new(first_atom, second_atom, bond_type, atoms);
+    bond.stereo = parse_code(line, number, 9, 12, "a bond stereo code")? as u8;
+    Ok(bond)
+}
+
+/// Reads the `(atom, value)` pairs of an `M  CHG` or `M  ISO` property line,
+/// failing on values that don't fit in `T`.
+fn parse_property_pairs<T: TryFrom<i32>>(
+    line: &str,
+    number: usize,
+    atom_count: usize,
+    expected: &'static str,
+) -> Result<Vec<(usize, T)>, ParseError> {
+    // Fields are "M  XXXnn8" followed by nn pairs of " aaa vvv"
+    let count: usize = parse_column(line, number, 6, 9, "an entry count")?;
+    (0..count)
+        .map(|entry| {
+            let start = 9 + entry * 8;
+            let index = atom_index(
+                parse_column(line, number, start, start + 4, "an atom number")?,
+                number,
+                start + 1,
+                atom_count,
+            )?;
+            let value: i32 = parse_column(line, number, start + 4, start + 8, expected)?;
+            let value = T::try_from(value).map_err(|_| ParseError::InvalidField {
+                line: number,
+                column: start + 5,
+                expected,
+                found: column(line, start + 4, start + 8).to_string(),
+            })?;
+            Ok((index, value))
+        })
+        .collect()
+}
+
+fn parse_properties(lines: &mut Lines, atoms: &mut [Atom]) -> Result<(), ParseError> {
+    let mut charges_reset = false;
+    while let Some(line) = lines.next() {
+        let number = lines.number;
+        if line.starts_with("M  END") {
+            break;
+        } else if line.starts_with("M  CHG") {
+            // The first CHG line supersedes every charge in the atom block
+            if !charges_reset {
+                atoms.iter_mut().for_each(|atom| atom.charge = 0);
+                charges_reset = true;
+            }
+            for (index, charge) in parse_property_pairs(line, number, atoms.len(), "a charge")? {
+                atoms[index].charge = charge;
+            }
+        } else if line.starts_with("M  ISO") {
+            for (index, mass) in parse_property_pairs(line, number, atoms.len(), "a mass number")? {
+                atoms[index].isotope = Some(mass);
+            }
+        } else if line.starts_with("A  ") || line.starts_with("G  ") {
+            // Atom aliases and group abbreviations carry their text on the next line
+            lines.expect("properties block")?;
+        } else if line.starts_with("S  SKP") {
+            let skip: usize = parse_column(line, number, 6, 9, "a skip count")?;
+            for _ in 0..skip {
+                lines.expect("properties block")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_v2000_ctab(
+    lines: &mut Lines,
+    atom_count: usize,
+    bond_count: usize,
+) -> Result<(Vec<Atom>, Vec<Bond>), ParseError> {
+    let mut atoms = Vec::with_capacity(atom_count);
+    for _ in 0..atom_count {
+        let line = lines.expect("atom block")?;
+        atoms.push(parse_atom(line, lines.number)?);
+    }
+
+    let mut bonds = Vec::with_capacity(bond_count);
+    for _ in 0..bond_count {
+        let line = lines.expect("bond block")?;
+        bonds.push(parse_bond(line, lines.number, &atoms)?);
+    }
+    Ok((atoms, bonds))
+}
+
+pub fn parse(text: &str) -> Result<Mol, ParseError> {
+    let mut lines = Lines::new(text);
+    let name = lines.expect("header block")?.trim().to_string();
+    lines.expect("header block")?;
+    let comment = lines.expect("header block")?.trim().to_string();
+
+    let counts = lines.expect("counts line")?;
+    let number = lines.number;
+    let atom_count = parse_count(counts, number, 0, 3)?;
+    let bond_count = parse_count(counts, number, 3, 6)?;
+    let (mut atoms, bonds) = match column(counts, 33, 39) {
+        "" | "V2000" => parse_v2000_ctab(&mut lines, atom_count, bond_count)?,
+        "V3000" => v3000::parse_ctab(&mut lines)?,
+        version => {
+            return Err(ParseError::UnsupportedVersion {
+                line: number,
+                version: version.to_string(),
+            })
+        }
+    };
+
+    parse_properties(&mut lines, &mut atoms)?;
+
+    Ok(Mol {
+        name,
+        comment,
+        ..Mol::new(atoms, bonds)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chloroacetate with a charged oxygen, a 13C label, a wedge and an x and
+    /// y that fill their columns
+    const CHLOROACETATE: &str = "\
+Chloroacetate
+     RDKit          2D
+Hand written
+  5  4  0  0  0  0  0  0  0  0999 V2000
+-1000.0000-1000.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    1.5000    0.0000    0.0000 C   1  0  0  0  0  0  0  0  0  0  0  0
+    2.2500    1.2990    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+    2.2500   -1.2990    0.0000 O   0  5  0  0  0  0  0  0  0  0  0  0
+   -0.7500    1.2990    0.0000 Cl  0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  2  3  2  0
+  2  4  1  0
+  1  5  1  1
+M  ISO  1   2  13
+M  END
+";
+
+    #[test]
+    fn reads_fixed_columns() {
+        let mol = parse(CHLOROACETATE).unwrap();
+        assert_eq!(mol.name, "Chloroacetate");
+        assert_eq!(mol.comment, "Hand written");
+        assert_eq!(mol.atoms.len(), 5);
+        assert_eq!(mol.atoms[0].position, Vec3::new(-1000.0, -1000.0, 0.0));
+        assert_eq!(mol.atoms[4].symbol, "Cl");
+        assert_eq!(mol.atoms[1].mass_difference, 1);
+        assert_eq!(mol.bonds.len(), 4);
+        assert_eq!(mol.bonds[1].bond_type, 2);
+        assert_eq!((mol.bonds[3].first_atom, mol.bonds[3].second_atom), (0, 4));
+        assert_eq!(mol.bonds[3].stereo, 1);
+    }
+
+    #[test]
+    fn reads_atom_block_charges() {
+        let mol = parse(CHLOROACETATE).unwrap();
+        assert_eq!(mol.atoms[3].charge, -1);
+        assert_eq!(mol.atoms[2].charge, 0);
+    }
+
+    #[test]
+    fn charge_property_supersedes_atom_block() {
+        let text = CHLOROACETATE.replace("M  END", "M  CHG  1   3  -1\nM  END");
+        let mol = parse(&text).unwrap();
+        assert_eq!(mol.atoms[2].charge, -1);
+        assert_eq!(mol.atoms[3].charge, 0);
+    }
+
+    #[test]
+    fn reads_isotopes() {
+        let mol = parse(CHLOROACETATE).unwrap();
+        assert_eq!(mol.atoms[1].isotope, Some(13));
+        assert_eq!(mol.atoms[0].isotope, None);
+    }
+
+    #[test]
+    fn skips_alias_text() {
+        // Read as a property, the alias text would charge the first atom
+        let text = CHLOROACETATE.replace("M  END", "A    5\nM  CHG  1   1   1\nM  END");
+        let mol = parse(&text).unwrap();
+        assert_eq!(mol.atoms[0].charge, 0);
+        assert_eq!(mol.atoms[3].charge, -1);
+    }
+
+    fn parse_error(text: &str) -> ParseError {
+        parse(text).err().expect("an error")
+    }
+
+    #[test]
+    fn locates_bad_fields() {
+        let text = CHLOROACETATE.replacen("    1.5000", "    1.5x00", 1);
+        let error = parse_error(&text);
+        assert_eq!((error.line(), error.column()), (6, Some(1)));
+        assert!(matches!(error, ParseError::InvalidField { .. }));
+
+        let text = CHLOROACETATE.replace("  5  4  0", "  x  4  0");
+        let error = parse_error(&text);
+        assert_eq!((error.line(), error.column()), (4, Some(1)));
+        assert!(matches!(error, ParseError::BadCountsLine { .. }));
+    }
+
+    #[test]
+    fn rejects_properties_out_of_range() {
+        let text = CHLOROACETATE.replace("M  END", "M  CHG  1   1 200\nM  END");
+        assert_eq!(
+            parse_error(&text),
+            ParseError::InvalidField {
+                line: 15,
+                column: 14,
+                expected: "a charge",
+                found: "200".to_string(),
+            }
+        );
+
+        let text = CHLOROACETATE.replace("M  ISO  1   2  13", "M  ISO  1   2  -1");
+        let error = parse_error(&text);
+        assert_eq!((error.line(), error.column()), (14, Some(14)));
+        assert!(matches!(error, ParseError::InvalidField { .. }));
+    }
+
+    #[test]
+    fn locates_unknown_elements() {
+        let error = parse_error(&CHLOROACETATE.replace("Cl  0", "Xx  0"));
+        assert_eq!(
+            error.to_string(),
+            "line 9, column 32: unknown element \"Xx\""
+        );
+    }
+
+    #[test]
+    fn locates_atom_numbers_out_of_range() {
+        let error = parse_error(&CHLOROACETATE.replace("  1  5  1  1", "  1  9  1  1"));
+        assert_eq!(
+            error,
+            ParseError::AtomIndexOutOfRange {
+                line: 13,
+                column: 4,
+                index: 9,
+                atom_count: 5,
+            }
+        );
+
+        let error = parse_error(&CHLOROACETATE.replace("M  ISO  1   2", "M  ISO  1   7"));
+        assert_eq!((error.line(), error.column()), (14, Some(10)));
+    }
+
+    #[test]
+    fn reports_truncation_and_versions() {
+        let text = CHLOROACETATE
+            .lines()
+            .take(11)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            parse_error(&text),
+            ParseError::TruncatedBlock {
+                line: 12,
+                block: "bond block",
+            }
+        );
+
+        let error = parse_error(&CHLOROACETATE.replace("V2000", "V4000"));
+        assert_eq!((error.line(), error.column()), (4, None));
+        assert!(parse("").is_err());
+    }
+}
@@ -0,0 +1,376 @@
+//! Reader for Protein Data Bank files.
+//!
+//! Atoms come from fixed-column `ATOM` and `HETATM` records. Only the first
+//! model and the first alternate location of each atom are kept. Bonds inside
+//! standard residues are rebuilt from templates, and `CONECT` records add the
+//! rest, usually ligands and disulfides. Residues with neither a template nor
+//! `CONECT` records fall back on bond perception.
+use super::molfile::{column, parse_column};
+use super::{perception, residues, Atom, Element, Mol, ParseError};
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
+
+/// Makes `FE` or `fe` into `Fe`.
+pub(super) fn capitalize_symbol(symbol: &str) -> String {
+    let mut characters = symbol.chars();
+    match characters.next() {
+        Some(first) => {
+            first.to_ascii_uppercase().to_string() + &characters.as_str().to_ascii_lowercase()
+        }
+        None => String::new(),
+    }
+}
+
+/// Reads the element from columns 77-78, falling back to the atom name for
+/// older files that leave them blank. Returns the symbol and the 1-based
+/// column it was read from.
+fn element_symbol(line: &str, hetero: bool) -> (String, usize) {
+    let explicit = column(line, 76, 78);
+    if !explicit.is_empty() {
+        return (capitalize_symbol(explicit), 77);
+    }
+    // Element symbols are right-justified in columns 13-14 of the atom name,
+    // so a letter in column 13 means a two letter element such as `FE`
+    let name = line.get(12..16.min(line.len())).unwrap_or("");
+    if hetero {
+        if let Some(two) = name.get(0..2) {
+            let two = capitalize_symbol(two);
+            if two.chars().all(char::is_alphabetic) && Element::is_known(&two) {
+                return (two, 13);
+            }
+        }
+    }
+    let symbol = name
+        .chars()
+        .find(|character| character.is_ascii_alphabetic())
+        .map(|character| character.to_ascii_uppercase().to_string())
+        .unwrap_or_default();
+    (symbol, 13)
+}
+
+/// Reads a charge such as `2+` or `1-` from columns 79-80.
+fn parse_charge(line: &str, number: usize) -> Result<i8, ParseError> {
+    let text = column(line, 78, 80);
+    let invalid = || ParseError::InvalidField {
+        line: number,
+        column: 79,
+        expected: "a charge such as 1+ or 2-",
+        found: text.to_string(),
+    };
+    match text.as_bytes() {
+        [] => Ok(0),
+        [digit @ b'0'..=b'9', sign] => {
+            let magnitude = (digit - b'0') as i8;
+            match sign {
+                b'+' => Ok(magnitude),
+                b'-' => Ok(-magnitude),
+                _ => Err(invalid()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Like `parse_column`, but a blank field reads as `None`.
+fn parse_optional(
+    line: &str,
+    number: usize,
+    start: usize,
+    end: usize,
+    expected: &'static str,
+) -> Result<Option<f32>, ParseError> {
+    if column(line, start, end).is_empty() {
+        Ok(None)
+    } else {
+        parse_column(line, number, start, end, expected).map(Some)
+    }
+}
+
+fn parse_atom(line: &str, number: usize, hetero: bool) -> Result<Atom, ParseError> {
+    let position = Vec3::new(
+        parse_column(line, number, 30, 38, "an x coordinate")?,
+        parse_column(line, number, 38, 46, "a y coordinate")?,
+        parse_column(line, number, 46, 54, "a z coordinate")?,
+    );
+    let (symbol, symbol_column) = element_symbol(line, hetero);
+    if !Element::is_known(&symbol) && symbol != "D" {
+        return Err(ParseError::UnknownElement {
+            line: number,
+            column: symbol_column,
+            symbol,
+        });
+    }
+    let mut atom = Atom::new(&symbol, position);
+    atom.name = Some(column(line, 12, 16).to_string());
+    atom.serial = column(line, 6, 11).parse().ok();
+    atom.residue_name = Some(column(line, 17, 20).to_string());
+    atom.residue_number = Some(parse_column(line, number, 22, 26, "a residue number")?);
+    atom.chain_id = Some(column(line, 21, 22).to_string()).filter(|chain| !chain.is_empty());
+    atom.occupancy = parse_optional(line, number, 54, 60, "an occupancy")?;
+    atom.b_factor = parse_optional(line, number, 60, 66, "a temperature factor")?;
+    atom.charge = parse_charge(line, number)?;
+    atom.hetero = hetero;
+    Ok(atom)
+}
+
+pub fn parse(text: &str) -> Result<Mol, ParseError> {
+    let mut atoms = Vec::new();
+    let mut serials = HashMap::new();
+    // Serials of alternate locations that were read past, which CONECT
+    // records may still mention
+    let mut skipped = HashSet::new();
+    let mut first_alternates = HashMap::new();
+    let mut conects = Vec::new();
+    let mut name = String::new();
+    let mut title = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        match line.get(0..6).unwrap_or(line).trim_end() {
+            "HEADER" => name = column(line, 62, 66).to_string(),
+            "TITLE" => title.push(column(line, 10, 80)),
+            record @ "ATOM" | record @ "HETATM" => {
+                let serial: Option<u32> = column(line, 6, 11).parse().ok();
+                // The first conformer of each atom, found by its name and
+                // residue, whatever it is labelled
+                let alternate = line.get(16..17).unwrap_or(" ");
+                let atom_key = (column(line, 12, 16), column(line, 21, 27));
+                if alternate != " "
+                    && *first_alternates.entry(atom_key).or_insert(alternate) != alternate
+                {
+                    skipped.extend(serial);
+                    continue;
+                }
+                if let Some(serial) = serial {
+                    serials.insert(serial, atoms.len());
+                }
+                atoms.push(parse_atom(line, number, record == "HETATM")?);
+            }
+            "CONECT" => conects.push((number, line)),
+            // Only the first model of an NMR ensemble or trajectory is shown
+            "ENDMDL" | "END" => break,
+            _ => {}
+        }
+    }
+
+    let mut bonds = residues::BondSet::new();
+    for (from, to, bond_type) in residues::infer_bonds(&atoms) {
+        bonds.insert(from, to, bond_type);
+    }
+
+    let mut conect_orders: HashMap<(usize, usize), u8> = HashMap::new();
+    for (number, line) in conects {
+        let resolve = |start: usize| -> Result<Option<usize>, ParseError> {
+            let text = column(line, start, start + 5);
+            if text.is_empty() {
+                return Ok(None);
+            }
+            let serial: u32 =
+                parse_column(line, number, start, start + 5, "an atom serial number")?;
+            match serials.get(&serial) {
+                Some(&index) => Ok(Some(index)),
+                None if skipped.contains(&serial) => Ok(None),
+                None => Err(ParseError::AtomIndexOutOfRange {
+                    line: number,
+                    column: start + 1,
+                    index: serial as i64,
+                    atom_count: atoms.len(),
+                }),
+            }
+        };
+        let from = match resolve(6)? {
+            Some(from) => from,
+            None => continue,
+        };
+        // Some writers repeat a partner to show a double or triple bond
+        let mut counts: HashMap<(usize, usize), u8> = HashMap::new();
+        for start in [11, 16, 21, 26].iter() {
+            if let Some(to) = resolve(*start)? {
+                if to != from {
+                    *counts.entry((from.min(to), from.max(to))).or_insert(0) += 1;
+                }
+            }
+        }
+        for (key, count) in counts {
+            let existing = conect_orders.entry(key).or_insert(0);
+            *existing = (*existing).max(count.min(3));
+        }
+    }
+    let mut conect_keys = conect_orders.keys().copied().collect::<Vec<_>>();
+    conect_keys.sort();
+    for (from, to) in conect_keys {
+        bonds.insert(from, to, conect_orders[&(from, to)]);
+    }
+
+    // Ligands and other non-standard residues that no CONECT record mentions
+    // would have no bonds at all, even when other residues' CONECT records,
+    // such as disulfides, are given
+    let mut covered = atoms.iter().map(residues::has_template).collect::<Vec<_>>();
+    let mut conected = vec![false; atoms.len()];
+    for &(from, to) in conect_orders.keys() {
+        conected[from] = true;
+        conected[to] = true;
+    }
+    for range in residues::residue_ranges(&atoms) {
+        if range.clone().any(|index| conected[index]) {
+            covered[range]
+                .iter_mut()
+                .for_each(|covered| *covered = true);
+        }
+    }
+    perception::fill_missing(&atoms, &mut bonds, |index| covered[index]);
+
+    let bonds = bonds.into_bonds(&atoms);
+    Ok(Mol {
+        name,
+        comment: title.join(" "),
+        ..Mol::new(atoms, bonds)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Alanine and glycine with an alternate location, a disulfide given by
+    /// `CONECT`, a charged iron, an oxygen with blank element columns and an
+    /// ethanol no `CONECT` record mentions
+    const PEPTIDE: &str = "\
+HEADER    TEST PROTEIN                            01-JAN-20   1ABC
+TITLE     A SMALL
+TITLE    2 TEST
+ATOM      1  N   ALA A   1      -0.677  -1.230  -0.491  1.00 20.00           N
+ATOM      2  CA  ALA A   1      -0.001   0.064  -0.491  1.00 20.00           C
+ATOM      3  C   ALA A   1       1.499  -0.110  -0.491  1.00 20.00           C
+ATOM      4  O   ALA A   1       2.030  -1.227  -0.502  1.00 20.00           O
+ATOM      5  CB  ALA A   1      -0.509   0.856   0.727  1.00 20.00           C
+ATOM      6  H   ALA A   1      -1.680  -1.230  -0.491  1.00 20.00           H
+ATOM      7  N   GLY A   2       2.250   0.987  -0.491  1.00 20.00           N
+ATOM      8  CA AGLY A   2       3.700   0.987  -0.491  0.60 20.00           C
+ATOM      9  CA BGLY A   2       3.650   1.100  -0.400  0.40 20.00           C
+ATOM     10  C   GLY A   2       4.200   2.400  -0.491  1.00 20.00           C
+ATOM     11  O   GLY A   2       3.500   3.400  -0.491  1.00 20.00           O
+ATOM     12  SG  CYS A   3      20.000   0.000   0.000  1.00 20.00           S
+ATOM     13  SG  CYS A   4      22.050   0.000   0.000  1.00 20.00           S
+HETATM   14 FE   HEM A 101      10.000  10.000  10.000  1.00 20.00          FE2+
+HETATM   15  O1  LIG A 102      12.000  10.000  10.000  1.00 20.00
+HETATM   16  C1  EOH A 103      30.000   0.000   0.000  1.00 20.00           C
+HETATM   17  C2  EOH A 103      31.500   0.000   0.000  1.00 20.00           C
+HETATM   18  O   EOH A 103      32.200   1.200   0.000  1.00 20.00           O
+CONECT   12   13
+CONECT   14   15   15
+CONECT    9   10
+END
+";
+
+    /// Bonds as `(first atom, second atom, bond type)`, lower index first.
+    fn bonds(mol: &Mol) -> Vec<(usize, usize, u8)> {
+        mol.bonds
+            .iter()
+            .map(|bond| {
+                let (first, second) = (bond.first_atom, bond.second_atom);
+                (first.min(second), first.max(second), bond.bond_type)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn perceives_residues_conect_records_miss() {
+        let bonds = bonds(&parse(PEPTIDE).unwrap());
+        // The ethanol's C1-C2 and C2-O, alongside the disulfide
+        assert!(bonds.contains(&(14, 15, 1)));
+        assert!(bonds.contains(&(15, 16, 1)));
+        assert!(bonds.contains(&(10, 11, 1)));
+    }
+
+    #[test]
+    fn reads_fixed_columns() {
+        let mol = parse(PEPTIDE).unwrap();
+        assert_eq!(mol.name, "1ABC");
+        assert_eq!(mol.comment, "A SMALL TEST");
+        let atom = &mol.atoms[1];
+        assert_eq!(atom.position, Vec3::new(-0.001, 0.064, -0.491));
+        assert_eq!(atom.name.as_deref(), Some("CA"));
+        assert_eq!(atom.serial, Some(2));
+        assert_eq!(atom.residue_name.as_deref(), Some("ALA"));
+        assert_eq!(atom.residue_number, Some(1));
+        assert_eq!(atom.chain_id.as_deref(), Some("A"));
+        assert_eq!(atom.b_factor, Some(20.0));
+        assert!(!atom.hetero && mol.atoms[12].hetero);
+    }
+
+    #[test]
+    fn reads_elements_and_charges() {
+        let mol = parse(PEPTIDE).unwrap();
+        assert_eq!(mol.atoms[12].symbol, "Fe");
+        assert_eq!(mol.atoms[12].charge, 2);
+        // Blank element columns fall back on the atom name
+        assert_eq!(mol.atoms[13].symbol, "O");
+
+        let text = PEPTIDE.replace("FE2+", "FE2x");
+        let error = parse(&text).err().expect("an error");
+        assert_eq!((error.line(), error.column()), (17, Some(79)));
+    }
+
+    #[test]
+    fn keeps_the_first_alternate_location() {
+        let mol = parse(PEPTIDE).unwrap();
+        assert_eq!(mol.atoms.len(), 17);
+        assert_eq!(mol.atoms[7].position, Vec3::new(3.7, 0.987, -0.491));
+        assert_eq!(mol.atoms[7].occupancy, Some(0.6));
+        assert_eq!(mol.atoms[8].name.as_deref(), Some("C"));
+    }
+
+    #[test]
+    fn keeps_atoms_with_only_a_later_alternate_location() {
+        let text = PEPTIDE
+            .replace("  CA AGLY", "  CA BGLY")
+            .replace("  CA BGLY A   2       3.650", "  CA CGLY A   2       3.650")
+            .replace("  CB  ALA", "  CB BALA");
+        let mol = parse(&text).unwrap();
+        assert_eq!(mol.atoms.len(), 17);
+        assert_eq!(mol.atoms[4].name.as_deref(), Some("CB"));
+        assert_eq!(mol.atoms[7].position, Vec3::new(3.7, 0.987, -0.491));
+    }
+
+    #[test]
+    fn reads_conect_bond_orders() {
+        let bonds = bonds(&parse(PEPTIDE).unwrap());
+        // A repeated partner makes a double bond
+        assert!(bonds.contains(&(12, 13, 2)));
+
+        // CONECT records mentioning a skipped alternate location add nothing
+        let text = PEPTIDE.replace("CONECT    9   10", "CONECT   10    9");
+        assert_eq!(parse(&text).unwrap().bonds.len(), bonds.len());
+
+        let text = PEPTIDE.replace("CONECT   14   15   15", "CONECT   14   99");
+        let error = parse(&text).err().expect("an error");
+        assert_eq!(
+            error,
+            ParseError::AtomIndexOutOfRange {
+                line: 23,
+                column: 12,
+                index: 99,
+                atom_count: 17,
+            }
+        );
+    }
+
+    #[test]
+    fn builds_residue_bonds_from_templates() {
+        let bonds = bonds(&parse(PEPTIDE).unwrap());
+        assert!(bonds.contains(&(0, 1, 1)));
+        assert!(bonds.contains(&(2, 3, 2)));
+        // The hydrogen on the nitrogen and the peptide bond
+        assert!(bonds.contains(&(0, 5, 1)));
+        assert!(bonds.contains(&(2, 6, 1)));
+        assert_eq!(bonds.len(), 13);
+    }
+
+    #[test]
+    fn reads_only_the_first_model() {
+        let text = PEPTIDE.replace("ATOM     12", "ENDMDL\nATOM     12");
+        let mol = parse(&text).unwrap();
+        assert_eq!(mol.atoms.len(), 10);
+    }
+}
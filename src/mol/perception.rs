@@ -0,0 +1,235 @@
+//! Bond perception for formats, such as XYZ, that only give coordinates.
+//!
+//! Two atoms are bonded when they are closer than the sum of their covalent
+//! radii plus a tolerance. Atoms are binned into a grid of cells as wide as
+//! the longest possible bond, so each atom is only compared with those in the
+//! 27 cells around it and large structures take roughly linear time.
+use super::residues::BondSet;
+use super::{Atom, Bond, Mol};
+use glam::Vec3;
+use std::collections::HashMap;
+
+/// Slack added to the sum of two covalent radii, in ångströms.
+pub const BOND_TOLERANCE: f32 = 0.45;
+
+/// Atoms closer than this are overlapping, not bonded.
+const MINIMUM_DISTANCE: f32 = 0.4;
+
+/// Covalent radius of an atom, with a generous one for query atoms and other
+/// symbols that aren't elements.
+fn covalent_radius(atom: &Atom) -> f32 {
+    atom.element.data().map_or(1.5, |data| data.covalent_radius)
+}
+
+/// Furthest a cell can be from the origin along each axis. Atoms further out
+/// share the outermost cells, which keeps stray coordinates such as `1e12`
+/// from overflowing when neighbouring cells are counted.
+const CELL_LIMIT: i32 = 1 << 24;
+
+/// Atoms binned into cubic cells, so those near a point can be found without
+/// checking every atom.
+pub struct Grid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32, i32), Vec<usize>>,
+}
+
+impl Grid {
+    /// Bins the atoms at `indices` into cells `cell_size` wide.
+    pub fn new(atoms: &[Atom], indices: impl Iterator<Item = usize>, cell_size: f32) -> Self {
+        let mut grid = Grid {
+            cell_size,
+            cells: HashMap::new(),
+        };
+        for index in indices {
+            let cell = grid.cell(atoms[index].position);
+            grid.cells.entry(cell).or_default().push(index);
+        }
+        grid
+    }
+
+    fn cell(&self, position: Vec3) -> (i32, i32, i32) {
+        let position = position / self.cell_size;
+        let axis = |coordinate: f32| (coordinate.floor() as i32).clamp(-CELL_LIMIT, CELL_LIMIT);
+        (axis(position.x()), axis(position.y()), axis(position.z()))
+    }
+
+    /// The atoms in the 27 cells around `position`, which include every atom
+    /// within a cell's width of it.
+    pub fn near(&self, position: Vec3) -> impl Iterator<Item = usize> + '_ {
+        let (x, y, z) = self.cell(position);
+        (-1..=1)
+            .flat_map(move |dx| {
+                (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| (x + dx, y + dy, z + dz)))
+            })
+            .flat_map(move |cell| self.cells.get(&cell).into_iter().flatten().copied())
+    }
+}
+
+/// Returns every pair of atoms within `tolerance` of bonding distance, with
+/// the lower index first.
+pub fn perceive_pairs(atoms: &[Atom], tolerance: f32) -> Vec<(usize, usize)> {
+    let radii = atoms.iter().map(covalent_radius).collect::<Vec<_>>();
+    let largest = radii.iter().copied().fold(0.0, f32::max);
+    let cell_size = (largest * 2.0 + tolerance).max(MINIMUM_DISTANCE);
+    let grid = Grid::new(atoms, 0..atoms.len(), cell_size);
+
+    let mut pairs = Vec::new();
+    for (first, atom) in atoms.iter().enumerate() {
+        for second in grid.near(atom.position) {
+            if second <= first {
+                continue;
+            }
+            let distance = (atoms[second].position - atom.position).length();
+            let cutoff = radii[first] + radii[second] + tolerance;
+            if distance > MINIMUM_DISTANCE && distance <= cutoff {
+                pairs.push((first, second));
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Connects every pair of atoms within bonding distance with a single bond.
+pub fn perceive_bonds(atoms: &[Atom]) -> Vec<Bond> {
+    perceive_pairs(atoms, BOND_TOLERANCE)
+        .into_iter()
+        .map(|(first, second)| Bond::new(first, second, 1, atoms))
+        .collect()
+}
+
+/// Adds perceived single bonds to `bonds` for pairs of atoms that aren't
+/// both `covered`, that is, already bonded by templates or the file itself.
+/// `covered` is given atom indices.
+pub(super) fn fill_missing(atoms: &[Atom], bonds: &mut BondSet, covered: impl Fn(usize) -> bool) {
+    for (first, second) in perceive_pairs(atoms, BOND_TOLERANCE) {
+        if !(covered(first) && covered(second)) {
+            bonds.insert(first, second, 1);
+        }
+    }
+}
+
+/// Bonds each atom usually makes, for elements whose bond orders are guessed.
+fn valence(symbol: &str) -> Option<u8> {
+    match symbol {
+        "C" => Some(4),
+        "N" => Some(3),
+        "O" | "S" => Some(2),
+        _ => None,
+    }
+}
+
+/// Raises single bonds to double or triple bonds where both atoms have
+/// valence to spare and the bond is short for a single bond. Shorter bonds
+/// are promoted first, which gives aromatic rings one Kekulé structure.
+///
+/// This relies on hydrogens being present, as they usually are in XYZ files.
+pub fn assign_bond_orders(atoms: &[Atom], bonds: &mut [Bond]) {
+    let mut free = atoms
+        .iter()
+        .map(|atom| valence(&atom.symbol).map_or(0, |valence| valence as i32))
+        .collect::<Vec<_>>();
+    for bond in bonds.iter() {
+        free[bond.first_atom] -= bond.bond_type as i32;
+        free[bond.second_atom] -= bond.bond_type as i32;
+    }
+
+    // Bond length over the sum of covalent radii, which is about 1 for single
+    // bonds, 0.87 for double bonds and 0.79 for triple bonds
+    let ratio = |bond: &Bond| {
+        bond.length
+            / (covalent_radius(&atoms[bond.first_atom]) + covalent_radius(&atoms[bond.second_atom]))
+    };
+    let mut candidates = (0..bonds.len())
+        .filter(|&index| {
+            let bond = &bonds[index];
+            bond.bond_type == 1
+                && valence(&atoms[bond.first_atom].symbol).is_some()
+                && valence(&atoms[bond.second_atom].symbol).is_some()
+                && ratio(bond) < 0.94
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|&a, &b| ratio(&bonds[a]).total_cmp(&ratio(&bonds[b])));
+
+    for index in candidates {
+        let bond = &mut bonds[index];
+        let spare = free[bond.first_atom].min(free[bond.second_atom]);
+        let extra = if spare >= 2 && ratio(bond) < 0.82 {
+            2
+        } else if spare >= 1 {
+            1
+        } else {
+            continue;
+        };
+        bond.bond_type += extra as u8;
+        free[bond.first_atom] -= extra;
+        free[bond.second_atom] -= extra;
+    }
+}
+
+impl Mol {
+    /// Replaces the bonds with ones perceived from atom distances.
+    pub fn perceive_bonds(&mut self) {
+        self.bonds = perceive_bonds(&self.atoms);
+    }
+
+    /// Guesses double and triple bonds among the current single bonds.
+    pub fn assign_bond_orders(&mut self) {
+        assign_bond_orders(&self.atoms, &mut self.bonds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atoms(positions: &[(&str, f32, f32, f32)]) -> Vec<Atom> {
+        positions
+            .iter()
+            .map(|&(symbol, x, y, z)| Atom::new(symbol, Vec3::new(x, y, z)))
+            .collect()
+    }
+
+    #[test]
+    fn bonds_atoms_within_reach() {
+        let atoms = atoms(&[
+            ("C", 0.0, 0.0, 0.0),
+            ("O", 1.2, 0.0, 0.0),
+            ("C", 1.2, 3.0, 0.0),
+            ("H", -0.3, 0.0, 0.0),
+        ]);
+        // Too far apart, or too close together
+        assert_eq!(perceive_pairs(&atoms, BOND_TOLERANCE), [(0, 1)]);
+    }
+
+    #[test]
+    fn survives_stray_coordinates() {
+        let atoms = atoms(&[
+            ("C", 0.0, 0.0, 0.0),
+            ("C", 1.2, 0.0, 0.0),
+            ("C", 1e12, -1e12, 3e38),
+            ("C", f32::NAN, 0.0, 0.0),
+            ("C", f32::INFINITY, 0.0, 0.0),
+        ]);
+        assert_eq!(perceive_pairs(&atoms, BOND_TOLERANCE), [(0, 1)]);
+    }
+
+    #[test]
+    fn assigns_orders_around_nan_lengths() {
+        let atoms = atoms(&[
+            ("C", 0.0, 0.0, 0.0),
+            ("C", 1.2, 0.0, 0.0),
+            ("C", f32::NAN, 0.0, 0.0),
+            ("C", 0.0, 1.3, 0.0),
+        ]);
+        // Bonds given by a file aren't checked against their atoms' positions
+        let mut bonds = vec![
+            Bond::new(0, 2, 1, &atoms),
+            Bond::new(0, 1, 1, &atoms),
+            Bond::new(0, 3, 1, &atoms),
+        ];
+        assign_bond_orders(&atoms, &mut bonds);
+        assert_eq!(bonds[0].bond_type, 1);
+        assert_eq!(bonds[1].bond_type, 2);
+    }
+}
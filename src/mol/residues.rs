@@ -0,0 +1,385 @@
+//! Bond templates for the standard amino acids and nucleotides. PDB and mmCIF
+//! files rarely list bonds inside these residues, so they are rebuilt from
+//! atom names instead. Aromatic rings use one Kekulé structure.
+use super::{Atom, Bond};
+use std::collections::HashMap;
+use std::ops::Range;
+
+type Template = &'static [(&'static str, &'static str, u8)];
+
+const BACKBONE: Template = &[
+    ("N", "CA", 1),
+    ("CA", "C", 1),
+    ("C", "O", 2),
+    ("C", "OXT", 1),
+];
+
+const SUGAR_PHOSPHATE: Template = &[
+    ("P", "OP1", 2),
+    ("P", "OP2", 1),
+    ("P", "OP3", 1),
+    ("P", "O5'", 1),
+    ("O5'", "C5'", 1),
+    ("C5'", "C4'", 1),
+    ("C4'", "O4'", 1),
+    ("C4'", "C3'", 1),
+    ("C3'", "O3'", 1),
+    ("C3'", "C2'", 1),
+    ("C2'", "C1'", 1),
+    ("C1'", "O4'", 1),
+    ("C2'", "O2'", 1),
+];
+
+/// Amino acid side chains, starting from `CA`.
+fn side_chain(residue: &str) -> Option<Template> {
+    Some(match residue {
+        "GLY" => &[],
+        "ALA" => &[("CA", "CB", 1)],
+        "ARG" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD", 1),
+            ("CD", "NE", 1),
+            ("NE", "CZ", 1),
+            ("CZ", "NH1", 1),
+            ("CZ", "NH2", 2),
+        ],
+        "ASN" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "OD1", 2),
+            ("CG", "ND2", 1),
+        ],
+        "ASP" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "OD1", 2),
+            ("CG", "OD2", 1),
+        ],
+        "CYS" => &[("CA", "CB", 1), ("CB", "SG", 1)],
+        "GLN" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD", 1),
+            ("CD", "OE1", 2),
+            ("CD", "NE2", 1),
+        ],
+        "GLU" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD", 1),
+            ("CD", "OE1", 2),
+            ("CD", "OE2", 1),
+        ],
+        "HIS" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "ND1", 1),
+            ("CG", "CD2", 2),
+            ("ND1", "CE1", 2),
+            ("CE1", "NE2", 1),
+            ("NE2", "CD2", 1),
+        ],
+        "ILE" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG1", 1),
+            ("CB", "CG2", 1),
+            ("CG1", "CD1", 1),
+        ],
+        "LEU" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD1", 1),
+            ("CG", "CD2", 1),
+        ],
+        "LYS" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD", 1),
+            ("CD", "CE", 1),
+            ("CE", "NZ", 1),
+        ],
+        "MET" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "SD", 1),
+            ("SD", "CE", 1),
+        ],
+        "MSE" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "SE", 1),
+            ("SE", "CE", 1),
+        ],
+        "PHE" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD1", 2),
+            ("CG", "CD2", 1),
+            ("CD1", "CE1", 1),
+            ("CD2", "CE2", 2),
+            ("CE1", "CZ", 2),
+            ("CE2", "CZ", 1),
+        ],
+        "PRO" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD", 1),
+            ("CD", "N", 1),
+        ],
+        "SER" => &[("CA", "CB", 1), ("CB", "OG", 1)],
+        "THR" => &[("CA", "CB", 1), ("CB", "OG1", 1), ("CB", "CG2", 1)],
+        "TRP" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD1", 2),
+            ("CG", "CD2", 1),
+            ("CD1", "NE1", 1),
+            ("NE1", "CE2", 1),
+            ("CD2", "CE2", 1),
+            ("CD2", "CE3", 2),
+            ("CE2", "CZ2", 2),
+            ("CE3", "CZ3", 1),
+            ("CZ3", "CH2", 2),
+            ("CZ2", "CH2", 1),
+        ],
+        "TYR" => &[
+            ("CA", "CB", 1),
+            ("CB", "CG", 1),
+            ("CG", "CD1", 2),
+            ("CG", "CD2", 1),
+            ("CD1", "CE1", 1),
+            ("CD2", "CE2", 2),
+            ("CE1", "CZ", 2),
+            ("CE2", "CZ", 1),
+            ("CZ", "OH", 1),
+        ],
+        "VAL" => &[("CA", "CB", 1), ("CB", "CG1", 1), ("CB", "CG2", 1)],
+        _ => return None,
+    })
+}
+
+/// Nucleotide bases, including the glycosidic bond to `C1'`.
+fn base(residue: &str) -> Option<Template> {
+    Some(match residue {
+        "A" | "DA" => &[
+            ("C1'", "N9", 1),
+            ("N9", "C8", 1),
+            ("C8", "N7", 2),
+            ("N7", "C5", 1),
+            ("C5", "C4", 2),
+            ("C4", "N9", 1),
+            ("C5", "C6", 1),
+            ("C6", "N6", 1),
+            ("C6", "N1", 2),
+            ("N1", "C2", 1),
+            ("C2", "N3", 2),
+            ("N3", "C4", 1),
+        ],
+        "G" | "DG" => &[
+            ("C1'", "N9", 1),
+            ("N9", "C8", 1),
+            ("C8", "N7", 2),
+            ("N7", "C5", 1),
+            ("C5", "C4", 2),
+            ("C4", "N9", 1),
+            ("C5", "C6", 1),
+            ("C6", "O6", 2),
+            ("C6", "N1", 1),
+            ("N1", "C2", 1),
+            ("C2", "N2", 1),
+            ("C2", "N3", 2),
+            ("N3", "C4", 1),
+        ],
+        "C" | "DC" => &[
+            ("C1'", "N1", 1),
+            ("N1", "C2", 1),
+            ("C2", "O2", 2),
+            ("C2", "N3", 1),
+            ("N3", "C4", 2),
+            ("C4", "N4", 1),
+            ("C4", "C5", 1),
+            ("C5", "C6", 2),
+            ("C6", "N1", 1),
+        ],
+        "U" | "DU" => &[
+            ("C1'", "N1", 1),
+            ("N1", "C2", 1),
+            ("C2", "O2", 2),
+            ("C2", "N3", 1),
+            ("N3", "C4", 1),
+            ("C4", "O4", 2),
+            ("C4", "C5", 1),
+            ("C5", "C6", 2),
+            ("C6", "N1", 1),
+        ],
+        "DT" => &[
+            ("C1'", "N1", 1),
+            ("N1", "C2", 1),
+            ("C2", "O2", 2),
+            ("C2", "N3", 1),
+            ("N3", "C4", 1),
+            ("C4", "O4", 2),
+            ("C4", "C5", 1),
+            ("C5", "C7", 1),
+            ("C5", "C6", 2),
+            ("C6", "N1", 1),
+        ],
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Polymer {
+    Protein,
+    NucleicAcid,
+}
+
+/// Maps pre-remediation atom names, such as `O3*` and `O1P`, to current ones.
+pub(super) fn normalize_name(name: &str) -> String {
+    match name {
+        "O1P" => "OP1".to_string(),
+        "O2P" => "OP2".to_string(),
+        "O3P" => "OP3".to_string(),
+        "C5M" => "C7".to_string(),
+        _ => name.replace('*', "'"),
+    }
+}
+
+/// Whether the atom's residue is one whose bonds come from a template.
+pub(super) fn has_template(atom: &Atom) -> bool {
+    let residue = atom.residue_name.as_deref().unwrap_or("");
+    side_chain(residue).is_some() || base(residue).is_some()
+}
+
+fn is_hydrogen(atom: &Atom) -> bool {
+    atom.symbol == "H" || atom.symbol == "D"
+}
+
+/// Splits atoms into residues: runs of atoms sharing a chain, residue number
+/// and residue name. A repeated atom name also starts a new residue, which
+/// separates insertion codes.
+pub(super) fn residue_ranges(atoms: &[Atom]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut names: Vec<&str> = Vec::new();
+    for (index, atom) in atoms.iter().enumerate() {
+        let first = &atoms[start];
+        let name = atom.name.as_deref().unwrap_or("");
+        let same_residue = index > start
+            && atom.chain_id == first.chain_id
+            && atom.residue_number == first.residue_number
+            && atom.residue_name == first.residue_name
+            && !names.contains(&name);
+        if index > start && !same_residue {
+            ranges.push(start..index);
+            start = index;
+            names.clear();
+        }
+        names.push(name);
+    }
+    if start < atoms.len() {
+        ranges.push(start..atoms.len());
+    }
+    ranges
+}
+
+/// Returns `(first atom, second atom, bond type)` for every bond implied by
+/// the standard residue templates, peptide and phosphodiester links between
+/// consecutive residues, and hydrogens on standard residues.
+pub(super) fn infer_bonds(atoms: &[Atom]) -> Vec<(usize, usize, u8)> {
+    let mut bonds = Vec::new();
+    // The previous residue's polymer type, chain and linking atom (C or O3')
+    let mut previous: Option<(Polymer, Option<&str>, Option<usize>)> = None;
+    for range in residue_ranges(atoms) {
+        let first = &atoms[range.start];
+        let residue = first.residue_name.as_deref().unwrap_or("");
+        let names: HashMap<String, usize> = range
+            .clone()
+            .filter_map(|index| {
+                let name = atoms[index].name.as_deref()?;
+                Some((normalize_name(name), index))
+            })
+            .collect();
+
+        let (polymer, templates): (Polymer, [Template; 2]) =
+            match (side_chain(residue), base(residue)) {
+                (Some(side_chain), _) => (Polymer::Protein, [BACKBONE, side_chain]),
+                (_, Some(base)) => (Polymer::NucleicAcid, [SUGAR_PHOSPHATE, base]),
+                _ => {
+                    previous = None;
+                    continue;
+                }
+            };
+        for &(from, to, bond_type) in templates.iter().flat_map(|template| template.iter()) {
+            if let (Some(&from), Some(&to)) = (names.get(from), names.get(to)) {
+                bonds.push((from, to, bond_type));
+            }
+        }
+
+        // Consecutive residues only link when the atoms are actually close,
+        // which keeps chain breaks and missing residues apart
+        let (head, tail) = match polymer {
+            Polymer::Protein => ("N", "C"),
+            Polymer::NucleicAcid => ("P", "O3'"),
+        };
+        if let (Some((kind, chain, Some(link))), Some(&head)) = (previous, names.get(head)) {
+            let close = (atoms[link].position - atoms[head].position).length() < 2.0;
+            if kind == polymer && chain == first.chain_id.as_deref() && close {
+                bonds.push((link, head, 1));
+            }
+        }
+        previous = Some((polymer, first.chain_id.as_deref(), names.get(tail).copied()));
+
+        for hydrogen in range.clone().filter(|&index| is_hydrogen(&atoms[index])) {
+            let nearest = range
+                .clone()
+                .filter(|&index| !is_hydrogen(&atoms[index]))
+                .map(|index| {
+                    (
+                        index,
+                        (atoms[index].position - atoms[hydrogen].position).length(),
+                    )
+                })
+                .filter(|&(_, distance)| distance < 1.4)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            if let Some((heavy, _)) = nearest {
+                bonds.push((heavy, hydrogen, 1));
+            }
+        }
+    }
+    bonds
+}
+
+/// Collects bonds from several sources, such as templates and `CONECT`
+/// records. The first order given for a pair of atoms wins, and bonds keep
+/// the order they were first seen in.
+pub(super) struct BondSet {
+    orders: HashMap<(usize, usize), u8>,
+    pairs: Vec<(usize, usize)>,
+}
+
+impl BondSet {
+    pub(super) fn new() -> Self {
+        BondSet {
+            orders: HashMap::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    pub(super) fn insert(&mut self, from: usize, to: usize, bond_type: u8) {
+        let key = (from.min(to), from.max(to));
+        if from != to && !self.orders.contains_key(&key) {
+            self.orders.insert(key, bond_type);
+            self.pairs.push(key);
+        }
+    }
+
+    pub(super) fn into_bonds(self, atoms: &[Atom]) -> Vec<Bond> {
+        let orders = self.orders;
+        self.pairs
+            .into_iter()
+            .map(|(from, to)| Bond::new(from, to, orders[&(from, to)], atoms))
+            .collect()
+    }
+}
@@ -0,0 +1,289 @@
+//! Reader for SD files: molfiles separated by `$$$$` lines, each optionally
+//! followed by `> <TAG>` data items.
+//!
+//! Splitting only scans for record boundaries, so opening a file with
+//! hundreds of records is cheap. Each record's molfile is parsed when it is
+//! asked for.
+use super::{molfile, Mol, ParseError};
+use std::ops::Range;
+
+pub struct SdfRecord {
+    /// First line of the molfile header, usually the compound name
+    pub name: String,
+    /// Number of lines in the file before this record
+    line_offset: usize,
+    /// Byte range of the molfile, up to and including `M  END`
+    molfile: Range<usize>,
+    /// Byte range of the data items between `M  END` and `$$$$`
+    data: Range<usize>,
+}
+
+pub struct Sdf {
+    text: String,
+    records: Vec<SdfRecord>,
+}
+
+impl Sdf {
+    pub fn new(text: String) -> Self {
+        let mut records = Vec::new();
+        let mut start = 0;
+        let mut start_line = 0;
+        let mut molfile_end = None;
+        let mut offset = 0;
+        for (number, line) in text.split('\n').enumerate() {
+            let line_end = offset + line.len();
+            let next = (line_end + 1).min(text.len());
+            let content = line.trim_end_matches('\r');
+            if content.starts_with("$$$$") {
+                let end = molfile_end.take().unwrap_or(offset);
+                records.push(SdfRecord::new(&text, start_line, start..end, end..offset));
+                start = next;
+                start_line = number + 1;
+            } else if molfile_end.is_none() && content.starts_with("M  END") {
+                molfile_end = Some(next);
+            } else if molfile_end.is_none() && number >= start_line + 4 && content.starts_with('>')
+            {
+                // Data items without an `M  END` before them
+                molfile_end = Some(offset);
+            }
+            offset = next;
+        }
+        // A file that doesn't end in `$$$$`, such as a lone molfile
+        if !text[start..].trim().is_empty() {
+            let end = molfile_end.unwrap_or(text.len());
+            records.push(SdfRecord::new(
+                &text,
+                start_line,
+                start..end,
+                end..text.len(),
+            ));
+        }
+        Sdf { text, records }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn records(&self) -> &[SdfRecord] {
+        &self.records
+    }
+
+    /// Parses the molfile of record `index`. Errors carry line numbers in the
+    /// whole file, not the record.
+    pub fn molecule(&self, index: usize) -> Option<Result<Mol, ParseError>> {
+        let record = self.records.get(index)?;
+        let mut mol = match molfile::parse(&self.text[record.molfile.clone()]) {
+            Ok(mol) => mol,
+            Err(error) => return Some(Err(error.offset(record.line_offset))),
+        };
+        for (tag, value) in self.properties(index)? {
+            if tag == "PUBCHEM_MMFF94_PARTIAL_CHARGES" {
+                apply_partial_charges(&mut mol, &value);
+            }
+        }
+        Some(Ok(mol))
+    }
+
+    /// Returns the `(tag, value)` data items of record `index`, in file order.
+    /// Multi-line values are joined with newlines.
+    pub fn properties(&self, index: usize) -> Option<Vec<(String, String)>> {
+        let record = self.records.get(index)?;
+        let mut properties = Vec::new();
+        let mut lines = self.text[record.data.clone()].lines();
+        while let Some(line) = lines.next() {
+            if !line.starts_with('>') {
+                continue;
+            }
+            let tag = line
+                .find('<')
+                .and_then(|open| {
+                    let close = line[open..].find('>')?;
+                    Some(&line[open + 1..open + close])
+                })
+                .unwrap_or("");
+            let value = lines
+                .by_ref()
+                .take_while(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            properties.push((tag.to_string(), value));
+        }
+        Some(properties)
+    }
+}
+
+/// Reads PubChem's partial charges: a count, then one `atom charge` line for
+/// each charged atom, numbered from 1. Malformed lines are skipped.
+fn apply_partial_charges(mol: &mut Mol, value: &str) {
+    for atom in &mut mol.atoms {
+        atom.partial_charge = Some(0.0);
+    }
+    for line in value.lines().skip(1) {
+        let mut fields = line.split_whitespace();
+        let number = fields.next().and_then(|field| field.parse::<usize>().ok());
+        let charge = fields.next().and_then(|field| field.parse::<f32>().ok());
+        if let (Some(number), Some(charge)) = (number, charge) {
+            if let Some(atom) = number
+                .checked_sub(1)
+                .and_then(|index| mol.atoms.get_mut(index))
+            {
+                atom.partial_charge = Some(charge);
+            }
+        }
+    }
+}
+
+impl SdfRecord {
+    fn new(text: &str, line_offset: usize, molfile: Range<usize>, data: Range<usize>) -> Self {
+        SdfRecord {
+            name: text[molfile.clone()]
+                .lines()
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_string(),
+            line_offset,
+            molfile,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three waters: one with `M  END` before its data items, one without,
+    /// and a last one with no `$$$$` after it
+    const WATERS: &str = "\
+First water
+     RDKit          3D
+
+  3  2  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+    0.9572    0.0000    0.0000 H   0  0  0  0  0  0  0  0  0  0  0  0
+   -0.2400    0.9266    0.0000 H   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  1  3  1  0
+M  END
+> <ID>
+W1
+
+> <PUBCHEM_MMFF94_PARTIAL_CHARGES>
+3
+1 -0.68
+not a charge
+3 0.34
+9 1.0
+
+$$$$
+Second water
+     RDKit          3D
+
+  3  2  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+    0.9572    0.0000    0.0000 H   0  0  0  0  0  0  0  0  0  0  0  0
+   -0.2400    0.9266    0.0000 H   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  1  3  1  0
+> <ID>
+W2
+
+> <NOTE>
+first line
+second line
+
+$$$$
+Third water
+     RDKit          3D
+
+  3  2  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+    0.9572    0.0000    0.0000 H   0  0  0  0  0  0  0  0  0  0  0  0
+   -0.2400    0.9266    0.0000 H   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  1  3  1  0
+M  END
+
+
+";
+
+    fn names(sdf: &Sdf) -> Vec<&str> {
+        sdf.records()
+            .iter()
+            .map(|record| record.name.as_str())
+            .collect()
+    }
+
+    fn check_waters(sdf: &Sdf) {
+        assert_eq!(names(sdf), ["First water", "Second water", "Third water"]);
+        for index in 0..sdf.len() {
+            let mol = sdf.molecule(index).unwrap().expect("a molecule");
+            assert_eq!((mol.atoms.len(), mol.bonds.len()), (3, 2));
+        }
+        let properties = sdf.properties(1).unwrap();
+        assert_eq!(
+            properties,
+            [
+                ("ID".to_string(), "W2".to_string()),
+                ("NOTE".to_string(), "first line\nsecond line".to_string()),
+            ]
+        );
+        assert!(sdf.properties(2).unwrap().is_empty());
+        assert!(sdf.molecule(3).is_none());
+    }
+
+    #[test]
+    fn splits_records_with_and_without_m_end() {
+        let sdf = Sdf::new(WATERS.to_string());
+        check_waters(&sdf);
+        assert_eq!(
+            sdf.properties(0).unwrap()[0],
+            ("ID".to_string(), "W1".to_string())
+        );
+    }
+
+    #[test]
+    fn splits_crlf_files() {
+        check_waters(&Sdf::new(WATERS.replace('\n', "\r\n")));
+    }
+
+    #[test]
+    fn ignores_trailing_blank_lines() {
+        let text = format!("{}$$$$\n\n\n", WATERS.trim_end());
+        let sdf = Sdf::new(text);
+        assert_eq!(sdf.len(), 3);
+        assert!(Sdf::new("\n\n".to_string()).is_empty());
+    }
+
+    #[test]
+    fn reads_partial_charges() {
+        let sdf = Sdf::new(WATERS.to_string());
+        let mol = sdf.molecule(0).unwrap().expect("a molecule");
+        let charges = mol.atoms.iter().map(|atom| atom.partial_charge);
+        // The second hydrogen is unlisted, and malformed lines are skipped
+        assert_eq!(
+            charges.collect::<Vec<_>>(),
+            [Some(-0.68), Some(0.0), Some(0.34)]
+        );
+        let mol = sdf.molecule(1).unwrap().expect("a molecule");
+        assert_eq!(mol.atoms[0].partial_charge, None);
+    }
+
+    #[test]
+    fn locates_errors_in_the_whole_file() {
+        // Breaks the second record's first atom, on line 26 of the file
+        let second = WATERS.find("Second water").unwrap();
+        let (first, rest) = WATERS.split_at(second);
+        let text = first.to_string() + &rest.replacen("    0.0000", "    0.0x00", 1);
+        let sdf = Sdf::new(text);
+        let error = sdf.molecule(1).unwrap().err().expect("an error");
+        assert_eq!((error.line(), error.column()), (26, Some(1)));
+        assert!(sdf.molecule(0).unwrap().is_ok());
+    }
+}
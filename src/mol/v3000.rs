@@ -0,0 +1,415 @@
+//! Reader for the MDL V3000 (extended) connection table, which RDKit and
+//! ChemDraw switch to for molecules with more than 999 atoms.
+//!
+//! Every line of a V3000 table starts with `M  V30 `. A line ending in `-`
+//! continues on the next one, and fields are whitespace separated, with
+//! optional `KEY=value` properties after the positional ones.
+use super::molfile::{check_symbol, Lines};
+use super::{Atom, Bond, ParseError};
+use glam::Vec3;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const PREFIX: &str = "M  V30 ";
+
+/// One `M  V30` record with its continuation lines joined together.
+struct Record {
+    text: String,
+    /// `(line number, offset into text)` of each physical line, so errors can
+    /// point back at the right place in the file
+    segments: Vec<(usize, usize)>,
+}
+
+impl Record {
+    /// Maps an offset into the joined text back to a 1-based line and column.
+    fn locate(&self, offset: usize) -> (usize, usize) {
+        let (line, start) = self
+            .segments
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= offset)
+            .copied()
+            .unwrap_or((0, 0));
+        (line, offset - start + PREFIX.len() + 1)
+    }
+
+    fn line(&self) -> usize {
+        self.segments[0].0
+    }
+
+    fn tokens(&self) -> Vec<Token<'_>> {
+        tokenize(&self.text)
+    }
+
+    fn invalid(&self, token: &Token, expected: &'static str) -> ParseError {
+        let (line, column) = self.locate(token.offset);
+        ParseError::InvalidField {
+            line,
+            column,
+            expected,
+            found: token.text.to_string(),
+        }
+    }
+
+    fn parse<T: FromStr>(&self, token: &Token, expected: &'static str) -> Result<T, ParseError> {
+        token
+            .text
+            .parse()
+            .map_err(|_| self.invalid(token, expected))
+    }
+
+    /// Parses the positional field at `index`, failing if the record is short.
+    fn field<T: FromStr>(
+        &self,
+        tokens: &[Token],
+        index: usize,
+        expected: &'static str,
+    ) -> Result<T, ParseError> {
+        match tokens.get(index) {
+            Some(token) => self.parse(token, expected),
+            None => {
+                let (line, column) = self.locate(self.text.len());
+                Err(ParseError::InvalidField {
+                    line,
+                    column,
+                    expected,
+                    found: String::new(),
+                })
+            }
+        }
+    }
+}
+
+struct Token<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Token<'a> {
+    /// Splits a `KEY=value` property, unquoting the value.
+    fn property(&self) -> Option<(&'a str, Token<'a>)> {
+        let equals = self.text.find('=')?;
+        let value = &self.text[equals + 1..];
+        let (value, skip) = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            (&value[1..value.len() - 1], 1)
+        } else {
+            (value, 0)
+        };
+        Some((
+            &self.text[..equals],
+            Token {
+                text: value,
+                offset: self.offset + equals + 1 + skip,
+            },
+        ))
+    }
+}
+
+/// Splits a record on whitespace, keeping `"quoted strings"` and
+/// `(parenthesised lists)` together as single tokens.
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut depth = 0;
+    let mut quoted = false;
+    for (offset, character) in text.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted && depth > 0 => depth -= 1,
+            _ => {}
+        }
+        if character.is_whitespace() && !quoted && depth == 0 {
+            if let Some(begin) = start.take() {
+                tokens.push(Token {
+                    text: &text[begin..offset],
+                    offset: begin,
+                });
+            }
+        } else if start.is_none() {
+            start = Some(offset);
+        }
+    }
+    if let Some(begin) = start {
+        tokens.push(Token {
+            text: &text[begin..],
+            offset: begin,
+        });
+    }
+    tokens
+}
+
+fn read_record(lines: &mut Lines, block: &'static str) -> Result<Record, ParseError> {
+    let mut record = Record {
+        text: String::new(),
+        segments: Vec::new(),
+    };
+    loop {
+        let line = lines.expect(block)?;
+        let body = match line.strip_prefix(PREFIX) {
+            Some(body) => body,
+            None => {
+                return Err(ParseError::InvalidField {
+                    line: lines.number,
+                    column: 1,
+                    expected: "an \"M  V30\" line",
+                    found: line.to_string(),
+                })
+            }
+        };
+        record.segments.push((lines.number, record.text.len()));
+        match body.trim_end().strip_suffix('-') {
+            Some(continued) => record.text.push_str(continued),
+            None => {
+                record.text.push_str(body);
+                return Ok(record);
+            }
+        }
+    }
+}
+
+/// Reads records up to `END <name>`, discarding them.
+fn skip_block(lines: &mut Lines, name: &str) -> Result<(), ParseError> {
+    let end = format!("END {}", name);
+    loop {
+        if read_record(lines, "connection table")?.text.trim() == end {
+            return Ok(());
+        }
+    }
+}
+
+/// Maps a V3000 bond `CFG` onto the V2000 stereo code kept in `Bond::stereo`.
+fn stereo_from_cfg(cfg: u8) -> u8 {
+    match cfg {
+        1 => 1,
+        2 => 4,
+        3 => 6,
+        _ => 0,
+    }
+}
+
+fn parse_atom(record: &Record) -> Result<(i64, Atom), ParseError> {
+    let mut tokens = record.tokens();
+    // Atom lists such as `[C,N]` or `NOT [O]` are query atoms
+    let negated_list = tokens.get(1).is_some_and(|token| token.text == "NOT");
+    if negated_list && tokens.len() > 2 {
+        tokens.remove(2);
+    }
+    let index = record.field(&tokens, 0, "an atom index")?;
+    let position = Vec3::new(
+        record.field(&tokens, 2, "an x coordinate")?,
+        record.field(&tokens, 3, "a y coordinate")?,
+        record.field(&tokens, 4, "a z coordinate")?,
+    );
+    let symbol = match tokens.get(1) {
+        Some(token) if token.text.starts_with('[') || token.text == "NOT" => "L",
+        Some(token) => token.text.trim_matches('"'),
+        None => "",
+    };
+    let (line, column) = record.locate(tokens.get(1).map_or(0, |token| token.offset));
+    check_symbol(symbol, line, column)?;
+
+    let mut atom = Atom::new(symbol, position);
+    for token in tokens.iter().skip(6) {
+        if let Some((key, value)) = token.property() {
+            match key {
+                "CHG" => atom.charge = record.parse(&value, "a charge")?,
+                "MASS" => atom.isotope = Some(record.parse(&value, "an isotope mass")?),
+                "CFG" => atom.stereo_parity = record.parse(&value, "a stereo configuration")?,
+                _ => {}
+            }
+        }
+    }
+    Ok((index, atom))
+}
+
+fn parse_bond(
+    record: &Record,
+    atoms: &[Atom],
+    indices: &HashMap<i64, usize>,
+) -> Result<Bond, ParseError> {
+    let tokens = record.tokens();
+    let bond_type = record.field(&tokens, 1, "a bond type")?;
+    let mut ends = [0; 2];
+    for (end, position) in ends.iter_mut().zip(2..4) {
+        let index = record.field(&tokens, position, "an atom index")?;
+        *end = match indices.get(&index) {
+            Some(&end) => end,
+            None => {
+                let (line, column) = record.locate(tokens[position].offset);
+                return Err(ParseError::AtomIndexOutOfRange {
+                    line,
+                    column,
+                    index,
+                    atom_count: atoms.len(),
+                });
+            }
+        };
+    }
+    let mut bond = Bond::new(ends[0], ends[1], bond_type, atoms);
+    for token in tokens.iter().skip(4) {
+        if let Some(("CFG", value)) = token.property() {
+            bond.stereo = stereo_from_cfg(record.parse(&value, "a stereo configuration")?);
+        }
+    }
+    Ok(bond)
+}
+
+/// Reads from `BEGIN CTAB` through `END CTAB`, leaving `lines` at the
+/// properties block.
+pub fn parse_ctab(lines: &mut Lines) -> Result<(Vec<Atom>, Vec<Bond>), ParseError> {
+    let begin = read_record(lines, "connection table")?;
+    if begin.text.trim() != "BEGIN CTAB" {
+        return Err(ParseError::InvalidField {
+            line: begin.line(),
+            column: PREFIX.len() + 1,
+            expected: "\"BEGIN CTAB\"",
+            found: begin.text.trim().to_string(),
+        });
+    }
+
+    let mut atoms = Vec::new();
+    let mut bonds = Vec::new();
+    // V3000 atom indices needn't be contiguous, so bonds are resolved through this
+    let mut indices = HashMap::new();
+    loop {
+        let record = read_record(lines, "connection table")?;
+        let tokens = record.tokens();
+        match tokens.iter().map(|token| token.text).collect::<Vec<_>>()[..] {
+            ["END", "CTAB"] => break,
+            ["BEGIN", "ATOM"] => loop {
+                let record = read_record(lines, "atom block")?;
+                if record.text.trim() == "END ATOM" {
+                    break;
+                }
+                let (index, atom) = parse_atom(&record)?;
+                indices.insert(index, atoms.len());
+                atoms.push(atom);
+            },
+            ["BEGIN", "BOND"] => loop {
+                let record = read_record(lines, "bond block")?;
+                if record.text.trim() == "END BOND" {
+                    break;
+                }
+                bonds.push(parse_bond(&record, &atoms, &indices)?);
+            },
+            // Sgroups, collections and 3D features don't affect what's drawn
+            ["BEGIN", name] => skip_block(lines, name)?,
+            // Including COUNTS, which isn't needed since blocks run to their
+            // END and can't be trusted to size anything
+            _ => {}
+        }
+    }
+    Ok((atoms, bonds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::molfile::parse;
+    use super::*;
+
+    /// Ethanol with a query atom list, a gap in the atom indices, a property
+    /// on a continuation line and an Sgroup to skip
+    const ETHANOL: &str = "\
+ethanol
+     RDKit          3D
+
+  0  0  0  0  0  0  0  0  0  0999 V3000
+M  V30 BEGIN CTAB
+M  V30 COUNTS 3 2 0 0 0
+M  V30 BEGIN ATOM
+M  V30 1 NOT [N,O] -0.9 0.1 0.0 0
+M  V30 2 C 0.5 -0.4 0.0 0 CFG=2 -
+M  V30 MASS=13
+M  V30 5 Cl 1.4 0.6 0.0 0 CHG=-1
+M  V30 END ATOM
+M  V30 BEGIN BOND
+M  V30 1 1 1 2
+M  V30 2 2 2 5 CFG=3
+M  V30 END BOND
+M  V30 BEGIN SGROUP
+M  V30 1 SUP 0 ATOMS=(1 1) LABEL=\"Me X\"
+M  V30 END SGROUP
+M  V30 END CTAB
+M  END
+";
+
+    fn parse_error(text: &str) -> ParseError {
+        parse(text).err().expect("an error")
+    }
+
+    #[test]
+    fn reads_atoms_and_bonds() {
+        let mol = parse(ETHANOL).unwrap();
+        assert_eq!(mol.name, "ethanol");
+        assert_eq!(mol.atoms.len(), 3);
+        assert_eq!(mol.atoms[0].symbol, "L");
+        assert_eq!(mol.atoms[2].symbol, "Cl");
+        assert_eq!(mol.atoms[2].position, Vec3::new(1.4, 0.6, 0.0));
+        assert_eq!(mol.atoms[2].charge, -1);
+        assert_eq!(mol.atoms[1].stereo_parity, 2);
+        assert_eq!(mol.bonds.len(), 2);
+        assert_eq!(mol.bonds[1].bond_type, 2);
+        assert_eq!(mol.bonds[1].stereo, 6);
+    }
+
+    #[test]
+    fn resolves_bonds_through_atom_indices() {
+        let mol = parse(ETHANOL).unwrap();
+        assert_eq!((mol.bonds[1].first_atom, mol.bonds[1].second_atom), (1, 2));
+    }
+
+    #[test]
+    fn joins_continuation_lines() {
+        let mol = parse(ETHANOL).unwrap();
+        assert_eq!(mol.atoms[1].isotope, Some(13));
+
+        // Errors on a continuation line point at that line
+        let error = parse_error(&ETHANOL.replace("M  V30 MASS=13", "M  V30 MASS=x"));
+        assert_eq!((error.line(), error.column()), (10, Some(13)));
+    }
+
+    #[test]
+    fn keeps_quotes_and_lists_together() {
+        let record = "1 SUP 0 ATOMS=(2 1 3) LABEL=\"Me X\"";
+        let tokens = tokenize(record);
+        let texts = tokens.iter().map(|token| token.text).collect::<Vec<_>>();
+        assert_eq!(texts, ["1", "SUP", "0", "ATOMS=(2 1 3)", "LABEL=\"Me X\""]);
+        let (key, value) = tokens[4].property().unwrap();
+        assert_eq!((key, value.text), ("LABEL", "Me X"));
+        assert_eq!(&record[value.offset..value.offset + 4], "Me X");
+    }
+
+    #[test]
+    fn locates_missing_atoms() {
+        let error = parse_error(&ETHANOL.replace("2 2 2 5", "2 2 2 6"));
+        assert_eq!(
+            error,
+            ParseError::AtomIndexOutOfRange {
+                line: 15,
+                column: 14,
+                index: 6,
+                atom_count: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn reports_unfinished_tables() {
+        let text = ETHANOL.lines().take(12).collect::<Vec<_>>().join("\n");
+        assert_eq!(
+            parse_error(&text),
+            ParseError::TruncatedBlock {
+                line: 13,
+                block: "connection table",
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_counts_too_large_to_allocate() {
+        let text = ETHANOL.replace("COUNTS 3 2", "COUNTS 99999999999999 99999999999999");
+        let mol = parse(&text).unwrap();
+        assert_eq!((mol.atoms.len(), mol.bonds.len()), (3, 2));
+    }
+}
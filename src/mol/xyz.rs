@@ -0,0 +1,230 @@
+//! Reader for XYZ files: an atom count line, a comment line, then one
+//! `Element x y z` line per atom. Geometry optimisations and molecular
+//! dynamics runs repeat that block once per frame.
+//!
+//! XYZ has no connectivity, so each frame's bonds and their orders come from
+//! bond perception.
+use super::molfile::Lines;
+use super::pdb::capitalize_symbol;
+use super::{perception, Atom, Element, ElementData, Mol, ParseError};
+use glam::Vec3;
+use std::str::FromStr;
+
+/// Iterates over the frames of an XYZ file, parsing each one as it is
+/// reached. Iteration stops after the first error.
+pub struct XyzFrames<'a> {
+    lines: Lines<'a>,
+    failed: bool,
+}
+
+impl<'a> XyzFrames<'a> {
+    pub fn new(text: &'a str) -> Self {
+        XyzFrames {
+            lines: Lines::new(text),
+            failed: false,
+        }
+    }
+
+    fn read_frame(&mut self, count_line: &str) -> Result<Mol, ParseError> {
+        let number = self.lines.number;
+        let count: usize = count_line
+            .trim()
+            .parse()
+            .map_err(|_| ParseError::BadCountsLine {
+                line: number,
+                column: 1,
+                found: count_line.trim().to_string(),
+            })?;
+        let comment = self.lines.expect("frame")?.trim().to_string();
+        // The count comes straight from the file, so it doesn't size
+        // anything. A file shorter than it says fails when it runs out.
+        let mut atoms = Vec::new();
+        for _ in 0..count {
+            let line = self.lines.expect("frame")?;
+            atoms.push(parse_atom(line, self.lines.number)?);
+        }
+        let mut bonds = perception::perceive_bonds(&atoms);
+        perception::assign_bond_orders(&atoms, &mut bonds);
+        Ok(Mol {
+            comment,
+            ..Mol::new(atoms, bonds)
+        })
+    }
+}
+
+impl<'a> Iterator for XyzFrames<'a> {
+    type Item = Result<Mol, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        // Some writers leave blank lines between frames
+        let count_line = loop {
+            let line = self.lines.next()?;
+            if !line.trim().is_empty() {
+                break line;
+            }
+        };
+        let frame = self.read_frame(count_line);
+        self.failed = frame.is_err();
+        Some(frame)
+    }
+}
+
+/// Splits a line on whitespace, keeping each field's 1-based column.
+fn fields(line: &str) -> Vec<(usize, &str)> {
+    let mut fields = Vec::new();
+    let mut start = None;
+    for (offset, character) in line.char_indices().chain(Some((line.len(), ' '))) {
+        match (character.is_whitespace(), start) {
+            (true, Some(begin)) => {
+                fields.push((begin + 1, &line[begin..offset]));
+                start = None;
+            }
+            (false, None) => start = Some(offset),
+            _ => {}
+        }
+    }
+    fields
+}
+
+/// Reads an element given as a symbol, an atomic number, or a label such as
+/// `C12` that starts with the symbol.
+fn element_symbol(field: &str) -> String {
+    if let Some(data) = field.parse().ok().and_then(ElementData::from_atomic_number) {
+        return data.symbol.to_string();
+    }
+    let symbol = capitalize_symbol(field);
+    if Element::is_known(&symbol) {
+        return symbol;
+    }
+    capitalize_symbol(field.trim_end_matches(|character: char| !character.is_ascii_alphabetic()))
+}
+
+fn parse_atom(line: &str, number: usize) -> Result<Atom, ParseError> {
+    let fields = fields(line);
+    let field = |index: usize| fields.get(index).copied().unwrap_or((line.len() + 1, ""));
+    let coordinate = |index: usize, expected: &'static str| -> Result<f32, ParseError> {
+        let (column, text) = field(index);
+        f32::from_str(text).map_err(|_| ParseError::InvalidField {
+            line: number,
+            column,
+            expected,
+            found: text.to_string(),
+        })
+    };
+    let position = Vec3::new(
+        coordinate(1, "an x coordinate")?,
+        coordinate(2, "a y coordinate")?,
+        coordinate(3, "a z coordinate")?,
+    );
+    let (column, text) = field(0);
+    let symbol = element_symbol(text);
+    if !Element::is_known(&symbol) && symbol != "D" {
+        return Err(ParseError::UnknownElement {
+            line: number,
+            column,
+            symbol: text.to_string(),
+        });
+    }
+    Ok(Atom::new(&symbol, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two frames, water and hydrogen cyanide, with a blank line between
+    /// them and elements written several ways
+    const FRAMES: &str = "\
+3
+water
+O   0.000  0.000  0.117
+H   0.000  0.757 -0.467
+1   0.000 -0.757 -0.467
+
+3
+hydrogen cyanide
+H   0.000  0.000 -1.066
+c   0.000  0.000  0.000
+N1  0.000  0.000  1.156
+";
+
+    #[test]
+    fn reads_every_frame() {
+        let frames = XyzFrames::new(FRAMES)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].comment, "water");
+        assert_eq!(frames[1].comment, "hydrogen cyanide");
+        assert_eq!(frames[1].atoms[2].position, Vec3::new(0.0, 0.0, 1.156));
+    }
+
+    #[test]
+    fn reads_symbols_numbers_and_labels() {
+        let frames = XyzFrames::new(FRAMES)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let symbols = frames
+            .iter()
+            .flat_map(|frame| frame.atoms.iter().map(|atom| atom.symbol.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(symbols, ["O", "H", "H", "H", "C", "N"]);
+    }
+
+    #[test]
+    fn perceives_bonds_and_orders() {
+        let frames = XyzFrames::new(FRAMES)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let orders = |mol: &Mol| {
+            mol.bonds
+                .iter()
+                .map(|bond| bond.bond_type)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(orders(&frames[0]), [1, 1]);
+        assert_eq!(orders(&frames[1]), [1, 3]);
+    }
+
+    #[test]
+    fn stops_after_an_error() {
+        let text = FRAMES.replace("0.757 -0.467", "0.757 zero");
+        let mut frames = XyzFrames::new(&text);
+        let error = frames.next().unwrap().err().expect("an error");
+        assert_eq!(
+            error,
+            ParseError::InvalidField {
+                line: 4,
+                column: 18,
+                expected: "a z coordinate",
+                found: "zero".to_string(),
+            }
+        );
+        assert!(frames.next().is_none());
+
+        let text = FRAMES.replace("c   0.000", "Xx  0.000");
+        let mut frames = XyzFrames::new(&text);
+        assert!(frames.next().unwrap().is_ok());
+        let error = frames.next().unwrap().err().expect("an error");
+        assert_eq!((error.line(), error.column()), (10, Some(1)));
+    }
+
+    #[test]
+    fn reports_counts_larger_than_the_file() {
+        let error = XyzFrames::new("9999999999999\nhuge\nO 0 0 0\n")
+            .next()
+            .unwrap()
+            .err()
+            .expect("an error");
+        assert_eq!(
+            error,
+            ParseError::TruncatedBlock {
+                line: 4,
+                block: "frame",
+            }
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Picking: finding the atom or bond under the pointer by casting a ray from
+//! the screen through the shapes last drawn, on the CPU.
+use crate::renderer::View;
+use crate::representation::Shapes;
+use glam::{Vec3, Vec4};
+
+/// Lines are a pixel wide, so they are picked as if they were this thick.
+const LINE_PICK_RADIUS: f32 = 0.5;
+
+pub struct Ray {
+    pub origin: Vec3,
+    /// Normalized
+    pub direction: Vec3,
+}
+
+/// What a ray hit first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pick {
+    /// The atom hit, or for a bond the atom whose half was hit
+    pub atom: usize,
+    pub bond: Option<usize>,
+    /// Along the ray, in world units
+    pub distance: f32,
+}
+
+impl Ray {
+    /// The ray through a point on screen, given in normalized device
+    /// coordinates from -1 to 1 with +y up, undoing the transform the
+    /// molecule was drawn with.
+    pub fn from_screen(view: &View, x: f32, y: f32) -> Self {
+        let inverse = (view.projection * view.view).inverse();
+        let unproject = |depth: f32| {
+            let point = inverse * Vec4::new(x, y, depth, 1.0);
+            point.truncate() / point.w()
+        };
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+        Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    }
+
+    /// Distance to the nearer side of a sphere, if the ray meets it. A ray
+    /// starting inside the sphere, as a camera zoomed into an atom's does,
+    /// meets its far side.
+    pub fn sphere(&self, center: Vec3, radius: f32) -> Option<f32> {
+        let offset = self.origin - center;
+        let b = offset.dot(self.direction);
+        let discriminant = b * b - offset.dot(offset) + radius * radius;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let (near, far) = (-b - discriminant.sqrt(), -b + discriminant.sqrt());
+        if near >= 0.0 {
+            Some(near)
+        } else if far >= 0.0 {
+            Some(far)
+        } else {
+            None
+        }
+    }
+
+    /// Distance to the side of an open cylinder, if the ray meets it between
+    /// the two ends. This is the same test the impostor shader makes.
+    pub fn cylinder(&self, start: Vec3, end: Vec3, radius: f32) -> Option<f32> {
+        let length = (end - start).length();
+        if length <= 0.0 {
+            return None;
+        }
+        let unit = (end - start) / length;
+        let offset = self.origin - start;
+        let d = self.direction - unit * self.direction.dot(unit);
+        let o = offset - unit * offset.dot(unit);
+        let a = d.dot(d);
+        let b = d.dot(o);
+        let discriminant = b * b - a * (o.dot(o) - radius * radius);
+        if a < 0.000_001 || discriminant < 0.0 {
+            return None;
+        }
+        let distance = (-b - discriminant.sqrt()) / a;
+        let height = (self.origin + self.direction * distance - start).dot(unit);
+        if distance >= 0.0 && height >= 0.0 && height <= length {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
+
+/// Finds the nearest atom or bond along `ray`.
+pub fn pick(shapes: &Shapes, ray: &Ray) -> Option<Pick> {
+    let spheres = shapes.spheres.iter().filter_map(|sphere| {
+        ray.sphere(sphere.center, sphere.radius)
+            .map(|distance| Pick {
+                atom: sphere.atom,
+                bond: None,
+                distance,
+            })
+    });
+    let cylinders = shapes.cylinders.iter().filter_map(|cylinder| {
+        let hit = ray.cylinder(cylinder.start, cylinder.end, cylinder.radius);
+        hit.map(|distance| Pick {
+            atom: cylinder.atom,
+            bond: Some(cylinder.bond),
+            distance,
+        })
+    });
+    let lines = shapes.lines.iter().filter_map(|line| {
+        let hit = ray.cylinder(line.start, line.end, LINE_PICK_RADIUS);
+        hit.map(|distance| Pick {
+            atom: line.atom,
+            bond: line.bond,
+            distance,
+        })
+    });
+    spheres
+        .chain(cylinders)
+        .chain(lines)
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::representation::Sphere;
+
+    fn sphere(x: f32, atom: usize) -> Sphere {
+        Sphere {
+            center: Vec3::new(x, 0.0, 0.0),
+            radius: 1.0,
+            color: Vec3::one(),
+            atom,
+        }
+    }
+
+    #[test]
+    fn picks_the_nearest_hit() {
+        let shapes = Shapes {
+            spheres: vec![sphere(10.0, 0), sphere(f32::NAN, 1), sphere(5.0, 2)],
+            ..Shapes::default()
+        };
+        let ray = Ray {
+            origin: Vec3::zero(),
+            direction: Vec3::unit_x(),
+        };
+        let picked = pick(&shapes, &ray).expect("a hit");
+        assert_eq!((picked.atom, picked.bond), (2, None));
+        assert!((picked.distance - 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn picks_spheres_around_the_camera() {
+        let shapes = Shapes {
+            spheres: vec![sphere(0.5, 0), sphere(-5.0, 1)],
+            ..Shapes::default()
+        };
+        let ray = Ray {
+            origin: Vec3::zero(),
+            direction: Vec3::unit_x(),
+        };
+        let picked = pick(&shapes, &ray).expect("a hit");
+        assert_eq!(picked.atom, 0);
+        assert!((picked.distance - 1.5).abs() < 1e-5);
+        // Spheres wholly behind the ray aren't hit
+        assert_eq!(ray.sphere(Vec3::new(-5.0, 0.0, 0.0), 1.0), None);
+    }
+}
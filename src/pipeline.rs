@@ -0,0 +1,467 @@
+//! Shader programs and the GL objects they draw with, which make up the GPU
+//! `Renderer`. Atoms and bonds are drawn either as instanced sphere and
+//! cylinder meshes, or as impostors: quads facing the viewer whose fragment
+//! shaders ray-cast a perfect sphere or cylinder and write its depth, so they
+//! stay smooth however close the camera gets. Wireframes are drawn as
+//! instanced lines in either case.
+//!
+//! Highlighted atoms and bonds get a halo: a second, slightly larger impostor
+//! drawn translucent over the top, whatever the geometry.
+use crate::assets::{
+    bind_instance_attributes, init_buffers_from_constants, init_quad_buffers, init_segment_buffers,
+    upload_floats, InstanceBuffers,
+};
+use crate::constants::{MESHES_SIZE, SPHERE_SIZE};
+use crate::instances::{Instances, CYLINDER_LAYOUT, LINE_LAYOUT, MESH_LAYOUT, SPHERE_LAYOUT};
+use crate::renderer::{Renderer, View};
+use glam::Vec3;
+use glow::HasContext;
+
+const MESH_VERTEX: &str = r#"layout(location = 0) in vec3 vert_in;
+layout(location = 1) in vec3 norm_in;
+layout(location = 2) in mat4 model;
+layout(location = 6) in vec3 color_in;
+out vec3 norm_out;
+out vec3 vert_out;
+out vec3 color_out;
+uniform mat4 transform;
+void main() {
+    vert_out = -normalize (vert_in);
+    norm_out = norm_in;
+    color_out = color_in;
+    gl_Position = transform * model * vec4(vert_in, 1.0);
+}"#;
+
+const MESH_FRAGMENT: &str = r#"precision mediump float;
+uniform bool u_light;
+uniform vec3 u_view;
+in vec3 norm_out;
+in vec3 vert_out;
+in vec3 color_out;
+out vec4 color;
+void main() {
+    if (u_light) {
+        color = vec4(color_out * 0.1 * vec3(dot(vert_out, normalize(reflect(u_view, norm_out)))) + color_out * 0.9, 1.0);
+    } else {
+        color = vec4(color_out, 1.0);
+    }
+}"#;
+
+// Impostor quads are lifted towards the viewer by the radius so the shape is
+// always behind them, and made larger under perspective, where a sphere's
+// outline grows past its radius.
+const SPHERE_VERTEX: &str = r#"layout(location = 0) in vec2 corner;
+layout(location = 1) in vec3 center_in;
+layout(location = 2) in float radius_in;
+layout(location = 3) in vec3 color_in;
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection;
+uniform bool u_orthographic;
+out vec3 position;
+flat out vec3 center;
+flat out float radius;
+flat out vec3 color_out;
+void main() {
+    center = (u_view_matrix * vec4(center_in, 1.0)).xyz;
+    radius = radius_in;
+    color_out = color_in;
+    float margin = u_orthographic ? 1.0 : 1.5;
+    position = center + vec3(corner * radius * margin, radius);
+    gl_Position = u_projection * vec4(position, 1.0);
+}"#;
+
+const SPHERE_FRAGMENT: &str = r#"precision highp float;
+uniform mat4 u_projection;
+uniform bool u_orthographic;
+uniform float u_alpha;
+in vec3 position;
+flat in vec3 center;
+flat in float radius;
+flat in vec3 color_out;
+out vec4 color;
+void main() {
+    vec3 direction = u_orthographic ? vec3(0.0, 0.0, -1.0) : normalize(position);
+    vec3 offset = position - center;
+    float b = dot(offset, direction);
+    float discriminant = b * b - dot(offset, offset) + radius * radius;
+    if (discriminant < 0.0) {
+        discard;
+    }
+    vec3 hit = position + direction * (-b - sqrt(discriminant));
+    vec3 normal = (hit - center) / radius;
+    vec4 clip = u_projection * vec4(hit, 1.0);
+    gl_FragDepth = clip.z / clip.w * 0.5 + 0.5;
+    color = vec4(color_out * (0.9 + 0.1 * normal.z), u_alpha);
+}"#;
+
+const CYLINDER_VERTEX: &str = r#"layout(location = 0) in vec2 corner;
+layout(location = 1) in vec3 start_in;
+layout(location = 2) in vec3 end_in;
+layout(location = 3) in float radius_in;
+layout(location = 4) in vec3 color_in;
+uniform mat4 u_view_matrix;
+uniform mat4 u_projection;
+uniform bool u_orthographic;
+out vec3 position;
+flat out vec3 start;
+flat out vec3 end;
+flat out float radius;
+flat out vec3 color_out;
+void main() {
+    start = (u_view_matrix * vec4(start_in, 1.0)).xyz;
+    end = (u_view_matrix * vec4(end_in, 1.0)).xyz;
+    radius = radius_in;
+    color_out = color_in;
+    vec3 toward = u_orthographic ? vec3(0.0, 0.0, 1.0) : normalize(-(start + end) * 0.5);
+    vec3 axis = end - start;
+    vec3 side = cross(axis, toward);
+    if (length(side) < 0.0001) {
+        // Seen end on, any direction across the screen will do
+        side = cross(abs(toward.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0), toward);
+    }
+    side = normalize(side);
+    vec3 along = normalize(cross(toward, side));
+    if (dot(along, axis) < 0.0) {
+        along = -along;
+    }
+    float margin = u_orthographic ? 1.0 : 1.5;
+    vec3 base = corner.y < 0.0 ? start - along * radius : end + along * radius;
+    position = base + side * corner.x * radius * margin + toward * radius;
+    gl_Position = u_projection * vec4(position, 1.0);
+}"#;
+
+// Cylinders are left open, since their ends are always inside atoms
+const CYLINDER_FRAGMENT: &str = r#"precision highp float;
+uniform mat4 u_projection;
+uniform bool u_orthographic;
+uniform float u_alpha;
+in vec3 position;
+flat in vec3 start;
+flat in vec3 end;
+flat in float radius;
+flat in vec3 color_out;
+out vec4 color;
+void main() {
+    vec3 direction = u_orthographic ? vec3(0.0, 0.0, -1.0) : normalize(position);
+    float len = length(end - start);
+    vec3 unit = (end - start) / len;
+    vec3 offset = position - start;
+    vec3 d = direction - dot(direction, unit) * unit;
+    vec3 o = offset - dot(offset, unit) * unit;
+    float a = dot(d, d);
+    float b = dot(d, o);
+    float discriminant = b * b - a * (dot(o, o) - radius * radius);
+    if (a < 0.000001 || discriminant < 0.0) {
+        discard;
+    }
+    vec3 hit = position + direction * ((-b - sqrt(discriminant)) / a);
+    float height = dot(hit - start, unit);
+    if (height < 0.0 || height > len) {
+        discard;
+    }
+    vec4 clip = u_projection * vec4(hit, 1.0);
+    gl_FragDepth = clip.z / clip.w * 0.5 + 0.5;
+    color = vec4(color_out, u_alpha);
+}"#;
+
+const LINE_VERTEX: &str = r#"layout(location = 0) in float along;
+layout(location = 1) in vec3 start;
+layout(location = 2) in vec3 end;
+layout(location = 3) in vec3 color_in;
+uniform mat4 transform;
+out vec3 color_out;
+void main() {
+    color_out = color_in;
+    gl_Position = transform * vec4(mix(start, end, along), 1.0);
+}"#;
+
+const LINE_FRAGMENT: &str = r#"precision mediump float;
+in vec3 color_out;
+out vec4 color;
+void main() {
+    color = vec4(color_out, 1.0);
+}"#;
+
+pub struct MeshProgram<B: HasContext> {
+    pub program: <B as HasContext>::Program,
+    transform: Option<<B as HasContext>::UniformLocation>,
+    light: Option<<B as HasContext>::UniformLocation>,
+    view: Option<<B as HasContext>::UniformLocation>,
+}
+
+pub struct ImpostorProgram<B: HasContext> {
+    pub program: <B as HasContext>::Program,
+    view_matrix: Option<<B as HasContext>::UniformLocation>,
+    projection: Option<<B as HasContext>::UniformLocation>,
+    orthographic: Option<<B as HasContext>::UniformLocation>,
+    alpha: Option<<B as HasContext>::UniformLocation>,
+}
+
+pub struct LineProgram<B: HasContext> {
+    pub program: <B as HasContext>::Program,
+    transform: Option<<B as HasContext>::UniformLocation>,
+}
+
+pub struct Pipeline<B: HasContext> {
+    mesh: MeshProgram<B>,
+    spheres: ImpostorProgram<B>,
+    cylinders: ImpostorProgram<B>,
+    lines: LineProgram<B>,
+    mesh_vertex_array: <B as HasContext>::VertexArray,
+    // Spheres and cylinders point different attributes at their instances,
+    // so each gets its own quad
+    sphere_vertex_array: <B as HasContext>::VertexArray,
+    cylinder_vertex_array: <B as HasContext>::VertexArray,
+    line_vertex_array: <B as HasContext>::VertexArray,
+    instances: InstanceBuffers<B>,
+    halos: InstanceBuffers<B>,
+}
+
+/// Compiles and links a program, returning the info log on failure.
+fn compile_program<B: HasContext>(
+    gl: &B,
+    shader_version: &str,
+    vertex: &str,
+    fragment: &str,
+) -> Result<<B as HasContext>::Program, String> {
+    unsafe {
+        let program = gl.create_program()?;
+        let mut shaders = Vec::with_capacity(2);
+        for &(shader_type, source) in [
+            (glow::VERTEX_SHADER, vertex),
+            (glow::FRAGMENT_SHADER, fragment),
+        ]
+        .iter()
+        {
+            let shader = gl.create_shader(shader_type)?;
+            gl.shader_source(shader, &format!("{}\n{}", shader_version, source));
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                return Err(gl.get_shader_info_log(shader));
+            }
+            gl.attach_shader(program, shader);
+            shaders.push(shader);
+        }
+        gl.link_program(program);
+        if !gl.get_program_link_status(program) {
+            return Err(gl.get_program_info_log(program));
+        }
+        for shader in shaders {
+            gl.detach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+        Ok(program)
+    }
+}
+
+impl<B: HasContext> ImpostorProgram<B> {
+    fn new(gl: &B, shader_version: &str, vertex: &str, fragment: &str) -> Result<Self, String> {
+        let program = compile_program(gl, shader_version, vertex, fragment)?;
+        unsafe {
+            Ok(ImpostorProgram {
+                program,
+                view_matrix: gl.get_uniform_location(program, "u_view_matrix"),
+                projection: gl.get_uniform_location(program, "u_projection"),
+                orthographic: gl.get_uniform_location(program, "u_orthographic"),
+                alpha: gl.get_uniform_location(program, "u_alpha"),
+            })
+        }
+    }
+
+    unsafe fn bind(&self, gl: &B, view: &View, alpha: f32) {
+        gl.use_program(Some(self.program));
+        gl.uniform_matrix_4_f32_slice(self.view_matrix.clone(), false, view.view.as_ref());
+        gl.uniform_matrix_4_f32_slice(self.projection.clone(), false, view.projection.as_ref());
+        gl.uniform_1_i32(self.orthographic.clone(), view.orthographic as i32);
+        gl.uniform_1_f32(self.alpha.clone(), alpha);
+    }
+}
+
+impl<B: HasContext> Pipeline<B> {
+    pub fn new(gl: &B, shader_version: &str) -> Result<Self, String> {
+        let program = compile_program(gl, shader_version, MESH_VERTEX, MESH_FRAGMENT)?;
+        let mesh = unsafe {
+            MeshProgram {
+                program,
+                transform: gl.get_uniform_location(program, "transform"),
+                light: gl.get_uniform_location(program, "u_light"),
+                view: gl.get_uniform_location(program, "u_view"),
+            }
+        };
+        let program = compile_program(gl, shader_version, LINE_VERTEX, LINE_FRAGMENT)?;
+        let lines = unsafe {
+            LineProgram {
+                program,
+                transform: gl.get_uniform_location(program, "transform"),
+            }
+        };
+        Ok(Pipeline {
+            mesh,
+            lines,
+            spheres: ImpostorProgram::new(gl, shader_version, SPHERE_VERTEX, SPHERE_FRAGMENT)?,
+            cylinders: ImpostorProgram::new(
+                gl,
+                shader_version,
+                CYLINDER_VERTEX,
+                CYLINDER_FRAGMENT,
+            )?,
+            mesh_vertex_array: init_buffers_from_constants(gl),
+            sphere_vertex_array: init_quad_buffers(gl),
+            cylinder_vertex_array: init_quad_buffers(gl),
+            line_vertex_array: init_segment_buffers(gl),
+            instances: InstanceBuffers::new(gl),
+            halos: InstanceBuffers::new(gl),
+        })
+    }
+
+    /// Draws with this pipeline on `gl`, which it must have been made with.
+    pub fn renderer<'a>(&'a self, gl: &'a B) -> GlRenderer<'a, B> {
+        GlRenderer { pipeline: self, gl }
+    }
+
+    unsafe fn draw_impostor_buffers(
+        &self,
+        gl: &B,
+        view: &View,
+        buffers: &InstanceBuffers<B>,
+        (atom_count, bond_count): (i32, i32),
+        alpha: f32,
+    ) {
+        // Quads can face either way depending on the shape's orientation
+        gl.disable(glow::CULL_FACE);
+
+        gl.bind_vertex_array(Some(self.sphere_vertex_array));
+        self.spheres.bind(gl, view, alpha);
+        bind_instance_attributes(gl, buffers.atoms, SPHERE_LAYOUT);
+        gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, atom_count);
+
+        gl.bind_vertex_array(Some(self.cylinder_vertex_array));
+        self.cylinders.bind(gl, view, alpha);
+        bind_instance_attributes(gl, buffers.bonds, CYLINDER_LAYOUT);
+        gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, bond_count);
+
+        gl.enable(glow::CULL_FACE);
+    }
+
+    pub fn delete(&self, gl: &B) {
+        unsafe {
+            gl.delete_program(self.mesh.program);
+            gl.delete_program(self.spheres.program);
+            gl.delete_program(self.cylinders.program);
+            gl.delete_program(self.lines.program);
+            gl.delete_vertex_array(self.mesh_vertex_array);
+            gl.delete_vertex_array(self.sphere_vertex_array);
+            gl.delete_vertex_array(self.cylinder_vertex_array);
+            gl.delete_vertex_array(self.line_vertex_array);
+        }
+        self.instances.delete(gl);
+        self.halos.delete(gl);
+    }
+}
+
+/// A pipeline and the context it draws on.
+pub struct GlRenderer<'a, B: HasContext> {
+    pipeline: &'a Pipeline<B>,
+    gl: &'a B,
+}
+
+impl<B: HasContext> Renderer for GlRenderer<'_, B> {
+    fn upload(&mut self, instances: &Instances) {
+        let (buffers, gl) = (&self.pipeline.instances, self.gl);
+        upload_floats(gl, buffers.atoms, &instances.atoms);
+        upload_floats(gl, buffers.bonds, &instances.bonds);
+        upload_floats(gl, buffers.lines, &instances.lines);
+    }
+
+    fn upload_halos(&mut self, halos: &Instances) {
+        let (buffers, gl) = (&self.pipeline.halos, self.gl);
+        upload_floats(gl, buffers.atoms, &halos.atoms);
+        upload_floats(gl, buffers.bonds, &halos.bonds);
+    }
+
+    fn draw_meshes(&mut self, view: &View, atom_count: i32, bond_count: i32) {
+        let (pipeline, gl) = (self.pipeline, self.gl);
+        unsafe {
+            gl.use_program(Some(pipeline.mesh.program));
+            gl.bind_vertex_array(Some(pipeline.mesh_vertex_array));
+            let view_vector = view.view.transpose().transform_vector3(Vec3::unit_z());
+            gl.uniform_3_f32(
+                pipeline.mesh.view.clone(),
+                view_vector.x(),
+                view_vector.y(),
+                view_vector.z(),
+            );
+            gl.uniform_matrix_4_f32_slice(
+                pipeline.mesh.transform.clone(),
+                false,
+                (view.projection * view.view).as_ref(),
+            );
+
+            gl.uniform_1_i32(pipeline.mesh.light.clone(), glow::TRUE as i32);
+            bind_instance_attributes(gl, pipeline.instances.atoms, MESH_LAYOUT);
+            gl.draw_elements_instanced(
+                glow::TRIANGLES,
+                SPHERE_SIZE as i32,
+                glow::UNSIGNED_INT,
+                0,
+                atom_count,
+            );
+
+            gl.uniform_1_i32(pipeline.mesh.light.clone(), glow::FALSE as i32);
+            bind_instance_attributes(gl, pipeline.instances.bonds, MESH_LAYOUT);
+            gl.draw_elements_instanced(
+                glow::TRIANGLES,
+                (MESHES_SIZE - SPHERE_SIZE) as i32,
+                glow::UNSIGNED_INT,
+                (SPHERE_SIZE * std::mem::size_of::<u32>()) as i32,
+                bond_count,
+            );
+        }
+    }
+
+    fn draw_impostors(&mut self, view: &View, atom_count: i32, bond_count: i32) {
+        let (pipeline, gl) = (self.pipeline, self.gl);
+        unsafe {
+            pipeline.draw_impostor_buffers(
+                gl,
+                view,
+                &pipeline.instances,
+                (atom_count, bond_count),
+                1.0,
+            );
+        }
+    }
+
+    fn draw_lines(&mut self, view: &View, line_count: i32) {
+        if line_count == 0 {
+            return;
+        }
+        let (pipeline, gl) = (self.pipeline, self.gl);
+        unsafe {
+            gl.use_program(Some(pipeline.lines.program));
+            gl.bind_vertex_array(Some(pipeline.line_vertex_array));
+            gl.uniform_matrix_4_f32_slice(
+                pipeline.lines.transform.clone(),
+                false,
+                (view.projection * view.view).as_ref(),
+            );
+            bind_instance_attributes(gl, pipeline.instances.lines, LINE_LAYOUT);
+            gl.draw_arrays_instanced(glow::LINES, 0, 2, line_count);
+        }
+    }
+
+    fn draw_halos(&mut self, view: &View, counts: (i32, i32), alpha: f32) {
+        if counts == (0, 0) {
+            return;
+        }
+        let (pipeline, gl) = (self.pipeline, self.gl);
+        unsafe {
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.depth_mask(false);
+            pipeline.draw_impostor_buffers(gl, view, &pipeline.halos, counts, alpha);
+            gl.depth_mask(true);
+            gl.disable(glow::BLEND);
+        }
+    }
+}
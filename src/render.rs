@@ -0,0 +1,383 @@
+//! Renders molecule files to PNG without a window or a GPU, using the
+//! software rasterizer:
+//!
+//! ```shell
+//! colco-render --size 256x256 --rotate 0,90,0 --background transparent caffeine.sdf
+//! colco-render --batch --name-by REGISTRY_ID --output thumbnails compounds.sdf
+//! ```
+#![deny(clippy::all)]
+use ::colco::colco::{Colco, RenderSettings};
+use ::colco::color::ColorScheme;
+use ::colco::mol::{Mol, Sdf};
+use ::colco::renderer::software::Rasterizer;
+use glam::Vec3;
+use serde::de::value::Error as ValueError;
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+
+const USAGE: &str = "\
+Usage: colco-render [options] <file>
+
+Options:
+  -o, --output <path>          PNG to write, or the directory to write to with
+                               --batch. By default the input file with a .png
+                               extension, or without one for --batch.
+  -s, --size <width>x<height>  Image size in pixels, 512x512 by default
+  -r, --rotate <x>,<y>,<z>     Degrees to turn about the x, y and z axes, in
+                               that order
+  -b, --background <color>     #rrggbb, #rrggbbaa or transparent, white by
+                               default
+      --representation <name>  ball_and_stick, space_filling, licorice,
+                               sticks or wireframe
+      --geometry <name>        mesh or impostor
+      --projection <name>      orthographic or perspective
+      --color-scheme <name>    element, cpk, jmol, rasmol, chain,
+                               residue_type or partial_charge
+      --batch                  Render every record of an SD file, to
+                               <number>.png counting from 1
+      --name-by <tag>          With --batch, name each image after the
+                               record's data item with this tag instead
+  -h, --help                   Show this message";
+
+/// Everything one run renders with, from the command line.
+struct Options {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    size: (u32, u32),
+    /// Degrees about the x, y and z axes
+    rotation: Vec3,
+    background: [f32; 4],
+    color_scheme: ColorScheme,
+    render_settings: RenderSettings,
+    batch: bool,
+    name_by: Option<String>,
+}
+
+/// Reads the value of `flag`, a setting named as JavaScript names it, such
+/// as `ball_and_stick`.
+fn named<'de, T: Deserialize<'de>>(flag: &str, name: &'de str) -> Result<T, String> {
+    T::deserialize(name.into_deserializer())
+        .map_err(|error: ValueError| format!("{}: {}", flag, error))
+}
+
+fn parse_size(text: &str) -> Result<(u32, u32), String> {
+    let error = || format!("Size {} should be <width>x<height>, such as 512x512", text);
+    let mut sides = text.splitn(2, 'x').map(|side| side.parse::<u32>().ok());
+    match (sides.next().flatten(), sides.next().flatten()) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(error()),
+    }
+}
+
+fn parse_rotation(text: &str) -> Result<Vec3, String> {
+    let angles = text
+        .split(',')
+        .map(|angle| angle.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>();
+    match angles.as_deref() {
+        Ok(&[x, y, z]) => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!(
+            "Rotation {} should be three angles in degrees, such as 0,90,0",
+            text
+        )),
+    }
+}
+
+fn parse_color(text: &str) -> Result<[f32; 4], String> {
+    if text == "transparent" {
+        return Ok([0.0; 4]);
+    }
+    let digits = text.trim_start_matches('#');
+    let hex = u32::from_str_radix(digits, 16).ok();
+    match (digits.len(), hex) {
+        (6, Some(hex)) => Ok(channels((hex << 8) | 0xFF)),
+        (8, Some(hex)) => Ok(channels(hex)),
+        _ => Err(format!(
+            "Color {} should be #rrggbb, #rrggbbaa or transparent",
+            text
+        )),
+    }
+}
+
+/// Splits `0xRRGGBBAA` into channels from 0 to 1.
+fn channels(rgba: u32) -> [f32; 4] {
+    let channel = |shift: u32| ((rgba >> shift) & 0xFF) as f32 / 255.0;
+    [channel(24), channel(16), channel(8), channel(0)]
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = OsString>) -> Result<Self, String> {
+        let mut options = Options {
+            input: PathBuf::new(),
+            output: None,
+            size: (512, 512),
+            rotation: Vec3::zero(),
+            background: [1.0; 4],
+            color_scheme: ColorScheme::default(),
+            render_settings: RenderSettings::default(),
+            batch: false,
+            name_by: None,
+        };
+        let mut input = None;
+        while let Some(arg) = args.next() {
+            let flag = match arg.to_str() {
+                Some(flag) if flag.starts_with('-') && flag.len() > 1 => flag.to_string(),
+                _ if input.is_none() => {
+                    input = Some(PathBuf::from(arg));
+                    continue;
+                }
+                _ => return Err("Only one file can be rendered at a time".to_string()),
+            };
+            match flag.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                "--batch" => {
+                    options.batch = true;
+                    continue;
+                }
+                _ => {}
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{} needs a value, see --help", flag))?;
+            if let "-o" | "--output" = flag.as_str() {
+                options.output = Some(PathBuf::from(value));
+                continue;
+            }
+            let value = value
+                .into_string()
+                .map_err(|value| format!("{} isn't valid for {}", value.to_string_lossy(), flag))?;
+            let settings = &mut options.render_settings;
+            match flag.as_str() {
+                "-s" | "--size" => options.size = parse_size(&value)?,
+                "-r" | "--rotate" => options.rotation = parse_rotation(&value)?,
+                "-b" | "--background" => options.background = parse_color(&value)?,
+                "--representation" => settings.representation = named(&flag, &value)?,
+                "--geometry" => settings.geometry = named(&flag, &value)?,
+                "--projection" => settings.projection = named(&flag, &value)?,
+                "--color-scheme" => options.color_scheme = named(&flag, &value)?,
+                "--name-by" => options.name_by = Some(value),
+                _ => return Err(format!("Unknown option {}, see --help", flag)),
+            }
+        }
+        options.input = input.ok_or_else(|| format!("No file to render\n\n{}", USAGE))?;
+        if options.name_by.is_some() && !options.batch {
+            return Err("--name-by only names images written with --batch".to_string());
+        }
+        Ok(options)
+    }
+
+    /// Draws `mol` as these options say, returning the image as 8 bit RGBA,
+    /// top row first.
+    fn render(&self, mol: Mol) -> Vec<u8> {
+        let (width, height) = self.size;
+        let mut colco = Colco::new(mol, &self.color_scheme);
+        colco.camera.aspect = width as f32 / height as f32;
+        let turns = [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()];
+        for (axis, degrees) in turns.iter().zip(self.rotation.as_ref()) {
+            colco.turn(*axis * degrees.to_radians());
+        }
+        let mut rasterizer = Rasterizer::new(width, height);
+        rasterizer.clear(self.background);
+        colco.render_mol(&self.render_settings, &mut rasterizer, 0.0);
+        rasterizer.rgba()
+    }
+
+    fn write(&self, path: &Path, rgba: &[u8]) -> Result<(), String> {
+        let file = File::create(path).map_err(|error| error.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.size.0, self.size.1);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(rgba))
+            .map_err(|error| error.to_string())
+    }
+
+    /// Renders the molecule in the input file, which is the first record of
+    /// an SD file.
+    fn run_single(&self, text: &str) -> Result<(), String> {
+        let mol =
+            Mol::from_str(text).map_err(|error| format!("{}: {}", self.input.display(), error))?;
+        let output = match &self.output {
+            Some(output) => output.clone(),
+            None => self.input.with_extension("png"),
+        };
+        self.write(&output, &self.render(mol))
+            .map_err(|error| format!("{}: {}", output.display(), error))
+    }
+
+    /// Renders every record of an SD file into one directory. A record that
+    /// can't be rendered is reported and skipped, and the rest carry on.
+    fn run_batch(&self, text: String) -> Result<(), String> {
+        let directory = match &self.output {
+            Some(output) => output.clone(),
+            None => self.input.with_extension(""),
+        };
+        fs::create_dir_all(&directory)
+            .map_err(|error| format!("{}: {}", directory.display(), error))?;
+        let sdf = Sdf::new(text);
+        let mut failed = 0;
+        for index in 0..sdf.len() {
+            let result = self.file_name(&sdf, index).and_then(|name| {
+                let mol = sdf
+                    .molecule(index)
+                    .unwrap()
+                    .map_err(|error| error.to_string())?;
+                let path = directory.join(name);
+                self.write(&path, &self.render(mol))
+                    .map_err(|error| format!("{}: {}", path.display(), error))
+            });
+            if let Err(error) = result {
+                eprintln!("Record {}: {}", index + 1, error);
+                failed += 1;
+            }
+        }
+        match failed {
+            0 => Ok(()),
+            _ => Err(format!("{} of {} records failed", failed, sdf.len())),
+        }
+    }
+
+    /// What record `index`'s image is called: its number counting from 1,
+    /// or its `name_by` data item.
+    fn file_name(&self, sdf: &Sdf, index: usize) -> Result<String, String> {
+        let tag = match &self.name_by {
+            Some(tag) => tag,
+            None => return Ok(format!("{}.png", index + 1)),
+        };
+        let properties = sdf.properties(index).unwrap_or_default();
+        let value = properties
+            .iter()
+            .find(|(name, _)| name == tag)
+            .map(|(_, value)| value.trim())
+            .ok_or_else(|| format!("No {} data item to name the image by", tag))?;
+        // The value is only ever a file name, never a path
+        if value.is_empty() || value.contains(&['/', '\\', '\n'][..]) {
+            return Err(format!("{} {:?} can't name a file", tag, value));
+        }
+        Ok(format!("{}.png", value))
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let text = fs::read_to_string(&self.input)
+            .map_err(|error| format!("{}: {}", self.input.display(), error))?;
+        if self.batch {
+            self.run_batch(text)
+        } else {
+            self.run_single(&text)
+        }
+    }
+}
+
+fn main() {
+    let result = Options::parse(env::args_os().skip(1)).and_then(|options| options.run());
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::colco::representation::Representation;
+
+    /// A lone carbon
+    const METHANE: &str = "\
+Methane
+     RDKit          3D
+
+  1  0  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+M  END
+";
+
+    fn parse_args(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn reads_sizes() {
+        assert_eq!(parse_size("256x128"), Ok((256, 128)));
+        assert!(parse_size("0x10").is_err());
+        assert!(parse_size("10x").is_err());
+        assert!(parse_size("10").is_err());
+    }
+
+    #[test]
+    fn reads_rotations() {
+        assert_eq!(parse_rotation("0, 90,-45"), Ok(Vec3::new(0.0, 90.0, -45.0)));
+        assert!(parse_rotation("0,90").is_err());
+    }
+
+    #[test]
+    fn reads_colors() {
+        assert_eq!(parse_color("#ff0000"), Ok([1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(parse_color("#00ff0000"), Ok([0.0, 1.0, 0.0, 0.0]));
+        assert_eq!(parse_color("transparent"), Ok([0.0; 4]));
+        assert!(parse_color("#fff").is_err());
+        assert!(parse_color("#gg0000").is_err());
+    }
+
+    #[test]
+    fn reads_options() {
+        let options =
+            parse_args(&["-s", "64x32", "--representation", "licorice", "in.sdf"]).unwrap();
+        assert_eq!(options.size, (64, 32));
+        assert_eq!(options.input, PathBuf::from("in.sdf"));
+        let representation = options.render_settings.representation;
+        assert_eq!(representation, Representation::Licorice);
+
+        assert!(Options::parse(Vec::new().into_iter()).is_err());
+        assert!(parse_args(&["--size"]).is_err());
+        assert!(parse_args(&["--geometry", "cubes", "in.sdf"]).is_err());
+        assert!(parse_args(&["a.sdf", "b.sdf"]).is_err());
+    }
+
+    #[test]
+    fn names_images_only_in_batches() {
+        let error = parse_args(&["--name-by", "ID", "in.sdf"]).err();
+        assert_eq!(
+            error.as_deref(),
+            Some("--name-by only names images written with --batch")
+        );
+    }
+
+    #[test]
+    fn names_batch_images() {
+        let text = format!(
+            "{0}> <ID>\nCID 297\n\n$$$$\n{0}> <ID>\n../escape\n\n$$$$\n{0}$$$$\n",
+            METHANE
+        );
+        let sdf = Sdf::new(text);
+        let numbered = parse_args(&["--batch", "in.sdf"]).unwrap();
+        assert_eq!(numbered.file_name(&sdf, 0), Ok("1.png".to_string()));
+        assert_eq!(numbered.file_name(&sdf, 2), Ok("3.png".to_string()));
+
+        let named = parse_args(&["--batch", "--name-by", "ID", "in.sdf"]).unwrap();
+        assert_eq!(named.file_name(&sdf, 0), Ok("CID 297.png".to_string()));
+        // Values that are paths, or missing, can't name an image
+        assert!(named.file_name(&sdf, 1).is_err());
+        assert!(named.file_name(&sdf, 2).is_err());
+    }
+
+    #[test]
+    fn renders_over_the_background() {
+        let options = parse_args(&["--size", "16x16", "in.mol"]).unwrap();
+        let rgba = options.render(Mol::from_str(METHANE).unwrap());
+        assert_eq!(rgba.len(), 16 * 16 * 4);
+        assert_eq!(rgba[..4], [255; 4]);
+        let center = (8 * 16 + 8) * 4;
+        assert_ne!(rgba[center..center + 4], [255; 4]);
+    }
+}
@@ -0,0 +1,39 @@
+//! What the viewer draws with. `Pipeline` draws on the GPU through glow, and
+//! `software::Rasterizer` draws the same pictures on the CPU, for places
+//! without a GPU such as servers and CI.
+use crate::instances::Instances;
+use glam::Mat4;
+
+pub mod software;
+
+/// Where the molecule is seen from, shared by both ways of drawing it.
+pub struct View {
+    /// World to view space
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub orthographic: bool,
+}
+
+/// Uploads instances built by `Instances` and draws them. The view and any
+/// other uniforms come with each draw call.
+pub trait Renderer {
+    /// Replaces the atom, bond and line instances the draw calls draw from.
+    fn upload(&mut self, instances: &Instances);
+
+    /// Replaces the halos, which are `Instances::impostors`.
+    fn upload_halos(&mut self, halos: &Instances);
+
+    /// Draws uploaded `Instances::meshes`. Atoms are lit and bonds are flat.
+    fn draw_meshes(&mut self, view: &View, atom_count: i32, bond_count: i32);
+
+    /// Draws uploaded `Instances::impostors`.
+    fn draw_impostors(&mut self, view: &View, atom_count: i32, bond_count: i32);
+
+    /// Draws uploaded line instances, which either kind of geometry can have.
+    fn draw_lines(&mut self, view: &View, line_count: i32);
+
+    /// Draws uploaded halos with `alpha` opacity, after everything else.
+    /// Halos are depth tested but don't write depth, so they tint what is
+    /// under them without hiding each other.
+    fn draw_halos(&mut self, view: &View, counts: (i32, i32), alpha: f32);
+}
@@ -0,0 +1,728 @@
+//! A renderer that draws on the CPU into an RGBA image. Each of `pipeline`'s
+//! shaders has a counterpart here doing the same sums, and triangles go
+//! through what a GPU would do between them: clipping against the near
+//! plane, back face culling, perspective correct interpolation, depth
+//! testing and blending.
+use super::{Renderer, View};
+use crate::constants::{INDICES, MESH, SPHERE_SIZE};
+use crate::instances::{
+    instance_floats, Instances, CYLINDER_LAYOUT, LINE_LAYOUT, MESH_LAYOUT, SPHERE_LAYOUT,
+};
+use glam::{Mat4, Vec3, Vec4};
+
+/// Corners of the quad impostors are drawn on, as two triangles
+const QUAD: [(f32, f32); 6] = [
+    (-1.0, -1.0),
+    (1.0, -1.0),
+    (-1.0, 1.0),
+    (-1.0, 1.0),
+    (1.0, -1.0),
+    (1.0, 1.0),
+];
+
+/// A vertex as a vertex shader leaves it: a position in clip space, and two
+/// vectors for the fragment shader, interpolated across the triangle.
+#[derive(Clone, Copy)]
+struct Vertex {
+    clip: Vec4,
+    varyings: [Vec3; 2],
+}
+
+/// What a fragment shader makes of a pixel.
+struct Fragment {
+    color: Vec4,
+    /// Set by impostors, which write the depth of the shape they ray-cast
+    depth: Option<f32>,
+}
+
+/// How fragments are written, like GL's blending and depth mask
+#[derive(Clone, Copy)]
+struct Output {
+    blend: bool,
+    depth_write: bool,
+}
+
+const OPAQUE: Output = Output {
+    blend: false,
+    depth_write: true,
+};
+
+const TRANSLUCENT: Output = Output {
+    blend: true,
+    depth_write: false,
+};
+
+/// Instance data as uploaded, which GL would keep in buffers.
+#[derive(Default)]
+struct Buffers {
+    atoms: Vec<f32>,
+    bonds: Vec<f32>,
+    lines: Vec<f32>,
+}
+
+/// The color and depth buffers being drawn into, top row first.
+struct Target {
+    width: usize,
+    height: usize,
+    color: Vec<Vec4>,
+    depth: Vec<f32>,
+}
+
+pub struct Rasterizer {
+    target: Target,
+    /// Positions and normals of the sphere and then the cylinder mesh
+    mesh: Vec<f32>,
+    indices: Vec<u32>,
+    instances: Buffers,
+    halos: Buffers,
+}
+
+/// Splits the raw bytes in `constants`, which are kept ready to upload, into
+/// 4 byte words.
+fn words(bytes: &[u8]) -> impl Iterator<Item = [u8; 4]> + '_ {
+    bytes
+        .chunks_exact(4)
+        .map(|word| [word[0], word[1], word[2], word[3]])
+}
+
+fn vec3(floats: &[f32]) -> Vec3 {
+    Vec3::new(floats[0], floats[1], floats[2])
+}
+
+/// GLSL's `reflect`
+fn reflect(incident: Vec3, normal: Vec3) -> Vec3 {
+    incident - normal * (2.0 * normal.dot(incident))
+}
+
+/// GLSL's `mix`, for vertices
+fn mix(a: &Vertex, b: &Vertex, t: f32) -> Vertex {
+    Vertex {
+        clip: a.clip.lerp(b.clip, t),
+        varyings: [
+            a.varyings[0].lerp(b.varyings[0], t),
+            a.varyings[1].lerp(b.varyings[1], t),
+        ],
+    }
+}
+
+/// Window space depth of a point in view space, as written to `gl_FragDepth`.
+fn depth(projection: &Mat4, point: Vec3) -> f32 {
+    let clip = *projection * point.extend(1.0);
+    clip.z() / clip.w() * 0.5 + 0.5
+}
+
+/// Cuts away the part of a triangle in front of the near plane, leaving up
+/// to four vertices.
+fn clip_near(triangle: &[Vertex; 3]) -> ([Vertex; 4], usize) {
+    let mut clipped = [triangle[0]; 4];
+    let mut count = 0;
+    for (index, a) in triangle.iter().enumerate() {
+        let b = &triangle[(index + 1) % 3];
+        let (from_a, from_b) = (a.clip.z() + a.clip.w(), b.clip.z() + b.clip.w());
+        if from_a >= 0.0 {
+            clipped[count] = *a;
+            count += 1;
+        }
+        if (from_a >= 0.0) != (from_b >= 0.0) {
+            clipped[count] = mix(a, b, from_a / (from_a - from_b));
+            count += 1;
+        }
+    }
+    (clipped, count)
+}
+
+impl Target {
+    fn new(width: usize, height: usize) -> Self {
+        Target {
+            width,
+            height,
+            color: vec![Vec4::zero(); width * height],
+            depth: vec![1.0; width * height],
+        }
+    }
+
+    /// Where a point in normalized device coordinates lands, in pixels.
+    fn to_window(&self, ndc: Vec3) -> (f32, f32) {
+        (
+            (ndc.x() + 1.0) * 0.5 * self.width as f32,
+            (1.0 - ndc.y()) * 0.5 * self.height as f32,
+        )
+    }
+
+    /// Depth tests a fragment, then writes it. Colors and depths are clamped
+    /// as they are for GL's fixed point buffers.
+    fn write(&mut self, index: usize, color: Vec4, depth: f32, output: Output) {
+        let depth = depth.clamp(0.0, 1.0);
+        if depth >= self.depth[index] {
+            return;
+        }
+        if output.depth_write {
+            self.depth[index] = depth;
+        }
+        let color = color.max(Vec4::zero()).min(Vec4::one());
+        self.color[index] = if output.blend {
+            color * color.w() + self.color[index] * (1.0 - color.w())
+        } else {
+            color
+        };
+    }
+
+    /// Clips a triangle and fills what's left, culling it first if `cull` is
+    /// set and it faces away.
+    fn triangle<S>(&mut self, triangle: [Vertex; 3], cull: bool, output: Output, shade: &S)
+    where
+        S: Fn(&[Vec3; 2]) -> Option<Fragment>,
+    {
+        let (clipped, count) = clip_near(&triangle);
+        for index in 1..count.saturating_sub(1) {
+            let fan = [clipped[0], clipped[index], clipped[index + 1]];
+            self.fill(fan, cull, output, shade);
+        }
+    }
+
+    fn fill<S>(&mut self, triangle: [Vertex; 3], cull: bool, output: Output, shade: &S)
+    where
+        S: Fn(&[Vec3; 2]) -> Option<Fragment>,
+    {
+        let inverse_w = [
+            1.0 / triangle[0].clip.w(),
+            1.0 / triangle[1].clip.w(),
+            1.0 / triangle[2].clip.w(),
+        ];
+        let ndc = [
+            triangle[0].clip.truncate() * inverse_w[0],
+            triangle[1].clip.truncate() * inverse_w[1],
+            triangle[2].clip.truncate() * inverse_w[2],
+        ];
+        // Front faces wind counter-clockwise in normalized device
+        // coordinates, as they do in GL by default
+        let (ab, ac) = (ndc[1] - ndc[0], ndc[2] - ndc[0]);
+        let facing = ab.x() * ac.y() - ac.x() * ab.y();
+        if facing == 0.0 || (cull && facing < 0.0) {
+            return;
+        }
+        let window = [
+            self.to_window(ndc[0]),
+            self.to_window(ndc[1]),
+            self.to_window(ndc[2]),
+        ];
+        let depths = [
+            ndc[0].z() * 0.5 + 0.5,
+            ndc[1].z() * 0.5 + 0.5,
+            ndc[2].z() * 0.5 + 0.5,
+        ];
+        let edge = |(ax, ay): (f32, f32), (bx, by): (f32, f32), x: f32, y: f32| {
+            (bx - ax) * (y - ay) - (by - ay) * (x - ax)
+        };
+        let area = edge(window[0], window[1], window[2].0, window[2].1);
+
+        let xs = window.iter().map(|point| point.0);
+        let ys = window.iter().map(|point| point.1);
+        let left = xs.clone().fold(f32::INFINITY, f32::min).max(0.0) as usize;
+        let right = xs.fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as usize;
+        let top = ys.clone().fold(f32::INFINITY, f32::min).max(0.0) as usize;
+        let bottom = ys.fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as usize;
+        for y in top..bottom.min(self.height) {
+            for x in left..right.min(self.width) {
+                // Sampled at pixel centers
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let weights = [
+                    edge(window[1], window[2], px, py) / area,
+                    edge(window[2], window[0], px, py) / area,
+                    edge(window[0], window[1], px, py) / area,
+                ];
+                if weights.iter().any(|&weight| weight < 0.0) {
+                    continue;
+                }
+                let depth: f32 = (0..3).map(|corner| weights[corner] * depths[corner]).sum();
+                if depth > 1.0 {
+                    continue;
+                }
+                // Interpolated in clip space rather than on screen, so
+                // they don't bend under perspective
+                let perspective = [
+                    weights[0] * inverse_w[0],
+                    weights[1] * inverse_w[1],
+                    weights[2] * inverse_w[2],
+                ];
+                let total: f32 = perspective.iter().sum();
+                let varying = |slot: usize| {
+                    (0..3).fold(Vec3::zero(), |sum, corner| {
+                        sum + triangle[corner].varyings[slot] * perspective[corner]
+                    }) / total
+                };
+                if let Some(fragment) = shade(&[varying(0), varying(1)]) {
+                    let depth = fragment.depth.unwrap_or(depth);
+                    self.write(y * self.width + x, fragment.color, depth, output);
+                }
+            }
+        }
+    }
+
+    /// Draws a one pixel wide line between two points in clip space.
+    fn line(&mut self, start: Vec4, end: Vec4, color: Vec4) {
+        let (from_start, from_end) = (start.z() + start.w(), end.z() + end.w());
+        if from_start < 0.0 && from_end < 0.0 {
+            return;
+        }
+        // Shorten the line to where it crosses the near plane
+        let cut = |inside: Vec4, outside: Vec4, from_inside: f32, from_outside: f32| {
+            inside.lerp(outside, from_inside / (from_inside - from_outside))
+        };
+        let (start, end) = if from_start < 0.0 {
+            (cut(end, start, from_end, from_start), end)
+        } else if from_end < 0.0 {
+            (start, cut(start, end, from_start, from_end))
+        } else {
+            (start, end)
+        };
+        let (start, end) = (start.truncate() / start.w(), end.truncate() / end.w());
+        let (a, b) = (self.to_window(start), self.to_window(end));
+        let steps = (b.0 - a.0).abs().max((b.1 - a.1).abs()).ceil().max(1.0);
+        for step in 0..=steps as usize {
+            let t = step as f32 / steps;
+            let (x, y) = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            let depth = (start.z() + (end.z() - start.z()) * t) * 0.5 + 0.5;
+            if x < 0.0 || y < 0.0 || depth > 1.0 {
+                continue;
+            }
+            let (x, y) = (x as usize, y as usize);
+            if x < self.width && y < self.height {
+                self.write(y * self.width + x, color, depth, OPAQUE);
+            }
+        }
+    }
+}
+
+/// The sphere and cylinder impostor shaders.
+fn draw_impostor_buffers(
+    target: &mut Target,
+    view: &View,
+    buffers: &Buffers,
+    (atom_count, bond_count): (i32, i32),
+    alpha: f32,
+    output: Output,
+) {
+    let projection = view.projection;
+    let orthographic = view.orthographic;
+    let margin = if orthographic { 1.0 } else { 1.5 };
+    let direction = |position: Vec3| {
+        if orthographic {
+            -Vec3::unit_z()
+        } else {
+            position.normalize()
+        }
+    };
+    let in_view = |point: Vec3| (view.view * point.extend(1.0)).truncate();
+    let corner_vertex = |position: Vec3| Vertex {
+        clip: projection * position.extend(1.0),
+        varyings: [position, Vec3::zero()],
+    };
+
+    let spheres = buffers.atoms.chunks_exact(instance_floats(SPHERE_LAYOUT));
+    for sphere in spheres.take(atom_count.max(0) as usize) {
+        let (center, radius, color) = (in_view(vec3(sphere)), sphere[3], vec3(&sphere[4..]));
+        let corners = QUAD.map(|(x, y)| {
+            let lift = Vec3::new(x * radius * margin, y * radius * margin, radius);
+            corner_vertex(center + lift)
+        });
+        let shade = |varyings: &[Vec3; 2]| {
+            let position = varyings[0];
+            let direction = direction(position);
+            let offset = position - center;
+            let b = offset.dot(direction);
+            let discriminant = b * b - offset.dot(offset) + radius * radius;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let hit = position + direction * (-b - discriminant.sqrt());
+            let normal = (hit - center) / radius;
+            Some(Fragment {
+                color: (color * (0.9 + 0.1 * normal.z())).extend(alpha),
+                depth: Some(depth(&projection, hit)),
+            })
+        };
+        for triangle in corners.chunks_exact(3) {
+            let triangle = [triangle[0], triangle[1], triangle[2]];
+            target.triangle(triangle, false, output, &shade);
+        }
+    }
+
+    let cylinders = buffers.bonds.chunks_exact(instance_floats(CYLINDER_LAYOUT));
+    for cylinder in cylinders.take(bond_count.max(0) as usize) {
+        let (start, end) = (in_view(vec3(cylinder)), in_view(vec3(&cylinder[3..])));
+        let (radius, color) = (cylinder[6], vec3(&cylinder[7..]));
+        let toward = if orthographic {
+            Vec3::unit_z()
+        } else {
+            (-(start + end) * 0.5).normalize()
+        };
+        let axis = end - start;
+        let mut side = axis.cross(toward);
+        if side.length() < 0.0001 {
+            // Seen end on, any direction across the screen will do
+            let across = if toward.x().abs() < 0.9 {
+                Vec3::unit_x()
+            } else {
+                Vec3::unit_y()
+            };
+            side = across.cross(toward);
+        }
+        let side = side.normalize();
+        let mut along = toward.cross(side).normalize();
+        if along.dot(axis) < 0.0 {
+            along = -along;
+        }
+        let corners = QUAD.map(|(x, y)| {
+            let base = if y < 0.0 {
+                start - along * radius
+            } else {
+                end + along * radius
+            };
+            corner_vertex(base + side * (x * radius * margin) + toward * radius)
+        });
+        let length = axis.length();
+        let unit = axis / length;
+        let shade = |varyings: &[Vec3; 2]| {
+            let position = varyings[0];
+            let direction = direction(position);
+            let offset = position - start;
+            let d = direction - unit * direction.dot(unit);
+            let o = offset - unit * offset.dot(unit);
+            let a = d.dot(d);
+            let b = d.dot(o);
+            let discriminant = b * b - a * (o.dot(o) - radius * radius);
+            if a < 0.000_001 || discriminant < 0.0 {
+                return None;
+            }
+            let hit = position + direction * ((-b - discriminant.sqrt()) / a);
+            let height = (hit - start).dot(unit);
+            if height < 0.0 || height > length {
+                return None;
+            }
+            Some(Fragment {
+                color: color.extend(alpha),
+                depth: Some(depth(&projection, hit)),
+            })
+        };
+        for triangle in corners.chunks_exact(3) {
+            let triangle = [triangle[0], triangle[1], triangle[2]];
+            target.triangle(triangle, false, output, &shade);
+        }
+    }
+}
+
+impl Rasterizer {
+    /// An image `width` by `height` pixels, cleared to transparent black.
+    pub fn new(width: u32, height: u32) -> Self {
+        Rasterizer {
+            target: Target::new(width as usize, height as usize),
+            mesh: words(&MESH).map(f32::from_ne_bytes).collect(),
+            indices: words(&INDICES).map(u32::from_ne_bytes).collect(),
+            instances: Buffers::default(),
+            halos: Buffers::default(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.target.width as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.target.height as u32
+    }
+
+    /// Fills the image with `color`, as RGBA from 0 to 1, and clears the
+    /// depth buffer.
+    pub fn clear(&mut self, color: [f32; 4]) {
+        let color = Vec4::from(color);
+        self.target
+            .color
+            .iter_mut()
+            .for_each(|pixel| *pixel = color);
+        self.target.depth.iter_mut().for_each(|depth| *depth = 1.0);
+    }
+
+    /// The image as 8 bit RGBA, top row first.
+    pub fn rgba(&self) -> Vec<u8> {
+        self.target
+            .color
+            .iter()
+            .flat_map(|pixel| {
+                let channels: &[f32; 4] = pixel.as_ref();
+                let mut bytes = [0; 4];
+                for (byte, channel) in bytes.iter_mut().zip(channels) {
+                    *byte = (channel * 255.0).round() as u8;
+                }
+                bytes
+            })
+            .collect()
+    }
+}
+
+impl Renderer for Rasterizer {
+    fn upload(&mut self, instances: &Instances) {
+        self.instances = Buffers {
+            atoms: instances.atoms.clone(),
+            bonds: instances.bonds.clone(),
+            lines: instances.lines.clone(),
+        };
+    }
+
+    fn upload_halos(&mut self, halos: &Instances) {
+        self.halos = Buffers {
+            atoms: halos.atoms.clone(),
+            bonds: halos.bonds.clone(),
+            lines: Vec::new(),
+        };
+    }
+
+    /// The mesh shader, which lights atoms with a glint from the direction
+    /// the camera looks in.
+    fn draw_meshes(&mut self, view: &View, atom_count: i32, bond_count: i32) {
+        let Rasterizer {
+            target,
+            mesh,
+            indices,
+            instances,
+            ..
+        } = self;
+        let view_vector = view.view.transpose().transform_vector3(Vec3::unit_z());
+        let (spheres, cylinders) = indices.split_at(SPHERE_SIZE);
+        let draws = [
+            (&instances.atoms, atom_count, spheres, true),
+            (&instances.bonds, bond_count, cylinders, false),
+        ];
+        for &(buffer, count, indices, light) in draws.iter() {
+            let floats = instance_floats(MESH_LAYOUT);
+            for instance in buffer.chunks_exact(floats).take(count.max(0) as usize) {
+                let model = Mat4::from_cols(
+                    Vec4::from_slice_unaligned(&instance[0..]),
+                    Vec4::from_slice_unaligned(&instance[4..]),
+                    Vec4::from_slice_unaligned(&instance[8..]),
+                    Vec4::from_slice_unaligned(&instance[12..]),
+                );
+                let color = vec3(&instance[16..]);
+                let transform = view.projection * view.view * model;
+                let vertex = |index: u32| {
+                    let vertex = &mesh[index as usize * 6..];
+                    let (position, normal) = (vec3(vertex), vec3(&vertex[3..]));
+                    Vertex {
+                        clip: transform * position.extend(1.0),
+                        varyings: [-position.normalize(), normal],
+                    }
+                };
+                let shade = |varyings: &[Vec3; 2]| {
+                    let color = if light {
+                        let glint = varyings[0].dot(reflect(view_vector, varyings[1]).normalize());
+                        color * 0.1 * glint + color * 0.9
+                    } else {
+                        color
+                    };
+                    Some(Fragment {
+                        color: color.extend(1.0),
+                        depth: None,
+                    })
+                };
+                for triangle in indices.chunks_exact(3) {
+                    let triangle = [
+                        vertex(triangle[0]),
+                        vertex(triangle[1]),
+                        vertex(triangle[2]),
+                    ];
+                    target.triangle(triangle, true, OPAQUE, &shade);
+                }
+            }
+        }
+    }
+
+    fn draw_impostors(&mut self, view: &View, atom_count: i32, bond_count: i32) {
+        let counts = (atom_count, bond_count);
+        draw_impostor_buffers(&mut self.target, view, &self.instances, counts, 1.0, OPAQUE);
+    }
+
+    fn draw_lines(&mut self, view: &View, line_count: i32) {
+        let transform = view.projection * view.view;
+        let lines = self
+            .instances
+            .lines
+            .chunks_exact(instance_floats(LINE_LAYOUT));
+        for line in lines.take(line_count.max(0) as usize) {
+            let (start, end) = (vec3(line), vec3(&line[3..]));
+            let color = vec3(&line[6..]).extend(1.0);
+            self.target.line(
+                transform * start.extend(1.0),
+                transform * end.extend(1.0),
+                color,
+            );
+        }
+    }
+
+    fn draw_halos(&mut self, view: &View, counts: (i32, i32), alpha: f32) {
+        if counts == (0, 0) {
+            return;
+        }
+        draw_impostor_buffers(
+            &mut self.target,
+            view,
+            &self.halos,
+            counts,
+            alpha,
+            TRANSLUCENT,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colco::{Colco, Geometry, RenderSettings};
+    use crate::color::ColorScheme;
+    use crate::mol::{Atom, Mol};
+    use crate::representation::{Line, Shapes, Sphere};
+
+    /// Looking down -Z from the origin at a 4 by 4 square
+    fn orthographic() -> View {
+        View {
+            view: Mat4::identity(),
+            projection: Mat4::orthographic_rh_gl(-2.0, 2.0, -2.0, 2.0, 0.1, 10.0),
+            orthographic: true,
+        }
+    }
+
+    fn pixel(rasterizer: &Rasterizer, x: usize, y: usize) -> [u8; 4] {
+        let start = (y * rasterizer.width() as usize + x) * 4;
+        let rgba = rasterizer.rgba();
+        [
+            rgba[start],
+            rgba[start + 1],
+            rgba[start + 2],
+            rgba[start + 3],
+        ]
+    }
+
+    fn sphere(center: Vec3, color: Vec3) -> Sphere {
+        Sphere {
+            center,
+            radius: 1.0,
+            color,
+            atom: 0,
+        }
+    }
+
+    /// Draws `shapes` as impostors into a transparent 40 by 40 image.
+    fn draw(shapes: &Shapes) -> Rasterizer {
+        let mut rasterizer = Rasterizer::new(40, 40);
+        let instances = Instances::impostors(shapes);
+        rasterizer.upload(&instances);
+        let view = orthographic();
+        let counts = (instances.atom_count, instances.bond_count);
+        rasterizer.draw_impostors(&view, counts.0, counts.1);
+        rasterizer.draw_lines(&view, instances.line_count);
+        rasterizer
+    }
+
+    #[test]
+    fn draws_an_atom_on_the_clear_color() {
+        let mol = Mol::new(vec![Atom::new("O", Vec3::zero())], Vec::new());
+        let scheme = ColorScheme::default();
+        let color = scheme.colors(&mol)[0];
+        let mut colco = Colco::new(mol, &scheme);
+        let mut rasterizer = Rasterizer::new(33, 33);
+        rasterizer.clear([1.0, 1.0, 1.0, 1.0]);
+        let settings = RenderSettings {
+            geometry: Geometry::Impostor,
+            ..RenderSettings::default()
+        };
+        colco.render_mol(&settings, &mut rasterizer, 0.0);
+
+        let center = pixel(&rasterizer, 16, 16);
+        let expected: &[f32; 3] = color.as_ref();
+        for (&channel, &expected) in center.iter().zip(expected) {
+            assert!((channel as f32 - expected * 255.0).abs() <= 1.0);
+        }
+        assert_eq!(center[3], 255);
+        assert_eq!(pixel(&rasterizer, 0, 0), [255; 4]);
+    }
+
+    #[test]
+    fn draws_the_nearer_sphere_in_front() {
+        // Blue is nearer the camera, so wins wherever they overlap,
+        // whichever is drawn first
+        let far = (Vec3::new(-0.5, 0.0, -5.0), Vec3::unit_x());
+        let near = (Vec3::new(0.5, 0.0, -4.0), Vec3::unit_z());
+        for &(first, second) in [(far, near), (near, far)].iter() {
+            let shapes = Shapes {
+                spheres: vec![sphere(first.0, first.1), sphere(second.0, second.1)],
+                ..Shapes::default()
+            };
+            let rasterizer = draw(&shapes);
+            // Shading dims both a little away from their centers
+            let [red, _, blue, _] = pixel(&rasterizer, 20, 20);
+            assert!(red == 0 && blue > 200);
+            let [red, _, blue, _] = pixel(&rasterizer, 11, 20);
+            assert!(red > 200 && blue == 0);
+        }
+    }
+
+    #[test]
+    fn clips_triangles_at_the_near_plane() {
+        let projection = Mat4::perspective_rh_gl(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 10.0);
+        let vertex = |x: f32, y: f32, z: f32| Vertex {
+            clip: projection * Vec4::new(x, y, z, 1.0),
+            varyings: [Vec3::zero(); 2],
+        };
+        let shade = |_: &[Vec3; 2]| {
+            Some(Fragment {
+                color: Vec4::one(),
+                depth: None,
+            })
+        };
+        let filled = |target: &Target, ndc_y: f32| {
+            let (x, y) = target.to_window(Vec3::new(0.0, ndc_y, 0.0));
+            target.color[y as usize * target.width + x as usize] == Vec4::one()
+        };
+
+        // The third corner is behind the camera. What's in front of the near
+        // plane lands between -0.5 and 0 on screen, but projecting the whole
+        // triangle would flip that corner below it.
+        let mut target = Target::new(40, 40);
+        let triangle = [
+            vertex(-1.0, -1.0, -2.0),
+            vertex(1.0, -1.0, -2.0),
+            vertex(0.0, 3.0, 2.0),
+        ];
+        target.triangle(triangle, false, OPAQUE, &shade);
+        assert!(filled(&target, -0.25));
+        assert!(!filled(&target, -0.75));
+        assert!(!filled(&target, 0.5));
+
+        let mut target = Target::new(40, 40);
+        let behind = [
+            vertex(-1.0, -1.0, 2.0),
+            vertex(1.0, -1.0, 2.0),
+            vertex(0.0, 1.0, 2.0),
+        ];
+        target.triangle(behind, false, OPAQUE, &shade);
+        assert!(target.color.iter().all(|&color| color == Vec4::zero()));
+    }
+
+    #[test]
+    fn writes_the_top_row_first() {
+        // A line across the top half of the view
+        let line = Line {
+            start: Vec3::new(-2.0, 1.0, -5.0),
+            end: Vec3::new(2.0, 1.0, -5.0),
+            color: Vec3::unit_x(),
+            atom: 0,
+            bond: None,
+        };
+        let shapes = Shapes {
+            lines: vec![line],
+            ..Shapes::default()
+        };
+        let rasterizer = draw(&shapes);
+        assert_eq!(pixel(&rasterizer, 20, 10), [255, 0, 0, 255]);
+        assert_eq!(pixel(&rasterizer, 20, 30), [0; 4]);
+    }
+}
@@ -0,0 +1,466 @@
+//! Representations: the ways a molecule can be turned into spheres, cylinders
+//! and lines. Shapes are in world space, where coordinates are 4.5 times the
+//! file's ångströms, and are packed for the GPU by `Instances`.
+//!
+//! Different parts of a molecule can be drawn differently by giving the render
+//! settings a list of layers, each pairing a selection with a representation,
+//! color scheme and sizes.
+use crate::colco::RenderSettings;
+use crate::color::ColorScheme;
+use crate::mol::{Atom, Mol, PERIODIC_TABLE};
+use crate::selection::Selection;
+use glam::Vec3;
+use serde::Deserialize;
+
+/// World units per ångström
+pub const WORLD_SCALE: f32 = 4.5;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Representation {
+    /// Atoms sized by `atom_size`, with one rod per bond order
+    #[default]
+    BallAndStick,
+    /// Atoms at their van der Waals radii, without bonds
+    SpaceFilling,
+    /// Atoms and bonds both `bond_size` wide
+    Licorice,
+    /// Bonds as single-pixel lines
+    Wireframe,
+    /// Bonds `bond_size` wide, without atoms
+    Sticks,
+}
+
+/// One part of a scene. Unset fields fall back to the render settings and the
+/// current color scheme.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Layer {
+    pub selection: Selection,
+    pub representation: Option<Representation>,
+    pub color_scheme: Option<ColorScheme>,
+    pub atom_size: Option<f32>,
+    pub bond_size: Option<f32>,
+}
+
+/// How highlighted atoms and bonds stand out: each gets a translucent halo
+/// `width` world units thicker than its shape.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Highlight {
+    pub color: [f32; 3],
+    pub width: f32,
+    pub opacity: f32,
+}
+
+impl Default for Highlight {
+    fn default() -> Self {
+        Highlight {
+            color: [1.0, 0.85, 0.2],
+            width: 0.6,
+            opacity: 0.45,
+        }
+    }
+}
+
+/// A representation with its sizes settled.
+struct Style {
+    representation: Representation,
+    atom_size: f32,
+    bond_size: f32,
+}
+
+// Every shape remembers the atom it is colored after, and the bond it is part
+// of if any, so picking can tell what was hit.
+
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+    pub color: Vec3,
+    pub atom: usize,
+}
+
+pub struct Cylinder {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: f32,
+    pub color: Vec3,
+    pub atom: usize,
+    pub bond: usize,
+}
+
+pub struct Line {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub color: Vec3,
+    pub atom: usize,
+    pub bond: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct Shapes {
+    pub spheres: Vec<Sphere>,
+    pub cylinders: Vec<Cylinder>,
+    pub lines: Vec<Line>,
+}
+
+impl Shapes {
+    /// Builds every layer of `settings`, or the whole molecule in the
+    /// settings' representation when there are none. `colors` are the current
+    /// color scheme's.
+    pub fn new(mol: &Mol, colors: &[Vec3], settings: &RenderSettings) -> Self {
+        let mut shapes = Shapes::default();
+        if settings.layers.is_empty() {
+            let style = Style {
+                representation: settings.representation,
+                atom_size: settings.atom_size,
+                bond_size: settings.bond_size,
+            };
+            let selected = vec![true; mol.atoms.len()];
+            shapes.add(mol, colors, &selected, &style);
+        }
+        for layer in &settings.layers {
+            let style = Style {
+                representation: layer.representation.unwrap_or(settings.representation),
+                atom_size: layer.atom_size.unwrap_or(settings.atom_size),
+                bond_size: layer.bond_size.unwrap_or(settings.bond_size),
+            };
+            let selected = layer.selection.atoms(mol);
+            match &layer.color_scheme {
+                Some(scheme) => shapes.add(mol, &scheme.colors(mol), &selected, &style),
+                None => shapes.add(mol, colors, &selected, &style),
+            }
+        }
+        shapes
+    }
+
+    /// Adds the `selected` atoms, and bonds between two of them.
+    fn add(&mut self, mol: &Mol, colors: &[Vec3], selected: &[bool], style: &Style) {
+        let bond_size = style.bond_size;
+        match style.representation {
+            Representation::BallAndStick => {
+                self.add_atoms(mol, colors, selected, |atom| {
+                    atom.element.scale * style.atom_size
+                });
+                self.add_bonds(mol, colors, selected, bond_size, true);
+            }
+            Representation::SpaceFilling => {
+                let carbon = PERIODIC_TABLE[5].vdw_radius;
+                self.add_atoms(mol, colors, selected, |atom| {
+                    atom.element.data().map_or(carbon, |data| data.vdw_radius) * WORLD_SCALE
+                });
+            }
+            Representation::Licorice => {
+                self.add_atoms(mol, colors, selected, |_| bond_size);
+                self.add_bonds(mol, colors, selected, bond_size, false);
+            }
+            Representation::Wireframe => self.add_lines(mol, colors, selected),
+            Representation::Sticks => self.add_bonds(mol, colors, selected, bond_size, false),
+        }
+    }
+
+    fn add_atoms(
+        &mut self,
+        mol: &Mol,
+        colors: &[Vec3],
+        selected: &[bool],
+        radius: impl Fn(&Atom) -> f32,
+    ) {
+        let atoms = mol.atoms.iter().enumerate();
+        for (index, atom) in atoms.filter(|&(index, _)| selected[index]) {
+            self.spheres.push(Sphere {
+                center: atom.position * WORLD_SCALE,
+                radius: radius(atom),
+                color: colors[index],
+                atom: index,
+            });
+        }
+    }
+
+    /// Adds two cylinders per bond, one in each atom's color. With `multiple`,
+    /// double and triple bonds get side by side rods sharing `width`.
+    fn add_bonds(
+        &mut self,
+        mol: &Mol,
+        colors: &[Vec3],
+        selected: &[bool],
+        width: f32,
+        multiple: bool,
+    ) {
+        let bonds = mol.bonds.iter().enumerate();
+        for (index, bond) in
+            bonds.filter(|(_, bond)| selected[bond.first_atom] && selected[bond.second_atom])
+        {
+            let start = mol.atoms[bond.first_atom].position * WORLD_SCALE;
+            let end = mol.atoms[bond.second_atom].position * WORLD_SCALE;
+            let middle = (start + end) * 0.5;
+            let side = bond.rotation * Vec3::unit_x();
+            let order = if multiple { bond.bond_type.max(1) } else { 1 };
+            for line in 0..order {
+                let shift = side * (line as f32 - 0.5 * (order - 1) as f32);
+                let halves = [
+                    (start, middle, bond.first_atom),
+                    (middle, end, bond.second_atom),
+                ];
+                for &(from, to, atom) in halves.iter() {
+                    self.cylinders.push(Cylinder {
+                        start: from + shift,
+                        end: to + shift,
+                        radius: width / order as f32,
+                        color: colors[atom],
+                        atom,
+                        bond: index,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Adds a two-colored line per bond, and a small cross on each atom
+    /// without bonds so lone ions and waters still show up.
+    fn add_lines(&mut self, mol: &Mol, colors: &[Vec3], selected: &[bool]) {
+        let mut bonded = vec![false; mol.atoms.len()];
+        let bonds = mol.bonds.iter().enumerate();
+        for (index, bond) in
+            bonds.filter(|(_, bond)| selected[bond.first_atom] && selected[bond.second_atom])
+        {
+            let start = mol.atoms[bond.first_atom].position * WORLD_SCALE;
+            let end = mol.atoms[bond.second_atom].position * WORLD_SCALE;
+            let middle = (start + end) * 0.5;
+            self.lines.push(Line {
+                start,
+                end: middle,
+                color: colors[bond.first_atom],
+                atom: bond.first_atom,
+                bond: Some(index),
+            });
+            self.lines.push(Line {
+                start: middle,
+                end,
+                color: colors[bond.second_atom],
+                atom: bond.second_atom,
+                bond: Some(index),
+            });
+            bonded[bond.first_atom] = true;
+            bonded[bond.second_atom] = true;
+        }
+        let arms = [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()];
+        for (index, atom) in mol.atoms.iter().enumerate() {
+            if bonded[index] || !selected[index] {
+                continue;
+            }
+            let center = atom.position * WORLD_SCALE;
+            for &arm in arms.iter() {
+                let arm = arm * 0.25 * WORLD_SCALE;
+                self.lines.push(Line {
+                    start: center - arm,
+                    end: center + arm,
+                    color: colors[index],
+                    atom: index,
+                    bond: None,
+                });
+            }
+        }
+    }
+}
+
+impl Shapes {
+    /// Halos around the shapes of `highlighted` atoms, and of bonds between
+    /// two of them. Highlighted atoms drawn without a sphere, such as in
+    /// sticks and wireframes, get a round halo as wide as their thickest bond,
+    /// and atoms that aren't drawn at all get none.
+    pub fn halos(&self, mol: &Mol, highlighted: &[bool], highlight: &Highlight) -> Shapes {
+        let color = Vec3::new(highlight.color[0], highlight.color[1], highlight.color[2]);
+        let width = highlight.width;
+        let bond_highlighted = |bond: usize| {
+            let bond = &mol.bonds[bond];
+            highlighted[bond.first_atom] && highlighted[bond.second_atom]
+        };
+        let mut halos = Shapes::default();
+        let mut has_sphere = vec![false; mol.atoms.len()];
+        // The thickest bond at each atom, for atoms without a sphere
+        let mut thickest = vec![0.0f32; mol.atoms.len()];
+        let mut drawn = vec![false; mol.atoms.len()];
+        for line in &self.lines {
+            drawn[line.atom] = true;
+        }
+
+        for sphere in self
+            .spheres
+            .iter()
+            .filter(|sphere| highlighted[sphere.atom])
+        {
+            has_sphere[sphere.atom] = true;
+            halos.spheres.push(Sphere {
+                radius: sphere.radius + width,
+                color,
+                ..*sphere
+            });
+        }
+        let lines = self.lines.iter().filter_map(|line| {
+            let bond = line.bond?;
+            Some((line.start, line.end, 0.0, line.atom, bond))
+        });
+        let cylinders = self.cylinders.iter().map(|cylinder| {
+            let Cylinder {
+                start,
+                end,
+                radius,
+                atom,
+                bond,
+                ..
+            } = *cylinder;
+            (start, end, radius, atom, bond)
+        });
+        for (start, end, radius, atom, bond) in cylinders.chain(lines) {
+            drawn[atom] = true;
+            if !bond_highlighted(bond) {
+                continue;
+            }
+            thickest[atom] = thickest[atom].max(radius);
+            halos.cylinders.push(Cylinder {
+                start,
+                end,
+                radius: radius + width,
+                color,
+                atom,
+                bond,
+            });
+        }
+        for (index, atom) in mol.atoms.iter().enumerate() {
+            if highlighted[index] && drawn[index] && !has_sphere[index] {
+                halos.spheres.push(Sphere {
+                    center: atom.position * WORLD_SCALE,
+                    radius: thickest[index] + width,
+                    color,
+                    atom: index,
+                });
+            }
+        }
+        halos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mol::Bond;
+
+    /// Formaldehyde's carbon and oxygen, double bonded
+    fn mol() -> Mol {
+        let atoms = vec![
+            Atom::new("C", Vec3::zero()),
+            Atom::new("O", Vec3::new(1.2, 0.0, 0.0)),
+        ];
+        let bonds = vec![Bond::new(0, 1, 2, &atoms)];
+        Mol::new(atoms, bonds)
+    }
+
+    fn shapes(mol: &Mol, representation: Representation) -> Shapes {
+        let settings = RenderSettings {
+            representation,
+            ..RenderSettings::default()
+        };
+        Shapes::new(mol, &[Vec3::one(); 2], &settings)
+    }
+
+    fn counts(shapes: &Shapes) -> (usize, usize, usize) {
+        (
+            shapes.spheres.len(),
+            shapes.cylinders.len(),
+            shapes.lines.len(),
+        )
+    }
+
+    #[test]
+    fn emits_each_representations_shapes() {
+        let mol = mol();
+        // A rod per line of the double bond, each in two halves
+        let ball_and_stick = shapes(&mol, Representation::BallAndStick);
+        assert_eq!(counts(&ball_and_stick), (2, 4, 0));
+        assert_eq!(
+            counts(&shapes(&mol, Representation::SpaceFilling)),
+            (2, 0, 0)
+        );
+        assert_eq!(counts(&shapes(&mol, Representation::Licorice)), (2, 2, 0));
+        assert_eq!(counts(&shapes(&mol, Representation::Wireframe)), (0, 0, 2));
+        assert_eq!(counts(&shapes(&mol, Representation::Sticks)), (0, 2, 0));
+    }
+
+    #[test]
+    fn sizes_shapes_from_the_settings() {
+        let mol = mol();
+        let settings = RenderSettings::default();
+        let ball_and_stick = shapes(&mol, Representation::BallAndStick);
+        let oxygen = mol.atoms[1].element.scale * settings.atom_size;
+        assert_eq!(ball_and_stick.spheres[1].radius, oxygen);
+        // The double bond's two rods share its width
+        assert_eq!(ball_and_stick.cylinders[0].radius, settings.bond_size / 2.0);
+
+        let licorice = shapes(&mol, Representation::Licorice);
+        let radii = licorice.spheres.iter().map(|sphere| sphere.radius);
+        assert!(radii
+            .chain(licorice.cylinders.iter().map(|cylinder| cylinder.radius))
+            .all(|radius| radius == settings.bond_size));
+    }
+
+    #[test]
+    fn fills_space_at_van_der_waals_radii() {
+        let mol = mol();
+        let space_filling = shapes(&mol, Representation::SpaceFilling);
+        let (carbon, oxygen) = (&space_filling.spheres[0], &space_filling.spheres[1]);
+        let scale = mol.atoms[1].element.scale;
+        assert!((oxygen.radius / carbon.radius - scale).abs() < 1e-6);
+        assert!((carbon.radius - 1.7 * WORLD_SCALE).abs() < 1e-6);
+    }
+
+    #[test]
+    fn draws_wireframes_as_bond_lines() {
+        let wireframe = shapes(&mol(), Representation::Wireframe);
+        for (line, atom) in wireframe.lines.iter().zip(0..) {
+            assert_eq!((line.atom, line.bond), (atom, Some(0)));
+        }
+        // A lone atom gets a cross of three lines, which aren't bonds
+        let lone = Mol::new(vec![Atom::new("Na", Vec3::zero())], Vec::new());
+        let lines = shapes(&lone, Representation::Wireframe).lines;
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.bond.is_none()));
+    }
+
+    #[test]
+    fn haloes_highlighted_spheres() {
+        let mol = mol();
+        let highlight = Highlight::default();
+        let ball_and_stick = shapes(&mol, Representation::BallAndStick);
+        let halos = ball_and_stick.halos(&mol, &[true, false], &highlight);
+        assert_eq!(halos.spheres.len(), 1);
+        let (halo, sphere) = (&halos.spheres[0], &ball_and_stick.spheres[0]);
+        assert_eq!(halo.atom, 0);
+        assert_eq!(halo.radius, sphere.radius + highlight.width);
+        assert_eq!(halo.color, Vec3::from(highlight.color));
+        // The bond is only half highlighted
+        assert!(halos.cylinders.is_empty());
+
+        let halos = ball_and_stick.halos(&mol, &[true, true], &highlight);
+        assert_eq!(counts(&halos), (2, 4, 0));
+        let (halo, cylinder) = (&halos.cylinders[0], &ball_and_stick.cylinders[0]);
+        assert_eq!(halo.radius, cylinder.radius + highlight.width);
+    }
+
+    #[test]
+    fn haloes_atoms_drawn_without_spheres() {
+        let mol = mol();
+        let highlight = Highlight::default();
+        let wireframe = shapes(&mol, Representation::Wireframe);
+        let halos = wireframe.halos(&mol, &[true, false], &highlight);
+        assert_eq!(counts(&halos), (1, 0, 0));
+        assert_eq!(halos.spheres[0].radius, highlight.width);
+
+        // Sticks' atoms are as wide as their bonds
+        let sticks = shapes(&mol, Representation::Sticks);
+        let halos = sticks.halos(&mol, &[true, true], &highlight);
+        assert_eq!(counts(&halos), (2, 2, 0));
+        let width = RenderSettings::default().bond_size + highlight.width;
+        assert!(halos.spheres.iter().all(|halo| halo.radius == width));
+    }
+}
@@ -0,0 +1,496 @@
+//! Selections, which pick out a subset of a molecule's atoms with a small query
+//! language, so parts of it can be drawn, colored or measured on their own.
+//!
+//! Queries combine terms with `and`, `or`, `not` and parentheses, where `not`
+//! binds tightest and `or` loosest:
+//!
+//! - `all`, `none`, `hydrogen`, `water` and `hetero`
+//! - `element C N`, `name CA`, `resname LIG HOH` and `chain A B`, which match
+//!   any of the values after them. Elements ignore case.
+//! - `resi 10-50`, `index 1-20` and `serial 100`, which take numbers or
+//!   inclusive ranges. Indices count atoms in file order from 0.
+//! - `within 5 of resname LIG`, the atoms within 5 ångströms of another
+//!   selection, including that selection itself
+use crate::mol::{Grid, Mol};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// Why a query couldn't be read. Columns are 1-based character positions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectionError {
+    /// A word that doesn't belong where it was found
+    Unexpected {
+        column: usize,
+        expected: &'static str,
+        found: String,
+    },
+    /// The query ended partway through a term
+    UnexpectedEnd { expected: &'static str },
+}
+
+impl SelectionError {
+    pub fn column(&self) -> Option<usize> {
+        match *self {
+            SelectionError::Unexpected { column, .. } => Some(column),
+            SelectionError::UnexpectedEnd { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectionError::Unexpected {
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "column {}: expected {}, found \"{}\"",
+                column, expected, found
+            ),
+            SelectionError::UnexpectedEnd { expected } => {
+                write!(f, "expected {} at the end of the selection", expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SelectionError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    All,
+    Nothing,
+    Hydrogen,
+    Water,
+    Hetero,
+    Element(Vec<String>),
+    Name(Vec<String>),
+    ResidueName(Vec<String>),
+    Chain(Vec<String>),
+    ResidueNumber(Vec<(i64, i64)>),
+    Index(Vec<(i64, i64)>),
+    Serial(Vec<(i64, i64)>),
+    Within(f32, Box<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// A parsed query. Selections deserialize from their query text, so layers
+/// can be given as `{ selection: "chain A and not water" }`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Selection {
+    query: String,
+    expr: Expr,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Selection {
+            query: "all".to_string(),
+            expr: Expr::All,
+        }
+    }
+}
+
+/// Words that end a list of values
+const KEYWORDS: [&str; 3] = ["and", "or", "not"];
+
+struct Parser<'a> {
+    /// Each word or parenthesis with the column it starts at
+    tokens: Vec<(usize, &'a str)>,
+    next: usize,
+}
+
+fn tokenize(query: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (offset, character) in query.char_indices() {
+        let separate = character.is_whitespace() || character == '(' || character == ')';
+        if separate {
+            if let Some(start) = start.take() {
+                tokens.push((start, &query[start..offset]));
+            }
+            if !character.is_whitespace() {
+                tokens.push((offset, &query[offset..offset + 1]));
+            }
+        } else if start.is_none() {
+            start = Some(offset);
+        }
+    }
+    if let Some(start) = start {
+        tokens.push((start, &query[start..]));
+    }
+    tokens
+        .into_iter()
+        .map(|(offset, token)| (query[..offset].chars().count() + 1, token))
+        .collect()
+}
+
+/// Reads `10` or an inclusive range such as `10-50` or `10:50`. The first
+/// character is skipped when looking for the separator so negative residue
+/// numbers like `-5-10` still read.
+fn parse_range(word: &str) -> Option<(i64, i64)> {
+    if let Ok(number) = word.parse() {
+        return Some((number, number));
+    }
+    let first = word.chars().next()?.len_utf8();
+    let separator = first + word[first..].find(&['-', ':'][..])?;
+    let low = word[..separator].parse().ok()?;
+    let high = word[separator + 1..].parse().ok()?;
+    Some((low, high))
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.next).map(|&(_, token)| token)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|token| token.eq_ignore_ascii_case(keyword))
+    }
+
+    fn take(&mut self, expected: &'static str) -> Result<(usize, &'a str), SelectionError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .copied()
+            .ok_or(SelectionError::UnexpectedEnd { expected })?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn unexpected(&self, (column, found): (usize, &str), expected: &'static str) -> SelectionError {
+        SelectionError::Unexpected {
+            column,
+            expected,
+            found: found.to_string(),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, SelectionError> {
+        let mut expr = self.and()?;
+        while self.peek_keyword("or") {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, SelectionError> {
+        let mut expr = self.unary()?;
+        while self.peek_keyword("and") {
+            self.next += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, SelectionError> {
+        let token = self.take("a selection")?;
+        let keyword = token.1.to_ascii_lowercase();
+        Ok(match keyword.as_str() {
+            "(" => {
+                let expr = self.or()?;
+                let close = self.take("\")\"")?;
+                if close.1 != ")" {
+                    return Err(self.unexpected(close, "\")\""));
+                }
+                expr
+            }
+            "not" => Expr::Not(Box::new(self.unary()?)),
+            "all" => Expr::All,
+            "none" => Expr::Nothing,
+            "hydrogen" => Expr::Hydrogen,
+            "water" => Expr::Water,
+            "hetero" | "hetatm" => Expr::Hetero,
+            "element" | "elem" => Expr::Element(self.words()?),
+            "name" => Expr::Name(self.words()?),
+            "resname" | "resn" => Expr::ResidueName(self.words()?),
+            "chain" => Expr::Chain(self.words()?),
+            "resi" | "resid" => Expr::ResidueNumber(self.ranges()?),
+            "index" => Expr::Index(self.ranges()?),
+            "serial" => Expr::Serial(self.ranges()?),
+            "within" => {
+                let distance = self.take("a distance")?;
+                let distance = match distance.1.parse::<f32>() {
+                    Ok(value) if value >= 0.0 => value,
+                    _ => return Err(self.unexpected(distance, "a distance")),
+                };
+                let of = self.take("\"of\"")?;
+                if !of.1.eq_ignore_ascii_case("of") {
+                    return Err(self.unexpected(of, "\"of\""));
+                }
+                Expr::Within(distance, Box::new(self.unary()?))
+            }
+            _ => return Err(self.unexpected(token, "a selection")),
+        })
+    }
+
+    /// Reads the values after a property, up to the next keyword or
+    /// parenthesis. At least one is required.
+    fn words(&mut self) -> Result<Vec<String>, SelectionError> {
+        let mut words = Vec::new();
+        while let Some(token) = self.peek() {
+            let ends = token == "(" || token == ")";
+            if ends
+                || KEYWORDS
+                    .iter()
+                    .any(|keyword| token.eq_ignore_ascii_case(keyword))
+            {
+                break;
+            }
+            words.push(token.to_string());
+            self.next += 1;
+        }
+        if words.is_empty() {
+            let expected = "a value";
+            return Err(match self.tokens.get(self.next) {
+                Some(&token) => self.unexpected(token, expected),
+                None => SelectionError::UnexpectedEnd { expected },
+            });
+        }
+        Ok(words)
+    }
+
+    fn ranges(&mut self) -> Result<Vec<(i64, i64)>, SelectionError> {
+        let start = self.next;
+        self.words()?
+            .iter()
+            .enumerate()
+            .map(|(offset, word)| {
+                parse_range(word).ok_or_else(|| {
+                    self.unexpected(self.tokens[start + offset], "a number or range")
+                })
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Selection {
+    type Err = SelectionError;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(query),
+            next: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(&token) = parser.tokens.get(parser.next) {
+            return Err(parser.unexpected(token, "\"and\" or \"or\""));
+        }
+        Ok(Selection {
+            query: query.to_string(),
+            expr,
+        })
+    }
+}
+
+impl TryFrom<String> for Selection {
+    type Error = SelectionError;
+
+    fn try_from(query: String) -> Result<Self, Self::Error> {
+        query.parse()
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.query)
+    }
+}
+
+fn in_ranges(ranges: &[(i64, i64)], value: Option<i64>) -> bool {
+    value.is_some_and(|value| {
+        ranges
+            .iter()
+            .any(|&(low, high)| low <= value && value <= high)
+    })
+}
+
+fn matches(values: &[String], value: Option<&String>) -> bool {
+    value.is_some_and(|value| values.contains(value))
+}
+
+/// Marks every atom within `distance` of a `target` atom. Targets are binned
+/// into cells `distance` wide, so each atom only checks the 27 cells around it.
+fn within(mol: &Mol, distance: f32, target: &[bool]) -> Vec<bool> {
+    let targets = (0..mol.atoms.len()).filter(|&index| target[index]);
+    let grid = Grid::new(&mol.atoms, targets, distance.max(0.1));
+    mol.atoms
+        .iter()
+        .map(|atom| {
+            grid.near(atom.position)
+                .any(|other| (mol.atoms[other].position - atom.position).length() <= distance)
+        })
+        .collect()
+}
+
+impl Expr {
+    fn atoms(&self, mol: &Mol) -> Vec<bool> {
+        let each = |test: &dyn Fn(usize) -> bool| (0..mol.atoms.len()).map(test).collect();
+        let atom = |index: usize| &mol.atoms[index];
+        match self {
+            Expr::All => vec![true; mol.atoms.len()],
+            Expr::Nothing => vec![false; mol.atoms.len()],
+            Expr::Hydrogen => each(&|index| atom(index).element.atomic_number == 1),
+            Expr::Water => each(&|index| match atom(index).residue_name.as_deref() {
+                Some(residue) => ["HOH", "WAT", "DOD", "H2O"].contains(&residue),
+                None => false,
+            }),
+            Expr::Hetero => each(&|index| atom(index).hetero),
+            Expr::Element(symbols) => each(&|index| {
+                let symbol = &atom(index).symbol;
+                symbols
+                    .iter()
+                    .any(|other| other.eq_ignore_ascii_case(symbol))
+            }),
+            Expr::Name(names) => each(&|index| matches(names, atom(index).name.as_ref())),
+            Expr::ResidueName(residues) => {
+                each(&|index| matches(residues, atom(index).residue_name.as_ref()))
+            }
+            Expr::Chain(chains) => each(&|index| matches(chains, atom(index).chain_id.as_ref())),
+            Expr::ResidueNumber(ranges) => {
+                each(&|index| in_ranges(ranges, atom(index).residue_number.map(i64::from)))
+            }
+            Expr::Index(ranges) => each(&|index| in_ranges(ranges, Some(index as i64))),
+            Expr::Serial(ranges) => {
+                each(&|index| in_ranges(ranges, atom(index).serial.map(i64::from)))
+            }
+            Expr::Within(distance, target) => within(mol, *distance, &target.atoms(mol)),
+            Expr::Not(expr) => expr.atoms(mol).into_iter().map(|atom| !atom).collect(),
+            Expr::And(left, right) => {
+                let right = right.atoms(mol);
+                let left = left.atoms(mol);
+                left.into_iter().zip(right).map(|(a, b)| a && b).collect()
+            }
+            Expr::Or(left, right) => {
+                let right = right.atoms(mol);
+                let left = left.atoms(mol);
+                left.into_iter().zip(right).map(|(a, b)| a || b).collect()
+            }
+        }
+    }
+}
+
+impl Selection {
+    /// Returns whether each atom is selected, in atom order.
+    pub fn atoms(&self, mol: &Mol) -> Vec<bool> {
+        self.expr.atoms(mol)
+    }
+
+    /// Returns the indices of the selected atoms, in increasing order.
+    pub fn indices(&self, mol: &Mol) -> Vec<usize> {
+        let selected = self.atoms(mol).into_iter().enumerate();
+        selected
+            .filter(|&(_, selected)| selected)
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mol::Atom;
+    use glam::Vec3;
+
+    /// Two residues in different chains, a ligand and a water
+    const PDB: &str = "\
+ATOM      1  N   ALA A  10      0.000   0.000   0.000  1.00  0.00           N  
+ATOM      2  CA  ALA A  10      1.450   0.000   0.000  1.00  0.00           C  
+ATOM      3  H   ALA A  10     -1.000   0.000   0.000  1.00  0.00           H  
+ATOM      4  CA  GLY B  60     30.000   0.000   0.000  1.00  0.00           C  
+HETATM    5  C1  LIG A 101      5.000   0.000   0.000  1.00 20.00           C  
+HETATM    6  O   HOH A 201      9.000   0.000   0.000  1.00 20.00           O  
+END
+";
+
+    fn select(query: &str) -> Vec<usize> {
+        let mol = Mol::from_str(PDB).expect("a molecule");
+        query.parse::<Selection>().unwrap().indices(&mol)
+    }
+
+    fn expr(query: &str) -> Expr {
+        query.parse::<Selection>().unwrap().expr
+    }
+
+    #[test]
+    fn binds_not_then_and_then_or() {
+        assert_eq!(
+            expr("water or hetero and not hydrogen"),
+            expr("water or (hetero and (not hydrogen))")
+        );
+        assert_eq!(expr("not water and hetero"), expr("(not water) and hetero"));
+        assert_eq!(
+            expr("hetero and water or hydrogen"),
+            expr("(hetero and water) or hydrogen")
+        );
+        assert_eq!(select("chain A and name CA or chain B"), [1, 3]);
+        assert_eq!(select("chain A and (name CA or chain B)"), [1]);
+        assert_eq!(select("NOT (chain A or hetero)"), [3]);
+        assert_eq!(select("not hydrogen and not water"), [0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn matches_properties() {
+        assert_eq!(select("all"), [0, 1, 2, 3, 4, 5]);
+        assert!(select("none").is_empty());
+        assert_eq!(select("element c n"), [0, 1, 3, 4]);
+        assert_eq!(select("name CA or serial 6"), [1, 3, 5]);
+        assert_eq!(select("resname ALA LIG"), [0, 1, 2, 4]);
+        assert_eq!(select("water"), [5]);
+    }
+
+    #[test]
+    fn matches_ranges() {
+        assert_eq!(select("chain A and resi 10-50"), [0, 1, 2]);
+        assert_eq!(select("resi 60:101"), [3, 4]);
+        assert_eq!(select("resi -5-10"), [0, 1, 2]);
+        assert_eq!(select("index 1-2 4"), [1, 2, 4]);
+    }
+
+    #[test]
+    fn selects_within_a_distance() {
+        assert_eq!(select("element C and within 5 of resname LIG"), [1, 4]);
+        assert!(select("within 0 of none").is_empty());
+    }
+
+    #[test]
+    fn locates_bad_queries() {
+        let error = |query: &str| Selection::from_str(query).unwrap_err();
+        assert_eq!(
+            error("chain A and"),
+            SelectionError::UnexpectedEnd {
+                expected: "a selection"
+            }
+        );
+        assert_eq!(error("resi ten").column(), Some(6));
+        assert_eq!(error("chain A)").column(), Some(8));
+        assert_eq!(error("within x of all").column(), Some(8));
+        assert_eq!(
+            error("(chain A").to_string(),
+            "expected \")\" at the end of the selection"
+        );
+    }
+
+    #[test]
+    fn finds_atoms_within_stray_coordinates() {
+        let positions = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1e12, -1e12, 3e38),
+            Vec3::new(f32::NAN, 0.0, 0.0),
+        ];
+        let atoms = positions.iter().map(|&position| Atom::new("C", position));
+        let mol = Mol::new(atoms.collect(), Vec::new());
+        let selection: Selection = "within 2 of index 0".parse().unwrap();
+        assert_eq!(selection.indices(&mol), [0, 1]);
+    }
+}